@@ -0,0 +1,56 @@
+//! What a bus access and an instruction cost, `cargo bench --bench bus`.
+//! There is no harness: each case is a timed loop, printed as nanoseconds
+//! an iteration.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use nesemu::bus::Bus;
+use nesemu::cpu::CPU;
+
+const ACCESSES: u32 = 50_000_000;
+const INSTRUCTIONS: u64 = 10_000_000;
+
+fn time(name: &str, iterations: u64, f: impl FnOnce()) {
+    let start = Instant::now();
+    f();
+    let ns = start.elapsed().as_nanos() as f64 / iterations as f64;
+    println!("{:<20} {:>8.2} ns", name, ns);
+}
+
+fn main() {
+    let mut bus = Bus::default();
+    time("bus read", ACCESSES as u64, || {
+        let mut sum = 0_u8;
+        for i in 0..ACCESSES {
+            sum = sum.wrapping_add(bus.read(black_box(i as u16)));
+        }
+        black_box(sum);
+    });
+    time("bus write", ACCESSES as u64, || {
+        for i in 0..ACCESSES {
+            bus.write(black_box(i as u16), i as u8);
+        }
+    });
+    time("bus read, logged", 1_000_000, || {
+        bus.start_log();
+        for i in 0..1_000_000_u32 {
+            black_box(bus.read(black_box(i as u16)));
+        }
+        black_box(bus.take_log());
+    });
+
+    let mut cpu = CPU::new(Bus::default());
+    cpu.load(vec![
+        0xe8, // $0600 INX
+        0x9d, 0x00, 0x02, // $0601 STA $0200,X
+        0xb5, 0x10, // $0604 LDA $10,X
+        0x4c, 0x00, 0x06, // $0606 JMP $0600
+    ]);
+    time("instruction", INSTRUCTIONS, || {
+        for _ in 0..INSTRUCTIONS {
+            cpu.exec();
+        }
+    });
+    black_box(cpu.cycles);
+}
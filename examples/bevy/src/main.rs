@@ -0,0 +1,107 @@
+//! The snake demo running inside a Bevy app, its display a texture on a
+//! sprite, to show the core embedded in a game engine. `cargo run` in this
+//! directory; WASD steer.
+
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use nesemu::screen::{Display, FrameSource, PixelFormat, Screen};
+use nesemu::{demo, script, Bus, CPU};
+
+/// Instructions run each frame, roughly the SDL front-end's pace.
+const STEPS_PER_FRAME: usize = 200;
+
+/// The machine and its display. The CPU's trace writer isn't `Sync`, so
+/// this lives in the world as a non-send resource.
+struct Machine {
+    cpu: CPU,
+    display: Display,
+    seed: u64,
+}
+
+/// The texture the display is copied into.
+#[derive(Resource)]
+struct Frame(Handle<Image>);
+
+fn main() {
+    let mut cpu = CPU::new(Bus::default());
+    cpu.stop_on_brk = true;
+    demo::find("snake").unwrap().load(&mut cpu);
+
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .insert_non_send_resource(Machine {
+            cpu,
+            display: Display::new(PixelFormat::Rgba8888),
+            seed: 1,
+        })
+        .add_systems(Startup, setup)
+        .add_systems(Update, run)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, machine: NonSend<Machine>) {
+    let (width, height) = machine.display.dimensions();
+    let mut image = Image::new(
+        Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        machine.display.pixels().to_vec(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    // keep the pixels sharp when scaled up
+    image.sampler = ImageSampler::nearest();
+    let handle = images.add(image);
+
+    commands.spawn(Camera2dBundle::default());
+    commands.spawn(SpriteBundle {
+        texture: handle.clone(),
+        sprite: Sprite {
+            custom_size: Some(Vec2::splat(512.0)),
+            ..default()
+        },
+        ..default()
+    });
+    commands.insert_resource(Frame(handle));
+}
+
+fn run(
+    mut machine: NonSendMut<Machine>,
+    frame: Res<Frame>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Machine { cpu, display, seed } = &mut *machine;
+
+    // the key goes to $FF, as the SDL front-end writes it
+    for (key, byte) in [
+        (KeyCode::KeyW, b'w'),
+        (KeyCode::KeyA, b'a'),
+        (KeyCode::KeyS, b's'),
+        (KeyCode::KeyD, b'd'),
+    ] {
+        if keys.just_pressed(key) {
+            cpu.bus.write(0xff, byte);
+        }
+    }
+
+    for _ in 0..STEPS_PER_FRAME {
+        if cpu.halted || cpu.is_jammed() {
+            break;
+        }
+        cpu.bus.write(0xfe, script::random_byte(seed));
+        cpu.exec();
+    }
+
+    display.update(cpu);
+    if display.take_dirty() {
+        if let Some(image) = images.get_mut(&frame.0) {
+            image.data.copy_from_slice(display.pixels());
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Runs the same registers and program on this core and on the `mos6502`
+//! crate and checks they agree after every instruction, with
+//! `cargo fuzz run differential` from the repository root.
+//!
+//! The input is A, X, Y, SP and P, then the program, loaded at $0600 into
+//! otherwise zeroed memory. Only documented opcodes are compared, and BRK
+//! and SED end the run: the reference takes BRK differently, and NMOS
+//! decimal mode leaves some flags undefined.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mos6502::instruction::Nmos6502;
+use mos6502::memory::{Bus as _, Memory};
+use mos6502::registers::{StackPointer, Status};
+use nesemu::cpu::Variant;
+use nesemu::{disasm, Bus, CPU};
+
+const ORIGIN: u16 = 0x0600;
+const MAX_STEPS: usize = 64;
+
+/// B and bit 5 only exist on the stack, the two cores may keep them apart.
+const FLAGS: u8 = 0b1100_1111;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&[a, x, y, sp, p], program)) = data.split_first_chunk() else {
+        return;
+    };
+    let program = &program[..program.len().min(0x100)];
+
+    let mut ours = CPU::new(Bus::default());
+    ours.variant = Variant::Nmos;
+    ours.load(program.to_vec());
+    (ours.reg.a, ours.reg.x, ours.reg.y, ours.reg.sp) = (a, x, y, sp);
+    ours.flags = p.into();
+
+    let mut theirs = mos6502::cpu::CPU::new(Memory::new(), Nmos6502);
+    theirs.memory.set_bytes(ORIGIN, program);
+    theirs.memory.set_bytes(0xFFFC, &[ORIGIN as u8, (ORIGIN >> 8) as u8]);
+    theirs.registers.program_counter = ORIGIN;
+    theirs.registers.accumulator = a;
+    theirs.registers.index_x = x;
+    theirs.registers.index_y = y;
+    theirs.registers.stack_pointer = StackPointer(sp);
+    theirs.registers.status = Status::from_bits_truncate(p);
+
+    for step in 0..MAX_STEPS {
+        let opcode = ours.bus.memory.read(ours.pc);
+        if !disasm::documented(opcode) || opcode == 0x00 || opcode == 0xF8 {
+            return;
+        }
+        let text = disasm::line(&ours, ours.pc).0;
+        ours.exec();
+        theirs.single_step();
+
+        let r = &theirs.registers;
+        let got = (ours.pc, ours.reg.a, ours.reg.x, ours.reg.y, ours.reg.sp);
+        let want = (
+            r.program_counter,
+            r.accumulator,
+            r.index_x,
+            r.index_y,
+            r.stack_pointer.0,
+        );
+        assert_eq!(got, want, "registers after step {}: {}", step, text);
+        assert_eq!(
+            u8::from(ours.flags) & FLAGS,
+            r.status.bits() & FLAGS,
+            "flags after step {}: {}",
+            step,
+            text
+        );
+
+        let memory = ours.bus.memory.dump();
+        for adr in 0..=0xFFFF_u16 {
+            assert_eq!(
+                memory[adr as usize],
+                theirs.memory.get_byte(adr),
+                "${:04X} after step {}: {}",
+                adr,
+                step,
+                text
+            );
+        }
+    }
+});
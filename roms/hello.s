@@ -0,0 +1,18 @@
+; Writes HELLO across the top of the display in white.
+; Source of hello.bin; build with ca65/ld65 at $0600.
+
+        ldx #0
+draw:   ldy pixels,x    ; offset of the next pixel from $0200
+        beq done        ; the list ends with 0
+        lda #1
+        sta $0200,y
+        inx
+        bne draw
+done:   jmp done
+
+pixels: .byte $26, $28, $2a, $2b, $2c, $2e, $32, $36, $37, $38
+        .byte $46, $48, $4a, $4e, $52, $56, $58, $66, $67, $68
+        .byte $6a, $6b, $6e, $72, $76, $78, $86, $88, $8a, $8e
+        .byte $92, $96, $98, $a6, $a8, $aa, $ab, $ac, $ae, $af
+        .byte $b0, $b2, $b3, $b4, $b6, $b7, $b8
+        .byte 0
@@ -0,0 +1,26 @@
+; Moves a dot along the top of the display once a frame, from the NMI
+; handler, while the main loop only counts. Source of interrupt.bin; build
+; with ca65/ld65 at $0600. The demo maps a frame counter at $D000 that
+; pulls NMI at the start of every frame.
+
+frames = $d000
+pos    = $00
+
+        lda #<nmi       ; point the NMI vector at the handler
+        sta $fffa
+        lda #>nmi
+        sta $fffb
+loop:   inx
+        jmp loop
+
+nmi:    pha
+        ldy pos
+        lda #0          ; clear the dot
+        sta $0200,y
+        iny
+        sty pos
+        lda frames      ; and draw it one on, coloured by the frame count
+        ora #1
+        sta $0200,y
+        pla
+        rti
@@ -1,7 +1,370 @@
-use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use nesemu::bus::RamInit;
+use nesemu::compat;
+use nesemu::cpu::{Magic, Variant};
+use nesemu::filter::{Filter, Palette};
+use nesemu::guard::Region;
+use nesemu::screen::Layout;
+use nesemu::search::Goal;
+use nesemu::text::TextLayout;
+use nesemu::trace::Destination;
+use nesemu::watchdog::WatchdogSpec;
+
+/// Where the process exit code comes from when a headless run halts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusSource {
+    Address(u16),
+    A,
+}
+
+impl std::str::FromStr for StatusSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("a") {
+            return Ok(StatusSource::A);
+        }
+
+        let hex = s.trim_start_matches('$').trim_start_matches("0x");
+        u16::from_str_radix(hex, 16)
+            .map(StatusSource::Address)
+            .map_err(|_| format!("expected an address or \"a\": {}", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Video {
+    /// SDL window
+    Sdl,
+    /// 24-bit colour half blocks in the terminal
+    Ansi,
+    /// Braille dots in the terminal
+    Braille,
+    /// DEC sixel graphics in the terminal
+    Sixel,
+    /// VNC server on --vnc-addr
+    Vnc,
+}
+
+/// What the window does while it doesn't have focus.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Background {
+    /// Keep running at full speed
+    Run,
+    /// Stop until focus comes back
+    Pause,
+    /// Run at a fraction of the speed
+    Throttle,
+}
+
+/// What BRK does.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Brk {
+    /// Halt the program, as easy6502 does
+    Stop,
+    /// Push the PC and flags and jump through the IRQ vector at $FFFE
+    Interrupt,
+}
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct EmuArgs {
-    pub file_name: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(required = true)]
+    pub file_name: Option<String>,
+
+    /// Magic constant for the unstable ANE/LXA opcodes (hex byte or "random")
+    #[arg(long, default_value = "ee")]
+    pub magic: Magic,
+
+    /// The chip to emulate: 6502 for BCD arithmetic when D is set, or the
+    /// NES's 2A03, which ignores D
+    #[arg(long, default_value = "2a03")]
+    pub cpu: Variant,
+
+    /// Power-on memory contents: a hex fill byte (00, ff), 55aa or
+    /// random[:seed]
+    #[arg(long, default_value = "00")]
+    pub ram_init: RamInit,
+
+    /// Map a watchdog at ADDR (hex) that resets the CPU unless its page is
+    /// written every CYCLES cycles
+    #[arg(long, value_name = "ADDR:CYCLES")]
+    pub watchdog: Option<WatchdogSpec>,
+
+    /// Stop with a report when the program reads, writes or runs code in
+    /// START-END (hex); repeatable
+    #[arg(long, value_name = "START-END")]
+    pub guard: Vec<Region>,
+
+    /// Stop with a report when the program runs code in START-END (hex);
+    /// repeatable
+    #[arg(long, value_name = "START-END")]
+    pub no_exec: Vec<Region>,
+
+    /// What BRK does
+    #[arg(long, value_enum, default_value = "stop")]
+    pub brk: Brk,
+
+    /// Stop with a crash report on undocumented opcodes instead of
+    /// emulating them
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Log each instruction's PC and opcode to this file, to stdout, or
+    /// off; {id} in a path is the instance number
+    #[arg(long, value_name = "PATH|stdout|off", default_value = "log.txt")]
+    pub trace: Destination,
+
+    /// Move the trace file aside to PATH.1 each time it reaches this many
+    /// megabytes, 0 to let it grow
+    #[arg(long, value_name = "MB", default_value_t = 64)]
+    pub trace_rotate: u64,
+
+    /// Old trace files kept, as PATH.1 (newest) to PATH.N
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub trace_keep: usize,
+
+    /// Stop after this many CPU cycles
+    #[arg(long)]
+    pub max_cycles: Option<u64>,
+
+    /// Stop after this many instructions
+    #[arg(long)]
+    pub max_instructions: Option<u64>,
+
+    /// Command used to assemble .s/.asm sources, with {in} and {out}
+    /// placeholders; run directly, not through a shell (defaults to ca65 +
+    /// ld65)
+    #[arg(long)]
+    pub assembler: Option<String>,
+
+    /// Enable a cheat (AAAA:VV or a Game Genie code) and save it to the ROM's
+    /// .cht file
+    #[arg(long)]
+    pub cheat: Vec<String>,
+
+    /// Where to show the display
+    #[arg(long, value_enum, default_value = "sdl")]
+    pub video: Video,
+
+    /// Address the VNC server listens on with --video vnc
+    #[arg(long, default_value = "127.0.0.1:5900")]
+    pub vnc_addr: String,
+
+    /// Show a bitmap display instead of the easy6502 one, laid out as
+    /// WIDTHxHEIGHT@BASE:BPP[:PITCH] (BASE in hex, BPP 1, 2 or 8)
+    #[arg(long)]
+    pub bitmap: Option<Layout>,
+
+    /// Show a text display instead, COLSxROWS@BASE (BASE in hex), with
+    /// glyphs from --charrom
+    #[arg(long, requires = "charrom", conflicts_with = "bitmap")]
+    pub text: Option<TextLayout>,
+
+    /// Character ROM for --text: 8 bytes per 8x8 glyph, MSB leftmost
+    #[arg(long, requires = "text")]
+    pub charrom: Option<String>,
+
+    /// Window size in display pixels per emulated pixel (defaults to half
+    /// the screen height)
+    #[arg(long)]
+    pub zoom: Option<usize>,
+
+    /// Show a d-pad that can be tapped on touch screens
+    #[arg(long)]
+    pub touch: bool,
+
+    /// Post-processing applied to the display
+    #[arg(long, value_enum, default_value = "none")]
+    pub filter: Filter,
+
+    /// Stream a chrome://tracing profile of the front-end loop to this file
+    /// (F4 shows the same breakdown as an overlay)
+    #[arg(long)]
+    pub profile: Option<PathBuf>,
+
+    /// Recolour the display for a colour-vision deficiency
+    #[arg(long, value_enum, default_value = "normal")]
+    pub palette: Palette,
+
+    /// Damp full-screen flashes beyond this many per second
+    #[arg(long, value_name = "PER_SECOND")]
+    pub flash_limit: Option<usize>,
+
+    /// What to do while the window is in the background
+    #[arg(long, value_enum, default_value = "run")]
+    pub background: Background,
+
+    /// Reload and restart the ROM whenever the file changes
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Treat FILE_NAME as a folder and cycle through its programs in the
+    /// window, this many seconds each, with attract-mode input
+    #[arg(long, value_name = "SECONDS", conflicts_with = "watch")]
+    pub jukebox: Option<u64>,
+
+    /// Compatibility database checked for known issues when the ROM loads
+    #[arg(long, default_value = compat::DEFAULT_PATH)]
+    pub compat_db: String,
+
+    /// Run without a window, exiting with the program's status when it halts
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Explain each instruction in plain English as it runs (use with
+    /// --max-instructions to keep it short)
+    #[arg(long, requires = "headless")]
+    pub explain: bool,
+
+    /// Track the data read from START-END (hex), such as the key port at FF,
+    /// through registers and memory, and list the branches and jumps it
+    /// decided when the run ends
+    #[arg(
+        long,
+        value_name = "START-END",
+        requires = "headless",
+        conflicts_with = "explain"
+    )]
+    pub taint: Option<Region>,
+
+    /// When the run ends, write the program out as ca65 source: the code it
+    /// executed as instructions, the rest as .byte
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub export_asm: Option<PathBuf>,
+
+    /// Headless exit status source: a memory address or "a" for the A register
+    #[arg(long, default_value = "6000")]
+    pub status: StatusSource,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run every test ROM and test script in a directory and print a report
+    Test(TestArgs),
+    /// Run every ROM in a directory briefly and triage what went wrong
+    Soak(SoakArgs),
+    /// Run one of the built-in demo programs, or list them
+    Demo(DemoArgs),
+    /// List the memory that differs between two save states or dumps
+    Diff(DiffArgs),
+    /// Look for key presses that get a program to an address or a memory
+    /// value (experimental)
+    Search(SearchArgs),
+    /// Run a program as a filter: its input port reads stdin and its output
+    /// port writes to stdout
+    Run(RunArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct TestArgs {
+    /// A directory of .nes/.bin ROMs and .toml test scripts, or just one
+    pub dir: PathBuf,
+
+    /// Give up on a ROM after this many CPU cycles
+    #[arg(long, default_value_t = 100_000_000)]
+    pub max_cycles: u64,
+
+    /// Also write the results as JUnit XML
+    #[arg(long)]
+    pub junit: Option<PathBuf>,
+
+    /// Threads to run ROMs on (defaults to one per core)
+    #[arg(long, short)]
+    pub jobs: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    pub before: PathBuf,
+    pub after: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    pub rom: PathBuf,
+
+    /// What to reach: pc=ADDR, or ADDR=VALUE for a byte of memory (hex)
+    #[arg(long)]
+    pub reach: Goal,
+
+    /// Keys that may be pressed, one per frame at most
+    #[arg(long, default_value = "wasd")]
+    pub keys: String,
+
+    /// Give up after this many frames
+    #[arg(long, default_value_t = 60)]
+    pub frames: u64,
+
+    /// Most states carried from one frame into the next
+    #[arg(long, default_value_t = 10_000)]
+    pub states: usize,
+
+    /// Seed for the random byte at $FE, as in a test script
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Write the key presses found as a test script
+    #[arg(long)]
+    pub script: Option<PathBuf>,
+}
+
+fn page(s: &str) -> Result<u8, String> {
+    u8::from_str_radix(s.trim_start_matches('$'), 16)
+        .map_err(|_| format!("expected a page number in hex: {}", s))
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    pub rom: PathBuf,
+
+    /// Page (hex) of the ports: DATA at offset 0, STATUS at 1 with bit 7
+    /// set at the end of the input
+    #[arg(long, value_parser = page, default_value = "d0")]
+    pub page: u8,
+
+    /// Stop after this many CPU cycles
+    #[arg(long)]
+    pub max_cycles: Option<u64>,
+
+    /// Command used to assemble .s/.asm sources, as for the emulator
+    #[arg(long)]
+    pub assembler: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DemoArgs {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SoakArgs {
+    pub dir: PathBuf,
+
+    /// Run each ROM for this many frames worth of CPU cycles
+    #[arg(long, default_value_t = 600)]
+    pub frames: u64,
+
+    /// Also write the results as JSON
+    #[arg(long)]
+    pub json: Option<PathBuf>,
+
+    /// Also write the results as CSV
+    #[arg(long)]
+    pub csv: Option<PathBuf>,
+
+    /// Compatibility database to update with the results
+    #[arg(long, default_value = compat::DEFAULT_PATH)]
+    pub compat_db: String,
+
+    /// Threads to run ROMs on (defaults to one per core)
+    #[arg(long, short)]
+    pub jobs: Option<usize>,
 }
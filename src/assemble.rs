@@ -0,0 +1,106 @@
+use std::fs;
+use std::io::{self, Error};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Programs are loaded at $0600, so that is where the linker places them.
+const LOAD_ADDRESS: &str = "0x0600";
+
+pub fn is_source(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext == "s" || ext == "asm" || ext == "a65")
+}
+
+fn run(cmd: &mut Command) -> io::Result<()> {
+    let status = cmd.status()?;
+    if !status.success() {
+        return Err(Error::other(format!("{:?} failed ({})", cmd, status)));
+    }
+    Ok(())
+}
+
+/// Builds from one template, split on whitespace, with `{in}` and `{out}`
+/// replaced inside each word. There is no shell, so a path with spaces or
+/// quotes in it stays one argument.
+fn command(template: &str, path: &str, out: &Path) -> io::Result<Command> {
+    let out = out.to_string_lossy();
+    let mut words = template
+        .split_whitespace()
+        .map(|w| w.replace("{in}", path).replace("{out}", &out));
+    let mut cmd = Command::new(
+        words
+            .next()
+            .ok_or_else(|| Error::other("empty assembler command"))?,
+    );
+    cmd.args(words);
+    Ok(cmd)
+}
+
+/// Assembles `path` into a flat binary. `command` is a template where
+/// `{in}` and `{out}` are replaced by the source and output paths; without
+/// one, the source is built with ca65 and linked with ld65.
+pub fn assemble(path: &str, command: Option<&str>) -> io::Result<Vec<u8>> {
+    // unique per call as well as per process, so no two builds share files
+    static BUILDS: AtomicUsize = AtomicUsize::new(0);
+    let tmp = std::env::temp_dir();
+    let id = format!(
+        "{}-{}",
+        std::process::id(),
+        BUILDS.fetch_add(1, Ordering::Relaxed)
+    );
+    let obj = tmp.join(format!("rusty6502-{}.o", id));
+    let out = tmp.join(format!("rusty6502-{}.bin", id));
+
+    let built = match command {
+        Some(template) => self::command(template, path, &out).and_then(|mut c| run(&mut c)),
+        None => run(Command::new("ca65").arg(path).arg("-o").arg(&obj)).and_then(|_| {
+            run(Command::new("ld65")
+                .args(["-t", "none", "-S", LOAD_ADDRESS, "-o"])
+                .arg(&out)
+                .arg(&obj))
+        }),
+    };
+
+    let program = built.and_then(|_| fs::read(&out));
+    let _ = fs::remove_file(&obj);
+    let _ = fs::remove_file(&out);
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assemble::*;
+
+    #[test]
+    fn detects_source() {
+        assert!(is_source("game.s"));
+        assert!(is_source("dir/game.asm"));
+        assert!(!is_source("roms/snake.nes"));
+    }
+
+    #[test]
+    fn custom_command() {
+        let src = std::env::temp_dir().join("rusty6502-custom-command.s");
+        fs::write(&src, [0xa9, 0x01, 0x00]).unwrap();
+
+        let program = assemble(src.to_str().unwrap(), Some("cp {in} {out}")).unwrap();
+        assert_eq!(program, vec![0xa9, 0x01, 0x00]);
+        assert!(assemble(src.to_str().unwrap(), Some("false")).is_err());
+
+        fs::remove_file(src).unwrap();
+    }
+
+    #[test]
+    fn paths_are_not_shell_words() {
+        let src = std::env::temp_dir().join("rusty6502 it's $(false).s");
+        fs::write(&src, [0xea]).unwrap();
+
+        let program = assemble(src.to_str().unwrap(), Some("cp {in} {out}")).unwrap();
+        assert_eq!(program, vec![0xea]);
+        assert!(assemble(src.to_str().unwrap(), Some("")).is_err());
+
+        fs::remove_file(src).unwrap();
+    }
+}
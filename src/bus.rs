@@ -1,23 +1,530 @@
+use crate::guard::{Kind, Region};
+use crate::memory::{Backing, Memory, Ram};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A single bus transaction, as seen on the address/data pins.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BusCycle {
+    pub addr: u16,
+    pub data: u8,
+    pub access: Access,
+    /// Made while the chip works out an address or modifies a byte, with
+    /// nothing in the instruction's result depending on it
+    pub dummy: bool,
+}
+
+/// What memory holds at power-on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RamInit {
+    Fill(u8),
+    /// $55, $AA, $55, ...
+    Alternating,
+    /// Seeded xorshift noise, so a run can be repeated.
+    Random(u64),
+}
+
+impl Default for RamInit {
+    fn default() -> Self {
+        RamInit::Fill(0)
+    }
+}
+
+impl RamInit {
+    pub fn bytes(&self) -> Vec<u8> {
+        match *self {
+            RamInit::Fill(b) => vec![b; 0x10000],
+            RamInit::Alternating => (0..0x10000)
+                .map(|i| if i % 2 == 0 { 0x55 } else { 0xAA })
+                .collect(),
+            RamInit::Random(seed) => {
+                // xorshift64 never leaves zero, so move that seed elsewhere
+                let mut x = if seed == 0 {
+                    0x9E37_79B9_7F4A_7C15
+                } else {
+                    seed
+                };
+                (0..0x10000)
+                    .map(|_| {
+                        x ^= x << 13;
+                        x ^= x >> 7;
+                        x ^= x << 17;
+                        (x >> 32) as u8
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for RamInit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        match lower.split_once(':') {
+            Some(("random", seed)) => seed
+                .parse()
+                .map(RamInit::Random)
+                .map_err(|_| format!("invalid seed: {}", seed)),
+            None if lower == "random" => Ok(RamInit::Random(rand::random())),
+            None if lower == "55aa" => Ok(RamInit::Alternating),
+            None => u8::from_str_radix(&lower, 16)
+                .map(RamInit::Fill)
+                .map_err(|_| format!("expected 00, ff, 55aa or random[:seed]: {}", s)),
+            Some(_) => Err(format!("expected 00, ff, 55aa or random[:seed]: {}", s)),
+        }
+    }
+}
+
+/// The CPU inputs a device can pull, as its last tick left them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lines {
+    pub reset: bool,
+    /// Level-triggered: interrupts whenever I is clear, until the device
+    /// lets go of it
+    pub irq: bool,
+    /// Edge-triggered: interrupts once each time it is pulled
+    pub nmi: bool,
+}
+
+/// Something memory-mapped on the bus, claiming whole 256-byte pages.
+pub trait Device: Send {
+    fn read(&mut self, adr: u16) -> u8;
+
+    fn write(&mut self, adr: u16, data: u8);
+
+    /// What `read` would return, without its side effects, for debuggers.
+    fn peek(&self, adr: u16) -> u8;
+
+    /// Advances the device by one CPU cycle, returning the lines it pulls.
+    fn tick(&mut self) -> Lines {
+        Lines::default()
+    }
+}
+
+// Page table entry for pages that go straight to memory.
+const MEMORY: u8 = 0;
+
 pub struct Bus {
-    pub memory: [u8; 0xFFFF],
+    pub memory: Backing,
+    log: Option<Vec<BusCycle>>,
+    pub ram_init: RamInit,
+    devices: Vec<Box<dyn Device>>,
+    reset_pending: bool,
+    /// The lines, wired-OR between the devices, as of the last tick
+    lines: Lines,
+    nmi_pending: bool,
+    /// The devices holding IRQ as of the last tick, by index
+    irq_sources: Vec<usize>,
+    /// Who answers for each page: MEMORY, or 1 + an index into `devices`.
+    pages: [u8; 0x100],
+    guards: Vec<Region>,
+    /// Pages with a guard region in them, so other accesses skip the search.
+    guarded: [bool; 0x100],
+    touched: Option<(BusCycle, Region)>,
+    /// No device, log or guard is installed, so accesses to Ram skip the
+    /// page table and the checks.
+    direct: bool,
 }
 
 impl Default for Bus {
     fn default() -> Self {
-        Bus {
-            memory: [0; 0xFFFF],
-        }
+        Bus::with_backing(Backing::Ram(Ram::default()))
     }
 }
 
 impl Bus {
+    pub fn new(memory: Box<dyn Memory>) -> Self {
+        Bus::with_backing(Backing::Other(memory))
+    }
+
+    fn with_backing(memory: Backing) -> Self {
+        Bus {
+            memory,
+            log: None,
+            ram_init: RamInit::default(),
+            devices: Vec::new(),
+            reset_pending: false,
+            lines: Lines::default(),
+            nmi_pending: false,
+            irq_sources: Vec::new(),
+            pages: [MEMORY; 0x100],
+            guards: Vec::new(),
+            guarded: [false; 0x100],
+            touched: None,
+            direct: true,
+        }
+    }
+
+    fn update_direct(&mut self) {
+        self.direct = self.devices.is_empty() && self.log.is_none() && self.guards.is_empty();
+    }
+
+    /// Stops the CPU when `region` is touched, see `guard::Kind`.
+    pub fn guard(&mut self, region: Region) {
+        if region.kind == Kind::Guard {
+            for page in region.start >> 8..=region.end >> 8 {
+                self.guarded[page as usize] = true;
+            }
+        }
+        self.guards.push(region);
+        self.update_direct();
+    }
+
+    /// The first region of either kind holding `adr`.
+    pub fn region_at(&self, adr: u16) -> Option<Region> {
+        self.guards.iter().find(|r| r.contains(adr)).copied()
+    }
+
+    /// The first access to a guard region since the last call, and the region.
+    pub fn take_touched(&mut self) -> Option<(BusCycle, Region)> {
+        self.touched.take()
+    }
+
+    /// Hands every page in `pages` (by high address byte) to `device`,
+    /// taking them from memory or whichever device had them before. Returns
+    /// the device's index, counting from 0 in the order they were mapped.
+    pub fn map(&mut self, pages: std::ops::RangeInclusive<u8>, device: Box<dyn Device>) -> usize {
+        assert!(self.devices.len() < 0xFF, "too many devices on the bus");
+        self.devices.push(device);
+
+        let id = self.devices.len() as u8;
+        for page in pages {
+            self.pages[page as usize] = id;
+        }
+        self.update_direct();
+        id as usize - 1
+    }
+
+    /// Fill memory with the power-on pattern, remembering it for save states.
+    pub fn power_on(&mut self, init: RamInit) {
+        self.ram_init = init;
+        self.memory.load(0, &init.bytes());
+    }
+
+    #[inline]
     pub fn read(&mut self, adr: u16) -> u8 {
-        self.memory[adr as usize]
+        self.read_cycle(adr, false)
+    }
+
+    /// A read the chip makes on its way to the real one. It reaches devices
+    /// like any other, so one that counts reads sees it.
+    pub fn dummy_read(&mut self, adr: u16) {
+        self.read_cycle(adr, true);
     }
 
+    #[inline]
+    fn read_cycle(&mut self, adr: u16, dummy: bool) -> u8 {
+        if let (true, Backing::Ram(ram)) = (self.direct, &self.memory) {
+            return ram.read(adr);
+        }
+        self.read_mapped(adr, dummy)
+    }
+
+    fn read_mapped(&mut self, adr: u16, dummy: bool) -> u8 {
+        let data = match self.pages[(adr >> 8) as usize] {
+            MEMORY => self.memory.read(adr),
+            id => self.devices[id as usize - 1].read(adr),
+        };
+        if self.log.is_some() {
+            self.record(adr, data, Access::Read, dummy);
+        }
+        if self.guarded[(adr >> 8) as usize] {
+            self.check_guard(adr, data, Access::Read, dummy);
+        }
+        data
+    }
+
+    /// What a read of `adr` would return, leaving devices, the activity log
+    /// and the guards alone.
+    pub fn peek(&self, adr: u16) -> u8 {
+        match self.pages[(adr >> 8) as usize] {
+            MEMORY => self.memory.read(adr),
+            id => self.devices[id as usize - 1].peek(adr),
+        }
+    }
+
+    #[inline]
     pub fn write(&mut self, adr: u16, data: u8) {
-        self.memory[adr as usize] = data
+        self.write_cycle(adr, data, false);
+    }
+
+    /// The write of the unmodified byte a read-modify-write makes before
+    /// the modified one.
+    pub fn dummy_write(&mut self, adr: u16, data: u8) {
+        self.write_cycle(adr, data, true);
+    }
+
+    #[inline]
+    fn write_cycle(&mut self, adr: u16, data: u8, dummy: bool) {
+        if let (true, Backing::Ram(ram)) = (self.direct, &mut self.memory) {
+            return ram.write(adr, data);
+        }
+        self.write_mapped(adr, data, dummy)
+    }
+
+    fn write_mapped(&mut self, adr: u16, data: u8, dummy: bool) {
+        if self.log.is_some() {
+            self.record(adr, data, Access::Write, dummy);
+        }
+        if self.guarded[(adr >> 8) as usize] {
+            self.check_guard(adr, data, Access::Write, dummy);
+        }
+        match self.pages[(adr >> 8) as usize] {
+            MEMORY => self.memory.write(adr, data),
+            id => self.devices[id as usize - 1].write(adr, data),
+        }
+    }
+
+    /// One CPU cycle has passed; the devices keep in step with it.
+    pub fn tick(&mut self) {
+        let mut lines = Lines::default();
+        self.irq_sources.clear();
+        for (i, d) in self.devices.iter_mut().enumerate() {
+            let l = d.tick();
+            lines.reset |= l.reset;
+            lines.irq |= l.irq;
+            lines.nmi |= l.nmi;
+            if l.irq {
+                self.irq_sources.push(i);
+            }
+        }
+        self.reset_pending |= lines.reset;
+        self.nmi_pending |= lines.nmi && !self.lines.nmi;
+        self.lines = lines;
+    }
+
+    /// Whether a device has asked for a reset since the last call.
+    pub fn take_reset(&mut self) -> bool {
+        std::mem::take(&mut self.reset_pending)
+    }
+
+    /// Whether NMI has been pulled since the last call.
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_pending)
+    }
+
+    /// Whether a device is holding IRQ.
+    pub fn irq(&self) -> bool {
+        self.lines.irq
+    }
+
+    /// Which devices are holding IRQ, by the index `map` gave them. Any
+    /// number can share the line; it stays pulled until all let go.
+    pub fn irq_sources(&self) -> &[usize] {
+        &self.irq_sources
+    }
+
+    /// Start recording every read and write into the activity log.
+    pub fn start_log(&mut self) {
+        self.log = Some(Vec::new());
+        self.update_direct();
+    }
+
+    /// Stop recording and hand back everything logged so far.
+    pub fn take_log(&mut self) -> Vec<BusCycle> {
+        let log = self.log.take().unwrap_or_default();
+        self.update_direct();
+        log
+    }
+
+    // Kept out of line so a mapped access that isn't logged stays small.
+    #[cold]
+    #[inline(never)]
+    fn record(&mut self, addr: u16, data: u8, access: Access, dummy: bool) {
+        if let Some(log) = self.log.as_mut() {
+            log.push(BusCycle {
+                addr,
+                data,
+                access,
+                dummy,
+            });
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn check_guard(&mut self, addr: u16, data: u8, access: Access, dummy: bool) {
+        let hit = self
+            .guards
+            .iter()
+            .find(|r| r.kind == Kind::Guard && r.contains(addr));
+        if let (Some(&region), None) = (hit, self.touched) {
+            let cycle = BusCycle {
+                addr,
+                data,
+                access,
+                dummy,
+            };
+            self.touched = Some((cycle, region));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::*;
+
+    #[test]
+    fn activity_log() {
+        let mut b = Bus::default();
+        b.write(0x10, 0xAA);
+        b.start_log();
+        b.read(0x10);
+        b.dummy_write(0x11, 0x00);
+        b.write(0x11, 0x55);
+
+        assert_eq!(
+            b.take_log(),
+            vec![
+                BusCycle {
+                    addr: 0x10,
+                    data: 0xAA,
+                    access: Access::Read,
+                    dummy: false,
+                },
+                BusCycle {
+                    addr: 0x11,
+                    data: 0x00,
+                    access: Access::Write,
+                    dummy: true,
+                },
+                BusCycle {
+                    addr: 0x11,
+                    data: 0x55,
+                    access: Access::Write,
+                    dummy: false,
+                },
+            ]
+        );
+        assert!(b.log.is_none());
+    }
+
+    #[test]
+    fn any_memory_behind_the_bus() {
+        let mut b = Bus::new(Box::new(crate::memory::CowRam::default()));
+        b.write(0x1234, 5);
+        assert_eq!((b.read(0x1234), b.memory.read(0x1234)), (5, 5));
+
+        b.start_log();
+        b.write(0x1234, 6);
+        assert_eq!(b.take_log().len(), 1);
+        assert_eq!(b.read(0x1234), 6);
+    }
+
+    struct Latch(u8);
+
+    impl Device for Latch {
+        fn read(&mut self, _adr: u16) -> u8 {
+            self.0
+        }
+
+        fn write(&mut self, _adr: u16, data: u8) {
+            self.0 = data;
+        }
+
+        fn peek(&self, _adr: u16) -> u8 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn page_table_decoding() {
+        let mut b = Bus::default();
+        b.map(0xD0..=0xD1, Box::new(Latch(0)));
+        b.map(0xD1..=0xD1, Box::new(Latch(7)));
+
+        b.write(0xD000, 0x42);
+        assert_eq!(b.read(0xD0FF), 0x42);
+        assert_eq!(b.read(0xD100), 7);
+        assert_eq!(b.memory.read(0xD000), 0);
+
+        b.write(0xCFFF, 1);
+        assert_eq!(b.memory.read(0xCFFF), 1);
+    }
+
+    #[test]
+    fn peek_has_no_side_effects() {
+        let mut b = Bus::default();
+        b.map(0xD0..=0xD0, Box::new(Latch(3)));
+        b.memory.write(0x0010, 9);
+        b.guard(Region {
+            start: 0x0010,
+            end: 0x0010,
+            kind: Kind::Guard,
+        });
+        b.start_log();
+
+        assert_eq!((b.peek(0x0010), b.peek(0xD000)), (9, 3));
+        assert_eq!(b.take_log(), []);
+        assert_eq!(b.take_touched(), None);
+    }
+
+    #[test]
+    fn power_on_patterns() {
+        assert_eq!("ff".parse(), Ok(RamInit::Fill(0xff)));
+        assert_eq!("55AA".parse(), Ok(RamInit::Alternating));
+        assert_eq!("random:42".parse(), Ok(RamInit::Random(42)));
+        assert!("random:x".parse::<RamInit>().is_err());
+
+        let mut b = Bus::default();
+        b.power_on(RamInit::Alternating);
+        assert_eq!((b.read(0x10), b.read(0x11)), (0x55, 0xAA));
+
+        let seeded = RamInit::Random(42).bytes();
+        assert_eq!(seeded, RamInit::Random(42).bytes());
+        assert_ne!(seeded, RamInit::Random(43).bytes());
     }
 
-    pub fn tick(&mut self, cycles: u8) {}
+    /// Holds IRQ while it holds a non-zero byte.
+    struct Request(u8);
+
+    impl Device for Request {
+        fn read(&mut self, _adr: u16) -> u8 {
+            self.0
+        }
+
+        fn write(&mut self, _adr: u16, data: u8) {
+            self.0 = data;
+        }
+
+        fn peek(&self, _adr: u16) -> u8 {
+            self.0
+        }
+
+        fn tick(&mut self) -> Lines {
+            Lines {
+                irq: self.0 != 0,
+                ..Lines::default()
+            }
+        }
+    }
+
+    #[test]
+    fn shared_irq_line() {
+        let mut b = Bus::default();
+        assert_eq!(b.map(0xd0..=0xd0, Box::new(Request(0))), 0);
+        assert_eq!(b.map(0xd1..=0xd1, Box::new(Latch(0))), 1);
+        assert_eq!(b.map(0xd2..=0xd2, Box::new(Request(0))), 2);
+
+        b.write(0xd000, 1);
+        b.write(0xd200, 1);
+        b.tick();
+        assert!(b.irq());
+        assert_eq!(b.irq_sources(), [0, 2]);
+
+        b.write(0xd000, 0);
+        b.tick();
+        assert_eq!(b.irq_sources(), [2]);
+
+        b.write(0xd200, 0);
+        b.tick();
+        assert!(!b.irq());
+        assert_eq!(b.irq_sources(), []);
+    }
 }
@@ -0,0 +1,48 @@
+use std::io::{self, Error, ErrorKind};
+
+use crate::cpu::CPU;
+
+/// An iNES image. Only mapper 0 (NROM) is understood.
+pub struct Cartridge {
+    pub prg: Vec<u8>,
+}
+
+impl Cartridge {
+    pub fn is_ines(data: &[u8]) -> bool {
+        data.starts_with(b"NES\x1a")
+    }
+
+    pub fn from_ines(data: &[u8]) -> io::Result<Cartridge> {
+        if !Cartridge::is_ines(data) || data.len() < 16 {
+            return Err(Error::new(ErrorKind::InvalidData, "not an iNES image"));
+        }
+
+        let mapper = (data[7] & 0xF0) | (data[6] >> 4);
+        if mapper != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported mapper {}", mapper),
+            ));
+        }
+
+        let prg_len = data[4] as usize * 0x4000;
+        let start = if data[6] & 0b100 != 0 { 16 + 512 } else { 16 };
+        let prg = data
+            .get(start..start + prg_len)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "truncated PRG ROM"))?
+            .to_vec();
+
+        Ok(Cartridge { prg })
+    }
+
+    /// Maps PRG ROM into $8000-$FFFF (16K images are mirrored) and resets.
+    pub fn insert(&self, cpu: &mut CPU) {
+        if self.prg.len() <= 0x4000 {
+            cpu.bus.memory.load(0x8000, &self.prg);
+            cpu.bus.memory.load(0xC000, &self.prg);
+        } else {
+            cpu.bus.memory.load(0x8000, &self.prg);
+        }
+        cpu.reset();
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+
+use crate::cpu::CPU;
+
+const GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub code: String,
+    pub name: String,
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+    pub enabled: bool,
+}
+
+fn invalid(code: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("invalid cheat code: {}", code),
+    )
+}
+
+fn decode_genie(code: &str) -> Option<(u16, u8, Option<u8>)> {
+    let n: Vec<u16> = code
+        .bytes()
+        .map(|c| {
+            GENIE_LETTERS
+                .iter()
+                .position(|&l| l == c.to_ascii_uppercase())
+                .map(|p| p as u16)
+        })
+        .collect::<Option<_>>()?;
+
+    if n.len() != 6 && n.len() != 8 {
+        return None;
+    }
+
+    let address = 0x8000
+        + (((n[3] & 7) << 12)
+            | ((n[5] & 7) << 8)
+            | ((n[4] & 8) << 8)
+            | ((n[2] & 7) << 4)
+            | ((n[1] & 8) << 4)
+            | (n[4] & 7)
+            | (n[3] & 8));
+
+    let last = if n.len() == 8 { n[7] } else { n[5] };
+    let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (last & 8);
+    let compare =
+        (n.len() == 8).then(|| ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8));
+
+    Some((address, value as u8, compare.map(|c| c as u8)))
+}
+
+/// Parses a raw `AAAA:VV` freeze or a 6/8 letter Game Genie code.
+pub fn parse_code(code: &str) -> io::Result<Cheat> {
+    let (address, value, compare) = match code.split_once(':') {
+        Some((adr, val)) => (
+            u16::from_str_radix(adr, 16).map_err(|_| invalid(code))?,
+            u8::from_str_radix(val, 16).map_err(|_| invalid(code))?,
+            None,
+        ),
+        None => decode_genie(code).ok_or_else(|| invalid(code))?,
+    };
+
+    Ok(Cheat {
+        code: code.to_string(),
+        name: String::new(),
+        address,
+        value,
+        compare,
+        enabled: true,
+    })
+}
+
+/// Reads a .cht file: one `CODE [name]` per line, `!` in front of the code
+/// disables it and `#` starts a comment.
+pub fn load_file(path: &str) -> io::Result<Vec<Cheat>> {
+    let mut cheats = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (code, name) = line.split_once(' ').unwrap_or((line, ""));
+        let (code, enabled) = match code.strip_prefix('!') {
+            Some(c) => (c, false),
+            None => (code, true),
+        };
+
+        let mut cheat = parse_code(code)?;
+        cheat.name = name.trim().to_string();
+        cheat.enabled = enabled;
+        cheats.push(cheat);
+    }
+    Ok(cheats)
+}
+
+pub fn save_file(path: &str, cheats: &[Cheat]) -> io::Result<()> {
+    let mut out = String::new();
+    for c in cheats {
+        let flag = if c.enabled { "" } else { "!" };
+        out += &format!("{}{} {}\n", flag, c.code, c.name);
+    }
+    fs::write(path, out)
+}
+
+/// Start of the ROM, where Game Genie codes patch.
+const ROM: u16 = 0x8000;
+
+/// The cheats written into a machine's memory. ROM bytes are patched rather
+/// than frozen, so what they held is kept and put back by `restore`; RAM
+/// freezes are left for the program to overwrite.
+#[derive(Default)]
+pub struct Patches {
+    original: HashMap<u16, u8>,
+}
+
+impl Patches {
+    /// Writes every enabled cheat into memory. Codes with a compare value
+    /// only patch the byte while its original contents are the expected ones.
+    pub fn apply(&mut self, cheats: &[Cheat], cpu: &mut CPU) {
+        for c in cheats.iter().filter(|c| c.enabled) {
+            let current = cpu.bus.memory.read(c.address);
+            let original = match c.address >= ROM {
+                true => *self.original.entry(c.address).or_insert(current),
+                false => current,
+            };
+            if c.compare.is_some_and(|v| original != v) {
+                continue;
+            }
+            cpu.bus.memory.write(c.address, c.value);
+        }
+    }
+
+    /// Puts back the ROM bytes patched since the last restore.
+    pub fn restore(&mut self, cpu: &mut CPU) {
+        for (adr, data) in self.original.drain() {
+            cpu.bus.memory.write(adr, data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cheats::*;
+
+    #[test]
+    fn parse_codes() {
+        let raw = parse_code("0075:09").unwrap();
+        assert_eq!((raw.address, raw.value, raw.compare), (0x0075, 0x09, None));
+
+        let genie = parse_code("PPPPPP").unwrap();
+        assert_eq!((genie.address, genie.value), (0x9111, 0x11));
+
+        let genie = parse_code("aaaaaaaa").unwrap();
+        assert_eq!(
+            (genie.address, genie.value, genie.compare),
+            (0x8000, 0x00, Some(0x00))
+        );
+
+        assert!(parse_code("QQQQQQ").is_err());
+        assert!(parse_code("75:zz").is_err());
+    }
+
+    #[test]
+    fn file_round_trip_and_apply() {
+        let path = std::env::temp_dir().join("rusty6502-cheats.cht");
+        let path = path.to_str().unwrap();
+        fs::write(path, "# lives\n0075:09 Infinite lives\n!0010:01 Off\n").unwrap();
+
+        let cheats = load_file(path).unwrap();
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(cheats[0].name, "Infinite lives");
+        assert!(!cheats[1].enabled);
+
+        let mut cpu = CPU::new(Bus::default());
+        Patches::default().apply(&cheats, &mut cpu);
+        assert_eq!(cpu.bus.read(0x75), 0x09);
+        assert_eq!(cpu.bus.read(0x10), 0x00);
+
+        save_file(path, &cheats).unwrap();
+        assert_eq!(load_file(path).unwrap(), cheats);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn restore_unpatches_rom() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.write(0x8000, 0x00);
+        cpu.bus.write(0x9111, 0x42);
+        cpu.bus.write(0x0075, 0x03);
+        let cheats = vec![
+            parse_code("aaaaaaaa").unwrap(), // $8000: $00 -> $00 if $00
+            parse_code("PPPPPP").unwrap(),   // $9111: -> $11
+            parse_code("0075:09").unwrap(),
+        ];
+
+        let mut patches = Patches::default();
+        patches.apply(&cheats, &mut cpu);
+        patches.apply(&cheats, &mut cpu);
+        assert_eq!(cpu.bus.read(0x9111), 0x11);
+
+        patches.restore(&mut cpu);
+        assert_eq!(cpu.bus.read(0x9111), 0x42);
+        assert_eq!(cpu.bus.read(0x8000), 0x00);
+        assert_eq!(cpu.bus.read(0x0075), 0x09);
+    }
+}
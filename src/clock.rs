@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use crate::cpu::CPU;
+
+/// The TV system a machine is timed for. Only the clock rates differ, the
+/// CPU runs the same code either way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+    /// 1.789773 MHz, 60.0988 frames a second
+    Ntsc,
+    /// 1.662607 MHz, 50.0070 frames a second
+    Pal,
+}
+
+impl std::str::FromStr for Timing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Timing::Ntsc),
+            "pal" => Ok(Timing::Pal),
+            _ => Err(format!("expected ntsc or pal: {}", s)),
+        }
+    }
+}
+
+impl Timing {
+    /// CPU cycles in one second of emulated time.
+    pub const fn cpu_hz(self) -> u64 {
+        match self {
+            Timing::Ntsc => 1_789_773,
+            Timing::Pal => 1_662_607,
+        }
+    }
+
+    /// CPU cycles in one frame, rounded up.
+    pub const fn cycles_per_frame(self) -> u64 {
+        match self {
+            Timing::Ntsc => 29_781,
+            Timing::Pal => 33_248,
+        }
+    }
+
+    pub const fn frames_to_cycles(self, frames: u64) -> u64 {
+        frames * self.cycles_per_frame()
+    }
+
+    /// Whole frames in `cycles`.
+    pub const fn cycles_to_frames(self, cycles: u64) -> u64 {
+        cycles / self.cycles_per_frame()
+    }
+
+    /// Cycles in `time` of emulated time, rounded down.
+    pub fn cycles(self, time: Duration) -> u64 {
+        (time.as_nanos() * self.cpu_hz() as u128 / 1_000_000_000) as u64
+    }
+
+    /// The emulated time `cycles` take, to the nanosecond.
+    pub fn duration(self, cycles: u64) -> Duration {
+        let nanos = cycles as u128 * 1_000_000_000 / self.cpu_hz() as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// The emulated time a frame takes.
+    pub fn frame_duration(self) -> Duration {
+        self.duration(self.cycles_per_frame())
+    }
+}
+
+/// Runs `cpu` until `time` more of emulated time has passed, or it halts or
+/// jams, however fast the host is. Returns the cycles run, which may go past
+/// the time by part of an instruction.
+pub fn advance(cpu: &mut CPU, timing: Timing, time: Duration) -> u64 {
+    advance_cycles(cpu, timing.cycles(time))
+}
+
+/// Runs `cpu` for `cycles` more cycles, or until it halts or jams. Returns
+/// the cycles run.
+pub fn advance_cycles(cpu: &mut CPU, cycles: u64) -> u64 {
+    let start = cpu.cycles;
+    while cpu.cycles < start + cycles && !cpu.halted && !cpu.is_jammed() {
+        cpu.exec();
+    }
+    cpu.cycles - start
+}
+
+/// The emulated time `cpu` has run since power-on.
+pub fn elapsed(cpu: &CPU, timing: Timing) -> Duration {
+    timing.duration(cpu.cycles)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::clock::*;
+
+    #[test]
+    fn emulated_time() {
+        let ntsc = Timing::Ntsc;
+        assert_eq!(ntsc.cycles(Duration::from_secs(1)), 1_789_773);
+        assert_eq!(ntsc.cycles(Duration::from_millis(500)), 894_886);
+        assert_eq!(ntsc.duration(1_789_773), Duration::from_secs(1));
+        assert_eq!(ntsc.frames_to_cycles(60), 1_786_860);
+        assert_eq!(ntsc.cycles_to_frames(1_789_773), 60);
+        assert_eq!(Timing::Pal.cycles_to_frames(Timing::Pal.cpu_hz()), 50);
+        assert_eq!(ntsc.frame_duration().as_micros(), 16_639);
+        assert_eq!("PAL".parse(), Ok(Timing::Pal));
+        assert!("secam".parse::<Timing>().is_err());
+
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xe8, // $0600 INX
+            0x4c, 0x00, 0x06, // $0601 JMP $0600
+        ]);
+
+        // 5 cycles a loop, and a millisecond of 1789 cycles ends a cycle into the 358th
+        let ran = advance(&mut cpu, ntsc, Duration::from_millis(1));
+        assert_eq!((ran, cpu.instructions), (1_790, 716));
+        assert_eq!(elapsed(&cpu, ntsc), ntsc.duration(1_790));
+        assert!(elapsed(&cpu, ntsc) >= Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+
+use crate::soak::{SoakResult, Status};
+
+/// Where the database lives unless told otherwise.
+pub const DEFAULT_PATH: &str = "rusty6502-compat.tsv";
+
+/// FNV-1a over the ROM file, the key for every database entry.
+pub fn rom_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// What is known about one ROM. `status` and `detail` come from the last soak
+/// run; `settings` and `notes` are written by hand and kept across runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub status: String,
+    pub detail: String,
+    pub settings: String,
+    pub notes: String,
+}
+
+/// Tab-separated, one ROM per line:
+///   hash  name  status  detail  settings  notes
+/// Lines starting with # are comments.
+#[derive(Default)]
+pub struct Database {
+    pub entries: BTreeMap<u64, Entry>,
+}
+
+fn field(s: &str) -> String {
+    s.replace(['\t', '\n'], " ")
+}
+
+impl Database {
+    /// Reads the database at `path`; a missing file is an empty database.
+    pub fn load(path: &str) -> io::Result<Database> {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Database::default()),
+            Err(e) => return Err(e),
+        };
+
+        let mut db = Database::default();
+        for line in text.lines() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut cols = line.split('\t');
+            let hash = cols
+                .next()
+                .and_then(|h| u64::from_str_radix(h, 16).ok())
+                .ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, format!("bad hash: {}", line))
+                })?;
+            let mut col = || cols.next().unwrap_or("").to_string();
+            let entry = Entry {
+                name: col(),
+                status: col(),
+                detail: col(),
+                settings: col(),
+                notes: col(),
+            };
+            db.entries.insert(hash, entry);
+        }
+        Ok(db)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut out = String::from("# hash\tname\tstatus\tdetail\tsettings\tnotes\n");
+        for (hash, e) in &self.entries {
+            out += &format!(
+                "{:016x}\t{}\t{}\t{}\t{}\t{}\n",
+                hash,
+                field(&e.name),
+                field(&e.status),
+                field(&e.detail),
+                field(&e.settings),
+                field(&e.notes)
+            );
+        }
+        fs::write(path, out)
+    }
+
+    /// Updates the tested status of every hashed soak result, keeping any
+    /// hand-written settings and notes.
+    pub fn record(&mut self, results: &[SoakResult]) {
+        for r in results {
+            if let Some(hash) = r.hash {
+                let e = self.entries.entry(hash).or_default();
+                e.name.clone_from(&r.name);
+                e.status = r.status.as_str().to_string();
+                e.detail.clone_from(&r.detail);
+            }
+        }
+    }
+
+    /// A message for the user if the ROM is known to misbehave or needs
+    /// particular settings.
+    pub fn warning(&self, hash: u64) -> Option<String> {
+        let e = self.entries.get(&hash)?;
+        let good = [Status::Ok.as_str(), Status::Halted.as_str()];
+
+        let mut lines = Vec::new();
+        if !good.contains(&e.status.as_str()) {
+            lines.push(format!("last tested as {} {}", e.status, e.detail));
+        }
+        if !e.notes.is_empty() {
+            lines.push(format!("known issues: {}", e.notes));
+        }
+        if !e.settings.is_empty() {
+            lines.push(format!("recommended settings: {}", e.settings));
+        }
+
+        (!lines.is_empty()).then(|| format!("{}: {}", e.name, lines.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compat::*;
+
+    #[test]
+    fn record_and_reload() {
+        let path = std::env::temp_dir().join("rusty6502-compat.tsv");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            "# comment\n00000000000000aa\told.nes\tok\t\t--magic 00\tflickers\n",
+        )
+        .unwrap();
+
+        let mut db = Database::load(path).unwrap();
+        db.record(&[
+            SoakResult {
+                name: "old.nes".to_string(),
+                hash: Some(0xaa),
+                status: Status::IllegalOpcode,
+                detail: "$FF at $8000".to_string(),
+                cycles: 1,
+                blank: None,
+            },
+            SoakResult {
+                name: "new.bin".to_string(),
+                hash: Some(0xbb),
+                status: Status::Halted,
+                detail: String::new(),
+                cycles: 1,
+                blank: Some(false),
+            },
+        ]);
+        db.save(path).unwrap();
+
+        let db = Database::load(path).unwrap();
+        assert_eq!(
+            db.warning(0xaa).unwrap(),
+            "old.nes: last tested as illegal-opcode $FF at $8000; known issues: flickers; \
+             recommended settings: --magic 00"
+        );
+        assert_eq!(db.warning(0xbb), None);
+        assert_eq!(db.warning(0xcc), None);
+
+        fs::remove_file(path).unwrap();
+        assert!(Database::load(path).unwrap().entries.is_empty());
+    }
+}
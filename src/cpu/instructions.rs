@@ -10,7 +10,7 @@ impl Data {
     fn default_unwrap(d: Data, cpu: &mut CPU) -> u8 {
         match d {
             Data::Immediate(x) => x as u8,
-            Data::Address(x) => cpu.bus.read(x),
+            Data::Address(x) => cpu.read(x),
         }
     }
 
@@ -24,12 +24,12 @@ impl Data {
     fn int_unwrap(d: Data, cpu: &mut CPU) -> i8 {
         match d {
             Data::Immediate(x) => x as i8,
-            Data::Address(x) => cpu.bus.read(x) as i8,
+            Data::Address(x) => cpu.read(x) as i8,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Addrmode {
     A,
     Abs,
@@ -74,40 +74,42 @@ impl Addrmode {
                 (Address(addr), page_crossed(base, addr))
             }
             Zpg => (Address(cpu.u8_operand() as u16), false),
-            ZpgX => (
-                Address(cpu.u8_operand().wrapping_add(cpu.reg.x) as u16),
-                false,
-            ),
-            ZpgY => (
-                Address(cpu.u8_operand().wrapping_add(cpu.reg.y) as u16),
-                false,
-            ),
+            // the chip reads the unindexed address while it adds the index
+            ZpgX => {
+                let base = cpu.u8_operand();
+                cpu.dummy_read(base as u16);
+                (Address(base.wrapping_add(cpu.reg.x) as u16), false)
+            }
+            ZpgY => {
+                let base = cpu.u8_operand();
+                cpu.dummy_read(base as u16);
+                (Address(base.wrapping_add(cpu.reg.y) as u16), false)
+            }
             Ind => (
                 {
+                    // the NMOS bug: the pointer's high byte never carries
+                    // into the next page, JMP ($10FF) reads $10FF and $1000
                     let adr = cpu.u16_operand();
-                    Address(join_bytes(
-                        cpu.bus.read(adr),
-                        cpu.bus.read(adr.wrapping_add(1)),
-                    ))
+                    let hi_adr = (adr & 0xFF00) | (adr.wrapping_add(1) & 0x00FF);
+                    Address(join_bytes(cpu.read(adr), cpu.read(hi_adr)))
                 },
                 false,
             ),
             XInd => (
                 {
                     let zp_base = cpu.u8_operand();
+                    cpu.dummy_read(zp_base as u16);
                     let ptr = zp_base.wrapping_add(cpu.reg.x);
-                    let lo = cpu.bus.read(ptr as u16);
-                    let hi = cpu.bus.read(ptr.wrapping_add(1) as u16);
+                    let lo = cpu.read(ptr as u16);
+                    let hi = cpu.read(ptr.wrapping_add(1) as u16);
                     Address(join_bytes(lo, hi))
                 },
                 false,
             ),
             IndY => {
                 let base = cpu.u8_operand();
-                let baseptr = join_bytes(
-                    cpu.bus.read(base as u16),
-                    cpu.bus.read(base.wrapping_add(1) as u16),
-                );
+                let baseptr =
+                    join_bytes(cpu.read(base as u16), cpu.read(base.wrapping_add(1) as u16));
                 let new = baseptr.wrapping_add(cpu.reg.y as u16);
                 (Address(new), page_crossed(baseptr, new))
             }
@@ -122,39 +124,99 @@ pub struct Instr {
     pub cycles: u8,
 }
 
+impl Instr {
+    /// Whether crossing a page while indexing costs an extra cycle. Only
+    /// reads pay it; stores and read-modify-writes always take the fix-up
+    /// cycle, and it is already in their count.
+    pub fn page_penalty(&self) -> bool {
+        use Addrmode::*;
+        matches!((self.mode, self.cycles), (AbsX | AbsY, 4) | (IndY, 5))
+    }
+
+    /// Whether the chip reads from the address before carrying the index
+    /// into its high byte: every store and read-modify-write in the 16-bit
+    /// indexed modes, and a read only when that carry costs it a cycle.
+    pub fn reads_uncarried(&self, pagecross: bool) -> bool {
+        use Addrmode::*;
+        matches!(self.mode, AbsX | AbsY | IndY) && (pagecross || !self.page_penalty())
+    }
+}
+
 pub mod instruction_set {
     use crate::cpu::instructions::Data;
-    use crate::cpu::CPU;
+    use crate::cpu::{CPU, IRQ_VECTOR};
     pub fn adc(d: Data, cpu: &mut CPU) {
         let w = Data::default_unwrap(d, cpu);
 
-        let sum: u16 = cpu.reg.a as u16 + w as u16 + if cpu.flags.carry { 1 } else { 0 };
+        let carry = cpu.flags.carry;
+        let sum: u16 = cpu.reg.a as u16 + w as u16 + if carry { 1 } else { 0 };
         let result = sum as u8;
 
         cpu.flags.carry = sum > 0xFF;
         cpu.flags.set_zero_negative(result);
         // cpu.flags.overflow = (cpu.reg.a >> 7) == (w >> 7) && (cpu.reg.a >> 7) != (result >> 7);
         cpu.flags.overflow = (w ^ result) & (cpu.reg.a ^ result) & 0x80 != 0;
+        if cpu.decimal_mode() {
+            cpu.reg.a = add_decimal(cpu.reg.a, w, carry, cpu);
+            return;
+        }
         cpu.reg.a = result;
     }
 
+    /// NMOS BCD addition. Z stays as the binary sum left it, N and V come
+    /// from the sum before the high digit is adjusted.
+    fn add_decimal(a: u8, w: u8, carry: bool, cpu: &mut CPU) -> u8 {
+        let mut lo = (a & 0x0f) + (w & 0x0f) + carry as u8;
+        if lo > 0x09 {
+            lo = ((lo + 0x06) & 0x0f) + 0x10;
+        }
+        let mut sum = (a & 0xf0) as u16 + (w & 0xf0) as u16 + lo as u16;
+
+        cpu.flags.negative = sum & 0x80 != 0;
+        cpu.flags.overflow = (w ^ sum as u8) & (a ^ sum as u8) & 0x80 != 0;
+        if sum > 0x9f {
+            sum += 0x60;
+        }
+        cpu.flags.carry = sum > 0xff;
+        sum as u8
+    }
+
+    /// NMOS BCD subtraction, borrowing when C is clear. The flags are
+    /// those of the binary subtraction.
+    fn sub_decimal(a: u8, q: u8, carry: bool) -> u8 {
+        let mut lo = (a & 0x0f) as i16 - (q & 0x0f) as i16 + carry as i16 - 1;
+        if lo < 0 {
+            lo = ((lo - 0x06) & 0x0f) - 0x10;
+        }
+        let mut diff = (a & 0xf0) as i16 - (q & 0xf0) as i16 + lo;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        diff as u8
+    }
+
     pub fn sbc(d: Data, cpu: &mut CPU) {
         let q = Data::default_unwrap(d, cpu);
         let w = (q as i8).wrapping_neg().wrapping_sub(1) as u8;
 
-        let sum: u16 = cpu.reg.a as u16 + w as u16 + if cpu.flags.carry { 1 } else { 0 };
+        let carry = cpu.flags.carry;
+        let sum: u16 = cpu.reg.a as u16 + w as u16 + if carry { 1 } else { 0 };
         let result = sum as u8;
 
         cpu.flags.carry = sum > 0xFF;
         cpu.flags.set_zero_negative(result);
         cpu.flags.overflow = (w ^ result) & (cpu.reg.a ^ result) & 0x80 != 0;
-        cpu.reg.a = result;
+        cpu.reg.a = if cpu.decimal_mode() {
+            sub_decimal(cpu.reg.a, q, carry)
+        } else {
+            result
+        };
     }
 
     pub fn inc(d: Data, cpu: &mut CPU) {
-        let q = Data::default_unwrap(d, cpu).wrapping_add(1);
-        let addr = Data::address_unwrap(d);
-        cpu.bus.write(addr, q);
+        let w = Data::default_unwrap(d, cpu);
+        let q = w.wrapping_add(1);
+        modify(cpu, Data::address_unwrap(d), w, q);
         cpu.flags.set_zero_negative(q);
     }
 
@@ -169,9 +231,9 @@ pub mod instruction_set {
     }
 
     pub fn dec(d: Data, cpu: &mut CPU) {
-        let q = Data::default_unwrap(d, cpu).wrapping_sub(1);
-        let addr = Data::address_unwrap(d);
-        cpu.bus.write(addr, q);
+        let w = Data::default_unwrap(d, cpu);
+        let q = w.wrapping_sub(1);
+        modify(cpu, Data::address_unwrap(d), w, q);
         cpu.flags.set_zero_negative(q);
     }
 
@@ -192,7 +254,7 @@ pub mod instruction_set {
 
     pub fn ldx(d: Data, cpu: &mut CPU) {
         cpu.reg.x = Data::default_unwrap(d, cpu);
-        cpu.flags.set_zero_negative(cpu.reg.y)
+        cpu.flags.set_zero_negative(cpu.reg.x);
     }
 
     pub fn lda(d: Data, cpu: &mut CPU) {
@@ -201,15 +263,15 @@ pub mod instruction_set {
     }
 
     pub fn sta(d: Data, cpu: &mut CPU) {
-        cpu.bus.write(Data::address_unwrap(d), cpu.reg.a);
+        cpu.write(Data::address_unwrap(d), cpu.reg.a);
     }
 
     pub fn stx(d: Data, cpu: &mut CPU) {
-        cpu.bus.write(Data::address_unwrap(d), cpu.reg.x);
+        cpu.write(Data::address_unwrap(d), cpu.reg.x);
     }
 
     pub fn sty(d: Data, cpu: &mut CPU) {
-        cpu.bus.write(Data::address_unwrap(d), cpu.reg.y);
+        cpu.write(Data::address_unwrap(d), cpu.reg.y);
     }
 
     pub fn tax(_: Data, cpu: &mut CPU) {
@@ -280,47 +342,54 @@ pub mod instruction_set {
         cpu.flags.set_zero_negative(cpu.reg.a);
     }
 
+    /// A read-modify-write's two writes: the byte as read while the
+    /// chip works on it, then the result.
+    fn modify(cpu: &mut CPU, adr: u16, w: u8, q: u8) {
+        cpu.dummy_write(adr, w);
+        cpu.write(adr, q);
+    }
+
+    // The shifts and rotates work on A in accumulator mode, on memory otherwise
+    fn write_back(d: Data, cpu: &mut CPU, w: u8, q: u8) {
+        match d {
+            Data::Immediate(_) => cpu.reg.a = q,
+            Data::Address(adr) => modify(cpu, adr, w, q),
+        }
+        cpu.flags.set_zero_negative(q);
+    }
+
+    fn shifted_left(w: u8, cpu: &mut CPU, carry_in: bool) -> u8 {
+        cpu.flags.carry = w >> 7 == 1;
+        w << 1 | carry_in as u8
+    }
+
+    fn shifted_right(w: u8, cpu: &mut CPU, carry_in: bool) -> u8 {
+        cpu.flags.carry = w & 1 == 1;
+        w >> 1 | (carry_in as u8) << 7
+    }
+
     pub fn asl(d: Data, cpu: &mut CPU) {
-        let mut w = Data::default_unwrap(d, cpu) as u16;
-        w <<= 1;
-        cpu.flags.carry = w >= 0xFF;
-        cpu.reg.a = w as u8;
-        cpu.flags.set_zero_negative(cpu.reg.a);
+        let w = Data::default_unwrap(d, cpu);
+        let q = shifted_left(w, cpu, false);
+        write_back(d, cpu, w, q);
     }
 
     pub fn lsr(d: Data, cpu: &mut CPU) {
         let w = Data::default_unwrap(d, cpu);
-        cpu.flags.carry = w & 1 == 1;
-        cpu.reg.a = w >> 1;
-        cpu.flags.set_zero_negative(w);
+        let q = shifted_right(w, cpu, false);
+        write_back(d, cpu, w, q);
     }
 
     pub fn rol(d: Data, cpu: &mut CPU) {
         let w = Data::default_unwrap(d, cpu);
-        let c = cpu.flags.carry;
-        cpu.flags.carry = w >> 7 == 1;
-        let mut q = w << 1;
-
-        if c {
-            q |= 1;
-        }
-
-        cpu.reg.a = q;
-        cpu.flags.set_zero_negative(q);
+        let q = shifted_left(w, cpu, cpu.flags.carry);
+        write_back(d, cpu, w, q);
     }
 
     pub fn ror(d: Data, cpu: &mut CPU) {
         let w = Data::default_unwrap(d, cpu);
-        let c = cpu.flags.carry;
-        cpu.flags.carry = w & 1 == 1;
-        let mut q = w >> 1;
-
-        if c {
-            q |= 0x80;
-        }
-
-        cpu.reg.a = q;
-        cpu.flags.set_zero_negative(q);
+        let q = shifted_right(w, cpu, cpu.flags.carry);
+        write_back(d, cpu, w, q);
     }
 
     pub fn clc(_: Data, cpu: &mut CPU) {
@@ -418,20 +487,37 @@ pub mod instruction_set {
     }
 
     pub fn jsr(d: Data, cpu: &mut CPU) {
-        cpu.stack_push(cpu.pc.wrapping_add(1));
+        // the return address pushed is the last byte of the JSR
+        cpu.stack_push16(cpu.pc);
         cpu.pc = Data::address_unwrap(d).wrapping_sub(1);
     }
 
     pub fn rts(_: Data, cpu: &mut CPU) {
-        cpu.pc = cpu.stack_pop16().wrapping_sub(1);
+        cpu.pc = cpu.stack_pop16();
     }
 
     pub fn brk(_: Data, cpu: &mut CPU) {
-        cpu.halted = true;
+        if cpu.stop_on_brk {
+            cpu.halted = true;
+            return;
+        }
+        // the byte after BRK is skipped, and B tells the handler it was BRK
+        cpu.stack_push16(cpu.pc.wrapping_add(2));
+        cpu.stack_push((u8::from(cpu.flags) | 0b110000) as u16);
+        cpu.flags.interrupt_disable = true;
+        cpu.pc = cpu.read_vector(IRQ_VECTOR).wrapping_sub(1);
+    }
+
+    pub fn jam(_: Data, cpu: &mut CPU) {
+        // stay on the opcode until reset
+        cpu.pc = cpu.pc.wrapping_sub(1);
+        cpu.jammed = true;
     }
 
-    pub fn rti(_: Data, _cpu: &mut CPU) {
-        // do nothing for now
+    pub fn rti(d: Data, cpu: &mut CPU) {
+        plp(d, cpu);
+        // interrupts push the address of the next instruction
+        cpu.pc = cpu.stack_pop16().wrapping_sub(1);
     }
 
     pub fn bit(d: Data, cpu: &mut CPU) {
@@ -442,4 +528,152 @@ pub mod instruction_set {
     }
 
     pub fn nop(_: Data, _cpu: &mut CPU) {}
+
+    // Unstable illegal opcodes, both depend on the chip's magic constant
+    pub fn ane(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        cpu.reg.a = (cpu.reg.a | cpu.magic.value()) & cpu.reg.x & w;
+        cpu.flags.set_zero_negative(cpu.reg.a);
+    }
+
+    pub fn lxa(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        cpu.reg.a = (cpu.reg.a | cpu.magic.value()) & w;
+        cpu.reg.x = cpu.reg.a;
+        cpu.flags.set_zero_negative(cpu.reg.a);
+    }
+
+    // Stable illegal opcodes. The read-modify-writes change memory as the
+    // documented instruction would, then hand the result to a second one.
+    pub fn slo(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        let q = shifted_left(w, cpu, false);
+        write_back(d, cpu, w, q);
+        ora(Data::Immediate(q as u16), cpu);
+    }
+
+    pub fn rla(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        let q = shifted_left(w, cpu, cpu.flags.carry);
+        write_back(d, cpu, w, q);
+        and(Data::Immediate(q as u16), cpu);
+    }
+
+    pub fn sre(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        let q = shifted_right(w, cpu, false);
+        write_back(d, cpu, w, q);
+        eor(Data::Immediate(q as u16), cpu);
+    }
+
+    pub fn rra(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        let q = shifted_right(w, cpu, cpu.flags.carry);
+        write_back(d, cpu, w, q);
+        adc(Data::Immediate(q as u16), cpu);
+    }
+
+    pub fn dcp(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        let q = w.wrapping_sub(1);
+        modify(cpu, Data::address_unwrap(d), w, q);
+        cmp(Data::Immediate(q as u16), cpu);
+    }
+
+    pub fn isc(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        let q = w.wrapping_add(1);
+        modify(cpu, Data::address_unwrap(d), w, q);
+        sbc(Data::Immediate(q as u16), cpu);
+    }
+
+    pub fn sax(d: Data, cpu: &mut CPU) {
+        cpu.write(Data::address_unwrap(d), cpu.reg.a & cpu.reg.x);
+    }
+
+    pub fn lax(d: Data, cpu: &mut CPU) {
+        cpu.reg.a = Data::default_unwrap(d, cpu);
+        cpu.reg.x = cpu.reg.a;
+        cpu.flags.set_zero_negative(cpu.reg.a);
+    }
+
+    pub fn anc(d: Data, cpu: &mut CPU) {
+        and(d, cpu);
+        cpu.flags.carry = cpu.flags.negative;
+    }
+
+    pub fn alr(d: Data, cpu: &mut CPU) {
+        and(d, cpu);
+        lsr(Data::Immediate(cpu.reg.a as u16), cpu);
+    }
+
+    pub fn arr(d: Data, cpu: &mut CPU) {
+        and(d, cpu);
+        let t = cpu.reg.a;
+        let q = t >> 1 | (cpu.flags.carry as u8) << 7;
+        cpu.reg.a = q;
+        cpu.flags.set_zero_negative(q);
+        cpu.flags.carry = q & 0x40 != 0;
+        cpu.flags.overflow = (q >> 6 ^ q >> 5) & 1 == 1;
+
+        // in BCD each digit of the rotated value is fixed up on its own
+        if cpu.decimal_mode() {
+            cpu.flags.overflow = (t ^ q) & 0x40 != 0;
+            if (t & 0x0f) + (t & 0x01) > 0x05 {
+                cpu.reg.a = q & 0xf0 | q.wrapping_add(0x06) & 0x0f;
+            }
+            cpu.flags.carry = (t & 0xf0) as u16 + (t & 0x10) as u16 > 0x50;
+            if cpu.flags.carry {
+                cpu.reg.a = cpu.reg.a.wrapping_add(0x60);
+            }
+        }
+    }
+
+    pub fn sbx(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu);
+        let ax = cpu.reg.a & cpu.reg.x;
+        cpu.flags.carry = ax >= w;
+        cpu.reg.x = ax.wrapping_sub(w);
+        cpu.flags.set_zero_negative(cpu.reg.x);
+    }
+
+    pub fn las(d: Data, cpu: &mut CPU) {
+        let w = Data::default_unwrap(d, cpu) & cpu.reg.sp;
+        cpu.reg.a = w;
+        cpu.reg.x = w;
+        cpu.reg.sp = w;
+        cpu.flags.set_zero_negative(w);
+    }
+
+    // The unstable stores AND the value with the high byte of the base
+    // address plus one. When indexing crosses a page the result lands in
+    // the high byte of the address as well.
+    fn store_high(cpu: &mut CPU, d: Data, index: u8, value: u8) {
+        let adr = Data::address_unwrap(d);
+        let base = adr.wrapping_sub(index as u16);
+        let q = value & ((base >> 8) as u8).wrapping_add(1);
+        let adr = if base & 0xFF00 != adr & 0xFF00 {
+            (q as u16) << 8 | adr & 0xFF
+        } else {
+            adr
+        };
+        cpu.write(adr, q);
+    }
+
+    pub fn sha(d: Data, cpu: &mut CPU) {
+        store_high(cpu, d, cpu.reg.y, cpu.reg.a & cpu.reg.x);
+    }
+
+    pub fn shx(d: Data, cpu: &mut CPU) {
+        store_high(cpu, d, cpu.reg.y, cpu.reg.x);
+    }
+
+    pub fn shy(d: Data, cpu: &mut CPU) {
+        store_high(cpu, d, cpu.reg.x, cpu.reg.y);
+    }
+
+    pub fn tas(d: Data, cpu: &mut CPU) {
+        cpu.reg.sp = cpu.reg.a & cpu.reg.x;
+        store_high(cpu, d, cpu.reg.y, cpu.reg.sp);
+    }
 }
@@ -13,6 +13,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: XInd,
             cycles: 6,
         },
+        0x02 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x03 => Instr {
+            run: slo,
+            mode: XInd,
+            cycles: 8,
+        },
+        0x04 => Instr {
+            run: nop,
+            mode: Zpg,
+            cycles: 3,
+        },
         0x05 => Instr {
             run: ora,
             mode: Zpg,
@@ -23,6 +38,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 5,
         },
+        0x07 => Instr {
+            run: slo,
+            mode: Zpg,
+            cycles: 5,
+        },
         0x08 => Instr {
             run: php,
             mode: Impl,
@@ -38,6 +58,16 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: A,
             cycles: 2,
         },
+        0x0B => Instr {
+            run: anc,
+            mode: Imm,
+            cycles: 2,
+        },
+        0x0C => Instr {
+            run: nop,
+            mode: Abs,
+            cycles: 4,
+        },
         0x0D => Instr {
             run: ora,
             mode: Abs,
@@ -48,6 +78,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 6,
         },
+        0x0F => Instr {
+            run: slo,
+            mode: Abs,
+            cycles: 6,
+        },
         0x10 => Instr {
             run: bpl,
             mode: Rel,
@@ -58,6 +93,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 5,
         },
+        0x12 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x13 => Instr {
+            run: slo,
+            mode: IndY,
+            cycles: 8,
+        },
+        0x14 => Instr {
+            run: nop,
+            mode: ZpgX,
+            cycles: 4,
+        },
         0x15 => Instr {
             run: ora,
             mode: ZpgX,
@@ -68,6 +118,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgX,
             cycles: 6,
         },
+        0x17 => Instr {
+            run: slo,
+            mode: ZpgX,
+            cycles: 6,
+        },
         0x18 => Instr {
             run: clc,
             mode: Impl,
@@ -78,6 +133,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsY,
             cycles: 4,
         },
+        0x1A => Instr {
+            run: nop,
+            mode: Impl,
+            cycles: 2,
+        },
+        0x1B => Instr {
+            run: slo,
+            mode: AbsY,
+            cycles: 7,
+        },
+        0x1C => Instr {
+            run: nop,
+            mode: AbsX,
+            cycles: 4,
+        },
         0x1D => Instr {
             run: ora,
             mode: AbsX,
@@ -88,6 +158,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsX,
             cycles: 7,
         },
+        0x1F => Instr {
+            run: slo,
+            mode: AbsX,
+            cycles: 7,
+        },
         0x20 => Instr {
             run: jsr,
             mode: Abs,
@@ -98,6 +173,16 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: XInd,
             cycles: 6,
         },
+        0x22 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x23 => Instr {
+            run: rla,
+            mode: XInd,
+            cycles: 8,
+        },
         0x24 => Instr {
             run: bit,
             mode: Zpg,
@@ -113,6 +198,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 5,
         },
+        0x27 => Instr {
+            run: rla,
+            mode: Zpg,
+            cycles: 5,
+        },
         0x28 => Instr {
             run: plp,
             mode: Impl,
@@ -128,6 +218,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: A,
             cycles: 2,
         },
+        0x2B => Instr {
+            run: anc,
+            mode: Imm,
+            cycles: 2,
+        },
         0x2C => Instr {
             run: bit,
             mode: Abs,
@@ -143,6 +238,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 6,
         },
+        0x2F => Instr {
+            run: rla,
+            mode: Abs,
+            cycles: 6,
+        },
         0x30 => Instr {
             run: bmi,
             mode: Rel,
@@ -153,6 +253,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 5,
         },
+        0x32 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x33 => Instr {
+            run: rla,
+            mode: IndY,
+            cycles: 8,
+        },
+        0x34 => Instr {
+            run: nop,
+            mode: ZpgX,
+            cycles: 4,
+        },
         0x35 => Instr {
             run: and,
             mode: ZpgX,
@@ -163,6 +278,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgX,
             cycles: 6,
         },
+        0x37 => Instr {
+            run: rla,
+            mode: ZpgX,
+            cycles: 6,
+        },
         0x38 => Instr {
             run: sec,
             mode: Impl,
@@ -173,6 +293,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsY,
             cycles: 4,
         },
+        0x3A => Instr {
+            run: nop,
+            mode: Impl,
+            cycles: 2,
+        },
+        0x3B => Instr {
+            run: rla,
+            mode: AbsY,
+            cycles: 7,
+        },
+        0x3C => Instr {
+            run: nop,
+            mode: AbsX,
+            cycles: 4,
+        },
         0x3D => Instr {
             run: and,
             mode: AbsX,
@@ -183,6 +318,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsX,
             cycles: 7,
         },
+        0x3F => Instr {
+            run: rla,
+            mode: AbsX,
+            cycles: 7,
+        },
         0x40 => Instr {
             run: rti,
             mode: Impl,
@@ -193,6 +333,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: XInd,
             cycles: 6,
         },
+        0x42 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x43 => Instr {
+            run: sre,
+            mode: XInd,
+            cycles: 8,
+        },
+        0x44 => Instr {
+            run: nop,
+            mode: Zpg,
+            cycles: 3,
+        },
         0x45 => Instr {
             run: eor,
             mode: Zpg,
@@ -203,6 +358,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 5,
         },
+        0x47 => Instr {
+            run: sre,
+            mode: Zpg,
+            cycles: 5,
+        },
         0x48 => Instr {
             run: pha,
             mode: Impl,
@@ -218,6 +378,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: A,
             cycles: 2,
         },
+        0x4B => Instr {
+            run: alr,
+            mode: Imm,
+            cycles: 2,
+        },
         0x4C => Instr {
             run: jmp,
             mode: Abs,
@@ -233,6 +398,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 6,
         },
+        0x4F => Instr {
+            run: sre,
+            mode: Abs,
+            cycles: 6,
+        },
         0x50 => Instr {
             run: bvc,
             mode: Rel,
@@ -243,6 +413,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 5,
         },
+        0x52 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x53 => Instr {
+            run: sre,
+            mode: IndY,
+            cycles: 8,
+        },
+        0x54 => Instr {
+            run: nop,
+            mode: ZpgX,
+            cycles: 4,
+        },
         0x55 => Instr {
             run: eor,
             mode: ZpgX,
@@ -253,6 +438,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgX,
             cycles: 6,
         },
+        0x57 => Instr {
+            run: sre,
+            mode: ZpgX,
+            cycles: 6,
+        },
         0x58 => Instr {
             run: cli,
             mode: Impl,
@@ -263,6 +453,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsY,
             cycles: 4,
         },
+        0x5A => Instr {
+            run: nop,
+            mode: Impl,
+            cycles: 2,
+        },
+        0x5B => Instr {
+            run: sre,
+            mode: AbsY,
+            cycles: 7,
+        },
+        0x5C => Instr {
+            run: nop,
+            mode: AbsX,
+            cycles: 4,
+        },
         0x5D => Instr {
             run: eor,
             mode: AbsX,
@@ -273,6 +478,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsX,
             cycles: 7,
         },
+        0x5F => Instr {
+            run: sre,
+            mode: AbsX,
+            cycles: 7,
+        },
         0x60 => Instr {
             run: rts,
             mode: Impl,
@@ -283,6 +493,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: XInd,
             cycles: 6,
         },
+        0x62 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x63 => Instr {
+            run: rra,
+            mode: XInd,
+            cycles: 8,
+        },
+        0x64 => Instr {
+            run: nop,
+            mode: Zpg,
+            cycles: 3,
+        },
         0x65 => Instr {
             run: adc,
             mode: Zpg,
@@ -293,6 +518,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 5,
         },
+        0x67 => Instr {
+            run: rra,
+            mode: Zpg,
+            cycles: 5,
+        },
         0x68 => Instr {
             run: pla,
             mode: Impl,
@@ -308,6 +538,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: A,
             cycles: 2,
         },
+        0x6B => Instr {
+            run: arr,
+            mode: Imm,
+            cycles: 2,
+        },
         0x6C => Instr {
             run: jmp,
             mode: Ind,
@@ -323,6 +558,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 6,
         },
+        0x6F => Instr {
+            run: rra,
+            mode: Abs,
+            cycles: 6,
+        },
         0x70 => Instr {
             run: bvs,
             mode: Rel,
@@ -333,6 +573,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 5,
         },
+        0x72 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x73 => Instr {
+            run: rra,
+            mode: IndY,
+            cycles: 8,
+        },
+        0x74 => Instr {
+            run: nop,
+            mode: ZpgX,
+            cycles: 4,
+        },
         0x75 => Instr {
             run: adc,
             mode: ZpgX,
@@ -343,6 +598,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgX,
             cycles: 6,
         },
+        0x77 => Instr {
+            run: rra,
+            mode: ZpgX,
+            cycles: 6,
+        },
         0x78 => Instr {
             run: sei,
             mode: Impl,
@@ -353,6 +613,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsY,
             cycles: 4,
         },
+        0x7A => Instr {
+            run: nop,
+            mode: Impl,
+            cycles: 2,
+        },
+        0x7B => Instr {
+            run: rra,
+            mode: AbsY,
+            cycles: 7,
+        },
+        0x7C => Instr {
+            run: nop,
+            mode: AbsX,
+            cycles: 4,
+        },
         0x7D => Instr {
             run: adc,
             mode: AbsX,
@@ -363,11 +638,31 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsX,
             cycles: 7,
         },
+        0x7F => Instr {
+            run: rra,
+            mode: AbsX,
+            cycles: 7,
+        },
+        0x80 => Instr {
+            run: nop,
+            mode: Imm,
+            cycles: 2,
+        },
         0x81 => Instr {
             run: sta,
             mode: XInd,
             cycles: 6,
         },
+        0x82 => Instr {
+            run: nop,
+            mode: Imm,
+            cycles: 2,
+        },
+        0x83 => Instr {
+            run: sax,
+            mode: XInd,
+            cycles: 6,
+        },
         0x84 => Instr {
             run: sty,
             mode: Zpg,
@@ -383,16 +678,31 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 3,
         },
+        0x87 => Instr {
+            run: sax,
+            mode: Zpg,
+            cycles: 3,
+        },
         0x88 => Instr {
             run: dey,
             mode: Impl,
             cycles: 2,
         },
+        0x89 => Instr {
+            run: nop,
+            mode: Imm,
+            cycles: 2,
+        },
         0x8A => Instr {
             run: txa,
             mode: Impl,
             cycles: 2,
         },
+        0x8B => Instr {
+            run: ane,
+            mode: Imm,
+            cycles: 2,
+        },
         0x8C => Instr {
             run: sty,
             mode: Abs,
@@ -408,6 +718,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 4,
         },
+        0x8F => Instr {
+            run: sax,
+            mode: Abs,
+            cycles: 4,
+        },
         0x90 => Instr {
             run: bcc,
             mode: Rel,
@@ -418,6 +733,16 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 6,
         },
+        0x92 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0x93 => Instr {
+            run: sha,
+            mode: IndY,
+            cycles: 6,
+        },
         0x94 => Instr {
             run: sty,
             mode: ZpgX,
@@ -433,6 +758,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgY,
             cycles: 4,
         },
+        0x97 => Instr {
+            run: sax,
+            mode: ZpgY,
+            cycles: 4,
+        },
         0x98 => Instr {
             run: tya,
             mode: Impl,
@@ -448,11 +778,31 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Impl,
             cycles: 2,
         },
+        0x9B => Instr {
+            run: tas,
+            mode: AbsY,
+            cycles: 5,
+        },
+        0x9C => Instr {
+            run: shy,
+            mode: AbsX,
+            cycles: 5,
+        },
         0x9D => Instr {
             run: sta,
             mode: AbsX,
             cycles: 5,
         },
+        0x9E => Instr {
+            run: shx,
+            mode: AbsY,
+            cycles: 5,
+        },
+        0x9F => Instr {
+            run: sha,
+            mode: AbsY,
+            cycles: 5,
+        },
         0xA0 => Instr {
             run: ldy,
             mode: Imm,
@@ -468,6 +818,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Imm,
             cycles: 2,
         },
+        0xA3 => Instr {
+            run: lax,
+            mode: XInd,
+            cycles: 6,
+        },
         0xA4 => Instr {
             run: ldy,
             mode: Zpg,
@@ -483,6 +838,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 3,
         },
+        0xA7 => Instr {
+            run: lax,
+            mode: Zpg,
+            cycles: 3,
+        },
         0xA8 => Instr {
             run: tay,
             mode: Impl,
@@ -498,6 +858,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Impl,
             cycles: 2,
         },
+        0xAB => Instr {
+            run: lxa,
+            mode: Imm,
+            cycles: 2,
+        },
         0xAC => Instr {
             run: ldy,
             mode: Abs,
@@ -513,6 +878,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 4,
         },
+        0xAF => Instr {
+            run: lax,
+            mode: Abs,
+            cycles: 4,
+        },
         0xB0 => Instr {
             run: bcs,
             mode: Rel,
@@ -523,6 +893,16 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 5,
         },
+        0xB2 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0xB3 => Instr {
+            run: lax,
+            mode: IndY,
+            cycles: 5,
+        },
         0xB4 => Instr {
             run: ldy,
             mode: ZpgX,
@@ -538,6 +918,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgY,
             cycles: 4,
         },
+        0xB7 => Instr {
+            run: lax,
+            mode: ZpgY,
+            cycles: 4,
+        },
         0xB8 => Instr {
             run: clv,
             mode: Impl,
@@ -553,6 +938,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Impl,
             cycles: 2,
         },
+        0xBB => Instr {
+            run: las,
+            mode: AbsY,
+            cycles: 4,
+        },
         0xBC => Instr {
             run: ldy,
             mode: AbsX,
@@ -568,6 +958,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsY,
             cycles: 4,
         },
+        0xBF => Instr {
+            run: lax,
+            mode: AbsY,
+            cycles: 4,
+        },
         0xC0 => Instr {
             run: cpy,
             mode: Imm,
@@ -578,6 +973,16 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: XInd,
             cycles: 6,
         },
+        0xC2 => Instr {
+            run: nop,
+            mode: Imm,
+            cycles: 2,
+        },
+        0xC3 => Instr {
+            run: dcp,
+            mode: XInd,
+            cycles: 8,
+        },
         0xC4 => Instr {
             run: cpy,
             mode: Zpg,
@@ -593,6 +998,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 5,
         },
+        0xC7 => Instr {
+            run: dcp,
+            mode: Zpg,
+            cycles: 5,
+        },
         0xC8 => Instr {
             run: iny,
             mode: Impl,
@@ -608,6 +1018,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Impl,
             cycles: 2,
         },
+        0xCB => Instr {
+            run: sbx,
+            mode: Imm,
+            cycles: 2,
+        },
         0xCC => Instr {
             run: cpy,
             mode: Abs,
@@ -623,6 +1038,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 6,
         },
+        0xCF => Instr {
+            run: dcp,
+            mode: Abs,
+            cycles: 6,
+        },
         0xD0 => Instr {
             run: bne,
             mode: Rel,
@@ -633,6 +1053,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 5,
         },
+        0xD2 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0xD3 => Instr {
+            run: dcp,
+            mode: IndY,
+            cycles: 8,
+        },
+        0xD4 => Instr {
+            run: nop,
+            mode: ZpgX,
+            cycles: 4,
+        },
         0xD5 => Instr {
             run: cmp,
             mode: ZpgX,
@@ -643,6 +1078,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgX,
             cycles: 6,
         },
+        0xD7 => Instr {
+            run: dcp,
+            mode: ZpgX,
+            cycles: 6,
+        },
         0xD8 => Instr {
             run: cld,
             mode: Impl,
@@ -653,6 +1093,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsY,
             cycles: 4,
         },
+        0xDA => Instr {
+            run: nop,
+            mode: Impl,
+            cycles: 2,
+        },
+        0xDB => Instr {
+            run: dcp,
+            mode: AbsY,
+            cycles: 7,
+        },
+        0xDC => Instr {
+            run: nop,
+            mode: AbsX,
+            cycles: 4,
+        },
         0xDD => Instr {
             run: cmp,
             mode: AbsX,
@@ -663,6 +1118,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsX,
             cycles: 7,
         },
+        0xDF => Instr {
+            run: dcp,
+            mode: AbsX,
+            cycles: 7,
+        },
         0xE0 => Instr {
             run: cpx,
             mode: Imm,
@@ -673,6 +1133,16 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: XInd,
             cycles: 6,
         },
+        0xE2 => Instr {
+            run: nop,
+            mode: Imm,
+            cycles: 2,
+        },
+        0xE3 => Instr {
+            run: isc,
+            mode: XInd,
+            cycles: 8,
+        },
         0xE4 => Instr {
             run: cpx,
             mode: Zpg,
@@ -688,6 +1158,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Zpg,
             cycles: 5,
         },
+        0xE7 => Instr {
+            run: isc,
+            mode: Zpg,
+            cycles: 5,
+        },
         0xE8 => Instr {
             run: inx,
             mode: Impl,
@@ -703,6 +1178,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Impl,
             cycles: 2,
         },
+        0xEB => Instr {
+            run: sbc,
+            mode: Imm,
+            cycles: 2,
+        },
         0xEC => Instr {
             run: cpx,
             mode: Abs,
@@ -718,6 +1198,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: Abs,
             cycles: 6,
         },
+        0xEF => Instr {
+            run: isc,
+            mode: Abs,
+            cycles: 6,
+        },
         0xF0 => Instr {
             run: beq,
             mode: Rel,
@@ -728,6 +1213,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: IndY,
             cycles: 5,
         },
+        0xF2 => Instr {
+            run: jam,
+            mode: Impl,
+            cycles: 0,
+        },
+        0xF3 => Instr {
+            run: isc,
+            mode: IndY,
+            cycles: 8,
+        },
+        0xF4 => Instr {
+            run: nop,
+            mode: ZpgX,
+            cycles: 4,
+        },
         0xF5 => Instr {
             run: sbc,
             mode: ZpgX,
@@ -738,6 +1238,11 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: ZpgX,
             cycles: 6,
         },
+        0xF7 => Instr {
+            run: isc,
+            mode: ZpgX,
+            cycles: 6,
+        },
         0xF8 => Instr {
             run: sed,
             mode: Impl,
@@ -748,6 +1253,21 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsY,
             cycles: 4,
         },
+        0xFA => Instr {
+            run: nop,
+            mode: Impl,
+            cycles: 2,
+        },
+        0xFB => Instr {
+            run: isc,
+            mode: AbsY,
+            cycles: 7,
+        },
+        0xFC => Instr {
+            run: nop,
+            mode: AbsX,
+            cycles: 4,
+        },
         0xFD => Instr {
             run: sbc,
             mode: AbsX,
@@ -758,9 +1278,429 @@ pub fn lookup(opcode: u8) -> Instr {
             mode: AbsX,
             cycles: 7,
         },
-        _ => {
-            println!("Opcode {:x}", opcode);
-            panic!("Err: Unknown instruction")
+        0xFF => Instr {
+            run: isc,
+            mode: AbsX,
+            cycles: 7,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::bus::Bus;
+    use crate::cpu::instructions::Addrmode::{self, *};
+    use crate::cpu::lookup_table::lookup;
+    use crate::cpu::{ExitReason, Flag, CPU};
+    use crate::disasm;
+
+    /// Base cycles of every opcode on the NMOS 6502, 0 for the ones that jam.
+    #[rustfmt::skip]
+    const CYCLES: [u8; 256] = [
+        7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6,
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6,
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6,
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6,
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+        2, 6, 0, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5,
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+        2, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4,
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    ];
+
+    const X: u8 = 0x01;
+    const Y: u8 = 0x02;
+
+    enum Out {
+        A,
+        X,
+        Y,
+        Mem,
+    }
+
+    /// Operations that take an operand, run in every mode they have:
+    /// (mnemonic, register in, carry in, operand, where the result goes,
+    /// result, flags after). The register is X or Y for the mnemonics
+    /// ending in them, A otherwise.
+    #[rustfmt::skip]
+    const OPERATIONS: [(&str, u8, bool, u8, Out, u8, &str); 21] = [
+        ("ADC", 0x50, false, 0x50, Out::A, 0xa0, "NV"),
+        ("SBC", 0x50, true, 0xb0, Out::A, 0xa0, "NV"),
+        ("AND", 0xf0, false, 0x0f, Out::A, 0x00, "Z"),
+        ("ORA", 0x80, false, 0x01, Out::A, 0x81, "N"),
+        ("EOR", 0xff, false, 0xff, Out::A, 0x00, "Z"),
+        ("LDA", 0x00, false, 0x80, Out::A, 0x80, "N"),
+        ("LDX", 0xff, false, 0x00, Out::X, 0x00, "Z"),
+        ("LDY", 0x00, false, 0x80, Out::Y, 0x80, "N"),
+        ("CMP", 0x40, false, 0x40, Out::A, 0x40, "ZC"),
+        ("CPX", 0x10, true, 0x20, Out::X, 0x10, "N"),
+        ("CPY", 0x30, false, 0x20, Out::Y, 0x30, "C"),
+        ("BIT", 0x01, false, 0xc0, Out::A, 0x01, "NVZ"),
+        ("STA", 0x7f, false, 0x00, Out::Mem, 0x7f, ""),
+        ("STX", 0x7f, false, 0x00, Out::Mem, 0x7f, ""),
+        ("STY", 0x7f, false, 0x00, Out::Mem, 0x7f, ""),
+        ("ASL", 0x00, false, 0x81, Out::Mem, 0x02, "C"),
+        ("LSR", 0x00, false, 0x01, Out::Mem, 0x00, "ZC"),
+        ("ROL", 0x00, true, 0x80, Out::Mem, 0x01, "C"),
+        ("ROR", 0x00, false, 0x01, Out::Mem, 0x00, "ZC"),
+        ("INC", 0x00, false, 0xff, Out::Mem, 0x00, "Z"),
+        ("DEC", 0x00, false, 0x00, Out::Mem, 0xff, "N"),
+    ];
+
+    /// Implied instructions, run with A = $80 and $FF on top of the stack:
+    /// (opcode, flags in, [A, X, Y, SP] after, flags after, byte at $01FD).
+    #[rustfmt::skip]
+    const IMPLIED: [(u8, &str, [u8; 4], &str, u8); 24] = [
+        (0xaa, "", [0x80, 0x80, Y, 0xfd], "N", 0),     // TAX
+        (0xa8, "", [0x80, X, 0x80, 0xfd], "N", 0),     // TAY
+        (0x8a, "", [X, X, Y, 0xfd], "", 0),            // TXA
+        (0x98, "", [Y, X, Y, 0xfd], "", 0),            // TYA
+        (0xba, "", [0x80, 0xfd, Y, 0xfd], "N", 0),     // TSX
+        (0x9a, "Z", [0x80, X, Y, X], "Z", 0),          // TXS
+        (0xe8, "", [0x80, 0x02, Y, 0xfd], "", 0),      // INX
+        (0xc8, "", [0x80, X, 0x03, 0xfd], "", 0),      // INY
+        (0xca, "", [0x80, 0x00, Y, 0xfd], "Z", 0),     // DEX
+        (0x88, "", [0x80, X, 0x01, 0xfd], "", 0),      // DEY
+        (0x18, "NC", [0x80, X, Y, 0xfd], "N", 0),      // CLC
+        (0x38, "", [0x80, X, Y, 0xfd], "C", 0),        // SEC
+        (0xd8, "D", [0x80, X, Y, 0xfd], "", 0),        // CLD
+        (0xf8, "", [0x80, X, Y, 0xfd], "D", 0),        // SED
+        (0x58, "I", [0x80, X, Y, 0xfd], "", 0),        // CLI
+        (0x78, "", [0x80, X, Y, 0xfd], "I", 0),        // SEI
+        (0xb8, "V", [0x80, X, Y, 0xfd], "", 0),        // CLV
+        (0xea, "NZ", [0x80, X, Y, 0xfd], "NZ", 0),     // NOP
+        (0x48, "", [0x80, X, Y, 0xfc], "", 0x80),      // PHA
+        (0x08, "NC", [0x80, X, Y, 0xfc], "NC", 0xb1),  // PHP, B and bit 5 set
+        (0x68, "", [0xff, X, Y, 0xfe], "N", 0),        // PLA
+        (0x28, "", [0x80, X, Y, 0xfe], "NVDIZC", 0),   // PLP, B and bit 5 dropped
+        (0x0a, "", [0x00, X, Y, 0xfd], "ZC", 0),       // ASL A
+        (0x6a, "C", [0xc0, X, Y, 0xfd], "N", 0),       // ROR A
+    ];
+
+    /// (opcode, flags that take the branch, flags that don't).
+    const BRANCHES: [(u8, &str, &str); 8] = [
+        (0x10, "", "N"), // BPL
+        (0x30, "N", ""), // BMI
+        (0x50, "", "V"), // BVC
+        (0x70, "V", ""), // BVS
+        (0x90, "", "C"), // BCC
+        (0xb0, "C", ""), // BCS
+        (0xd0, "", "Z"), // BNE
+        (0xf0, "Z", ""), // BEQ
+    ];
+
+    /// Undocumented operations, run in every mode they have: (mnemonic, A
+    /// in, carry in, operand, [A, X] after, byte at the effective address
+    /// after, flags after).
+    type Undocumented = (&'static str, u8, bool, u8, [u8; 2], u8, &'static str);
+
+    #[rustfmt::skip]
+    const UNDOCUMENTED: [Undocumented; 15] = [
+        ("SLO", 0x01, false, 0x81, [0x03, X], 0x02, "C"),
+        ("RLA", 0xff, true, 0x80, [0x01, X], 0x01, "C"),
+        ("SRE", 0x01, false, 0x03, [0x00, X], 0x01, "ZC"),
+        ("RRA", 0x10, true, 0x02, [0x91, X], 0x81, "N"),
+        ("DCP", 0x40, false, 0x41, [0x40, X], 0x40, "ZC"),
+        ("ISC", 0x50, true, 0xaf, [0xa0, X], 0xb0, "NV"),
+        ("SAX", 0xf3, false, 0x00, [0xf3, X], 0x01, ""),
+        ("LAX", 0x00, false, 0x80, [0x80, 0x80], 0x80, "N"),
+        ("ANC", 0xff, false, 0x80, [0x80, X], 0, "NC"),
+        ("ALR", 0xff, false, 0x03, [0x01, X], 0, "C"),
+        ("ARR", 0xff, true, 0xc0, [0xe0, X], 0, "NC"),
+        ("SBX", 0xff, false, 0x02, [0xff, 0xff], 0, "N"),
+        ("SBC", 0x50, true, 0xb0, [0xa0, X], 0, "NV"),
+        ("NOP", 0x00, true, 0x00, [0x00, X], 0, "C"),
+        ("LAS", 0x00, false, 0xf3, [0xf1, 0xf1], 0xf3, "N"),
+    ];
+
+    fn flags(s: &str) -> Flag {
+        Flag {
+            carry: s.contains('C'),
+            zero: s.contains('Z'),
+            interrupt_disable: s.contains('I'),
+            decimal: s.contains('D'),
+            b: false,
+            overflow: s.contains('V'),
+            negative: s.contains('N'),
+        }
+    }
+
+    /// A CPU about to run `code` at $0600, with the pointers the indirect
+    /// modes use in place and $FF on top of the stack.
+    fn machine(code: &[u8], f: &str) -> CPU {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(code.to_vec());
+        cpu.bus.memory.load(0x0040, &[0x00, 0x03, 0xfe, 0x02]);
+        cpu.bus.write(0x01fe, 0xff);
+        cpu.reg.x = X;
+        cpu.reg.y = Y;
+        cpu.flags = flags(f);
+        cpu
+    }
+
+    /// Operand bytes that make `mode` land on its effective address, and
+    /// that address. Every indexed absolute mode crosses a page.
+    fn operand(mode: Addrmode) -> (Vec<u8>, u16) {
+        match mode {
+            Zpg => (vec![0x30], 0x0030),
+            ZpgX => (vec![0x2f], 0x0030),
+            ZpgY => (vec![0x2e], 0x0030),
+            Abs => (vec![0x00, 0x03], 0x0300),
+            AbsX => (vec![0xff, 0x02], 0x0300),
+            AbsY => (vec![0xfe, 0x02], 0x0300),
+            XInd => (vec![0x3f], 0x0300),
+            IndY => (vec![0x42], 0x0300),
+            _ => unreachable!("{:?} has no effective address", mode),
+        }
+    }
+
+    /// Runs the single instruction `op` on `value`, wherever its mode finds
+    /// it, after `setup`. Returns the CPU and the effective address, if any.
+    fn operation(
+        op: u8,
+        value: u8,
+        carry: bool,
+        setup: impl FnOnce(&mut CPU),
+    ) -> (CPU, Option<u16>) {
+        let mode = lookup(op).mode;
+        let (code, ea) = match mode {
+            Imm => (vec![op, value], None),
+            A | Impl => (vec![op], None),
+            _ => {
+                let (bytes, ea) = operand(mode);
+                ([vec![op], bytes].concat(), Some(ea))
+            }
+        };
+
+        let mut cpu = machine(&code, if carry { "C" } else { "" });
+        setup(&mut cpu);
+        match ea {
+            Some(ea) => cpu.bus.write(ea, value),
+            None if mode == A => cpu.reg.a = value,
+            None => (),
+        }
+        cpu.exec();
+        (cpu, ea)
+    }
+
+    /// Base cycles, plus one when a read crosses a page; writes always take it.
+    fn cycles(op: u8, write: bool) -> u64 {
+        let crossed = matches!(lookup(op).mode, AbsX | AbsY | IndY);
+        (CYCLES[op as usize] + (crossed && !write) as u8) as u64
+    }
+
+    #[test]
+    fn table_matches_the_nmos_cycles() {
+        for op in 0..=255u8 {
+            assert_eq!(lookup(op).cycles, CYCLES[op as usize], "${:02X} cycles", op);
+        }
+        let official = (0..=255u8).filter(|&op| disasm::documented(op)).count();
+        assert_eq!(official, 151);
+    }
+
+    #[test]
+    fn every_official_opcode() {
+        let mut covered = BTreeSet::new();
+
+        for (name, reg, carry, value, out, result, after) in OPERATIONS {
+            let opcodes =
+                (0..=255u8).filter(|&op| disasm::documented(op) && disasm::decode(op).0 == name);
+            for op in opcodes {
+                let (mut cpu, ea) = operation(op, value, carry, |cpu| match name.as_bytes()[2] {
+                    b'X' => cpu.reg.x = reg,
+                    b'Y' => cpu.reg.y = reg,
+                    _ => cpu.reg.a = reg,
+                });
+
+                let got = match (&out, ea) {
+                    (Out::A, _) | (Out::Mem, None) => cpu.reg.a,
+                    (Out::X, _) => cpu.reg.x,
+                    (Out::Y, _) => cpu.reg.y,
+                    (Out::Mem, Some(ea)) => cpu.bus.read(ea),
+                };
+                let what = format!("{} {:?} (${:02X})", name, lookup(op).mode, op);
+                assert_eq!(got, result, "{} result", what);
+                assert_eq!(
+                    u8::from(cpu.flags),
+                    u8::from(flags(after)),
+                    "{} flags",
+                    what
+                );
+
+                let write = matches!(
+                    name,
+                    "STA" | "STX" | "STY" | "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC"
+                );
+                assert_eq!(cpu.cycles, cycles(op, write), "{} cycles", what);
+                covered.insert(op);
+            }
+        }
+
+        for (op, before, [a, x, y, sp], after, pushed) in IMPLIED {
+            let mut cpu = machine(&[op], before);
+            cpu.reg.a = 0x80;
+            cpu.exec();
+
+            let regs = [cpu.reg.a, cpu.reg.x, cpu.reg.y, cpu.reg.sp];
+            assert_eq!(regs, [a, x, y, sp], "${:02X} registers", op);
+            assert_eq!(
+                u8::from(cpu.flags),
+                u8::from(flags(after)),
+                "${:02X} flags",
+                op
+            );
+            assert_eq!(cpu.bus.read(0x01fd), pushed, "${:02X} stack", op);
+            assert_eq!(cpu.cycles, CYCLES[op as usize] as u64, "${:02X} cycles", op);
+            covered.insert(op);
+        }
+
+        for (op, taken, not_taken) in BRANCHES {
+            // a taken branch costs one more cycle, two if it crosses a page
+            // from the instruction after it
+            for (at, offset, f, pc, cycles) in [
+                (0x0600, 0x10, taken, 0x0612, 3),
+                (0x0600, 0xf0, taken, 0x05f2, 4),
+                (0x0600, 0x10, not_taken, 0x0602, 2),
+                (0x06fc, 0x01, taken, 0x06ff, 3),
+                (0x06fd, 0x01, taken, 0x0700, 4),
+                (0x06fe, 0x01, taken, 0x0701, 3),
+                (0x06fe, 0x01, not_taken, 0x0700, 2),
+                (0x06ff, 0xfe, taken, 0x06ff, 4),
+            ] {
+                let mut cpu = machine(&[], f);
+                cpu.bus.memory.load(at, &[op, offset]);
+                cpu.pc = at;
+                cpu.exec();
+                assert_eq!((cpu.pc, cpu.cycles), (pc, cycles), "${:02X} {:?}", op, f);
+            }
+            covered.insert(op);
         }
+
+        let mut cpu = machine(&[0x4c, 0x00, 0x03], ""); // JMP $0300
+        cpu.exec();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0300, 3));
+
+        // the pointer's high byte comes from $0200, not $0300
+        let mut cpu = machine(&[0x6c, 0xff, 0x02], ""); // JMP ($02FF)
+        cpu.bus.memory.load(0x02ff, &[0x00, 0x05]);
+        cpu.bus.write(0x0200, 0x04);
+        cpu.exec();
+        assert_eq!((cpu.pc, cpu.cycles), (0x0400, 5));
+
+        let mut cpu = machine(&[0x20, 0x00, 0x03], ""); // JSR $0300
+        cpu.bus.write(0x0300, 0x60); // RTS
+        cpu.exec();
+        assert_eq!((cpu.pc, cpu.reg.sp, cpu.cycles), (0x0300, 0xfb, 6));
+        assert_eq!([cpu.bus.read(0x01fc), cpu.bus.read(0x01fd)], [0x02, 0x06]);
+        cpu.exec();
+        assert_eq!((cpu.pc, cpu.reg.sp, cpu.cycles), (0x0603, 0xfd, 12));
+        // flags then the PC come off the stack, B and bit 5 dropped
+        let mut cpu = machine(&[0x40], ""); // RTI
+        cpu.bus.memory.load(0x01fd, &[0xff, 0x00, 0x03]);
+        cpu.reg.sp = 0xfc;
+        cpu.exec();
+        assert_eq!((cpu.pc, cpu.reg.sp, cpu.cycles), (0x0300, 0xff, 6));
+        assert_eq!(u8::from(cpu.flags), u8::from(flags("NVDIZC")));
+        // BRK pushes its address + 2 and the flags with B set
+        let mut cpu = machine(&[0x00], "C"); // BRK
+        cpu.bus.memory.load(0xfffe, &[0x00, 0x03]);
+        cpu.exec();
+        assert_eq!((cpu.pc, cpu.reg.sp, cpu.cycles), (0x0300, 0xfa, 7));
+        assert_eq!(cpu.bus.read(0x01fb), 0x31);
+        assert_eq!(u8::from(cpu.flags), u8::from(flags("IC")));
+        covered.extend([0x4c, 0x6c, 0x20, 0x60, 0x40, 0x00]);
+
+        let missing: Vec<u8> = (0..=255u8)
+            .filter(|&op| disasm::documented(op) && !covered.contains(&op))
+            .collect();
+        assert_eq!(missing, []);
+    }
+
+    #[test]
+    fn every_undocumented_opcode() {
+        let mut covered = BTreeSet::new();
+
+        for (name, a, carry, value, [a_after, x_after], stored, after) in UNDOCUMENTED {
+            let opcodes =
+                (0..=255u8).filter(|&op| !disasm::documented(op) && disasm::decode(op).0 == name);
+            for op in opcodes {
+                let (mut cpu, ea) = operation(op, value, carry, |cpu| cpu.reg.a = a);
+
+                let what = format!("{} {:?} (${:02X})", name, lookup(op).mode, op);
+                assert_eq!(
+                    [cpu.reg.a, cpu.reg.x],
+                    [a_after, x_after],
+                    "{} registers",
+                    what
+                );
+                if let Some(ea) = ea {
+                    assert_eq!(cpu.bus.read(ea), stored, "{} memory", what);
+                }
+                assert_eq!(
+                    u8::from(cpu.flags),
+                    u8::from(flags(after)),
+                    "{} flags",
+                    what
+                );
+
+                let write = !matches!(name, "LAX" | "NOP" | "LAS");
+                assert_eq!(cpu.cycles, cycles(op, write), "{} cycles", what);
+                covered.insert(op);
+            }
+        }
+
+        // The unstable stores AND the value with the base's high byte plus
+        // one, $02 + 1 here. Crossing into $03 sends it to that page instead.
+        for (op, [a, x, y], adr, stored) in [
+            (0x93, [0xff, 0xf1, Y], 0x0100, 0x01), // SHA ($42),Y
+            (0x9f, [0xff, 0xf1, Y], 0x0100, 0x01), // SHA $02FE,Y
+            (0x9e, [0x00, X, Y], 0x0100, 0x01),    // SHX $02FE,Y
+            (0x9c, [0x00, X, 0x06], 0x0200, 0x02), // SHY $02FF,X
+            (0x9b, [0xf3, 0x3f, Y], 0x0300, 0x03), // TAS $02FE,Y
+        ] {
+            let (mut cpu, _) = operation(op, 0, false, |cpu| {
+                (cpu.reg.a, cpu.reg.x, cpu.reg.y) = (a, x, y);
+            });
+            assert_eq!(cpu.bus.read(adr), stored, "${:02X} store", op);
+            assert_eq!(cpu.cycles, cycles(op, true), "${:02X} cycles", op);
+            covered.insert(op);
+        }
+        let (cpu, _) = operation(0x9b, 0, false, |cpu| (cpu.reg.a, cpu.reg.x) = (0xf3, 0x3f));
+        assert_eq!(cpu.reg.sp, 0x33);
+
+        // ANE and LXA mix in a chip-dependent constant, JAM stops the CPU
+        let (cpu, _) = operation(0x02, 0, false, |_| {});
+        assert!(cpu.jammed);
+        let jams = (0..=255u8).filter(|&op| disasm::decode(op).0 == "JAM");
+        covered.extend(jams.chain([0x8b, 0xab]));
+
+        let missing: Vec<u8> = (0..=255u8)
+            .filter(|&op| !disasm::documented(op) && !covered.contains(&op))
+            .collect();
+        assert_eq!(missing, []);
+    }
+
+    #[test]
+    fn strict_traps_undocumented() {
+        let (cpu, _) = operation(0xa7, 0x42, false, |_| {}); // LAX $30
+        assert_eq!(cpu.reg.x, 0x42);
+
+        let mut cpu = machine(&[0xa7, 0x30], "");
+        cpu.strict = true;
+        cpu.exec();
+        assert_eq!(
+            (cpu.undocumented, cpu.pc, cpu.reg.x),
+            (Some(0x0600), 0x0600, X)
+        );
+        assert_eq!(cpu.run(|_| {}), ExitReason::Undocumented);
     }
 }
@@ -2,29 +2,121 @@ pub mod instructions;
 pub mod lookup_table;
 pub mod registers;
 
-use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Write};
+use std::fs::File;
+use std::io::{Read, Write};
 
 use crate::bus::Bus;
-use registers::{Flag, Registers};
+use crate::cpu::instructions::Data;
+use crate::disasm;
+use crate::guard::Fault;
+pub use registers::{Flag, Registers};
+
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+
+/// Constant OR'd into A by the unstable ANE/LXA opcodes, varies between chips.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Magic {
+    Fixed(u8),
+    Random,
+}
 
-fn uint_to_string_literal<T: std::fmt::Display + std::fmt::LowerHex + std::fmt::UpperHex>(
-    value: T,
-) -> &'static str {
-    Box::leak(Box::new(format!("{:0002X}", value)))
+impl Magic {
+    pub fn value(&self) -> u8 {
+        match self {
+            Magic::Fixed(m) => *m,
+            Magic::Random => rand::random(),
+        }
+    }
 }
 
-fn append_to_file(file_path: &str, content: &str) -> Result<(), io::Error> {
-    // Open the file in append mode, creating it if it doesn't exist
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(file_path)?;
+impl std::str::FromStr for Magic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("random") {
+            return Ok(Magic::Random);
+        }
 
-    // Append the content followed by a newline to the file
-    writeln!(file, "{}", content)?;
+        let hex = s.trim_start_matches('$').trim_start_matches("0x");
+        u8::from_str_radix(hex, 16)
+            .map(Magic::Fixed)
+            .map_err(|_| format!("invalid magic constant: {}", s))
+    }
+}
 
-    Ok(())
+/// Which chip to behave as. They differ only in decimal mode: the NES's
+/// 2A03 has the D flag but its ALU ignores it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Variant {
+    /// The NMOS 6502, with BCD arithmetic in ADC and SBC when D is set
+    Nmos,
+    Ricoh2A03,
+}
+
+impl std::str::FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "6502" | "nmos" => Ok(Variant::Nmos),
+            "2a03" => Ok(Variant::Ricoh2A03),
+            _ => Err(format!("expected 6502 or 2a03: {}", s)),
+        }
+    }
+}
+
+/// Execution budget for `CPU::run`. A jammed CPU keeps burning cycles, so
+/// `max_cycles` also bounds a run that jams.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    pub max_cycles: Option<u64>,
+    pub max_instructions: Option<u64>,
+}
+
+/// A jump, branch, call or return: the instruction at `from` (opcode
+/// `opcode`) sent the PC to `to`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transfer {
+    pub from: u16,
+    pub to: u16,
+    pub opcode: u8,
+}
+
+/// The last few control-flow transfers, kept for crash reports.
+#[derive(Clone, Copy, Default)]
+pub struct Transfers {
+    ring: [Transfer; 8],
+    count: usize,
+}
+
+impl Transfers {
+    fn record(&mut self, t: Transfer) {
+        self.ring[self.count % self.ring.len()] = t;
+        self.count += 1;
+    }
+
+    /// Oldest first.
+    pub fn recent(&self) -> Vec<Transfer> {
+        let n = self.count.min(self.ring.len());
+        (self.count - n..self.count)
+            .map(|i| self.ring[i % self.ring.len()])
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitReason {
+    Halted,
+    /// A JAM opcode stopped the CPU; only a reset gets it going again
+    Jammed,
+    CycleLimit,
+    InstructionLimit,
+    /// A guard region was touched, see `CPU::fault`
+    Guard,
+    /// A strict CPU reached an undocumented opcode, see `CPU::undocumented`
+    Undocumented,
 }
 
 pub struct CPU {
@@ -33,7 +125,38 @@ pub struct CPU {
     pub flags: Flag,
     pub reg: Registers,
     pub halted: bool,
+    pub jammed: bool,
     pub stack_loc: u16,
+    pub magic: Magic,
+    pub variant: Variant,
+    /// Stop at the undocumented opcodes instead of running them.
+    pub strict: bool,
+    /// Halt at BRK, as easy6502 does, instead of taking the IRQ vector.
+    pub stop_on_brk: bool,
+    pub limits: Limits,
+    /// The master clock: cycles run since power-on, each one a bus tick.
+    pub cycles: u64,
+    pub instructions: u64,
+    pub transfers: Transfers,
+    /// Set when a guard region is touched; `run` stops until it is cleared.
+    pub fault: Option<Fault>,
+    /// The address of the undocumented opcode a strict CPU stopped at, with
+    /// the PC left on it for `crash::report`. `run` stops until it is
+    /// cleared.
+    pub undocumented: Option<u16>,
+    /// Interrupts waiting to be taken before the next instruction, see
+    /// `nmi` and `irq`.
+    pub nmi_pending: bool,
+    pub irq_pending: bool,
+    /// Where each instruction is logged as a `PC|OPCODE` line, if anywhere,
+    /// see `trace::Destination`. Every CPU has its own, so instances can run
+    /// side by side.
+    pub trace: Option<Box<dyn Write + Send>>,
+    /// Why tracing stopped, if writing the trace failed. The run goes on
+    /// without it.
+    pub trace_error: Option<std::io::Error>,
+    /// Bus accesses made so far by the instruction running, each a cycle
+    accesses: u8,
 }
 
 impl CPU {
@@ -49,12 +172,51 @@ impl CPU {
                 sp: 0xfd,
             },
             halted: false,
+            jammed: false,
             stack_loc: 0x100,
+            magic: Magic::Fixed(0xEE),
+            variant: Variant::Ricoh2A03,
+            strict: false,
+            stop_on_brk: false,
+            limits: Limits::default(),
+            cycles: 0,
+            instructions: 0,
+            transfers: Transfers::default(),
+            fault: None,
+            undocumented: None,
+            nmi_pending: false,
+            irq_pending: false,
+            trace: None,
+            trace_error: None,
+            accesses: 0,
         }
     }
 
-    pub fn run<F: FnMut(&mut CPU)>(&mut self, mut callback: F) {
-        while !self.halted {
+    pub fn run<F: FnMut(&mut CPU)>(&mut self, mut callback: F) -> ExitReason {
+        loop {
+            if self.fault.is_some() {
+                return ExitReason::Guard;
+            }
+            if self.undocumented.is_some() {
+                return ExitReason::Undocumented;
+            }
+            if self.halted {
+                return ExitReason::Halted;
+            }
+            if self.jammed {
+                return ExitReason::Jammed;
+            }
+            if self.limits.max_cycles.is_some_and(|m| self.cycles >= m) {
+                return ExitReason::CycleLimit;
+            }
+            if self
+                .limits
+                .max_instructions
+                .is_some_and(|m| self.instructions >= m)
+            {
+                return ExitReason::InstructionLimit;
+            }
+
             self.exec();
             callback(self);
         }
@@ -70,40 +232,223 @@ impl CPU {
     }
 
     pub fn load(&mut self, data: Vec<u8>) {
-        self.bus.memory[0x0600..(0x0600 + data.len())].copy_from_slice(&data[..]);
+        self.bus.memory.load(0x0600, &data);
         self.bus.write(0xFFFC, 0x00);
         self.bus.write(0xFFFD, 0x06);
         self.reset();
     }
 
+    pub fn is_jammed(&self) -> bool {
+        self.jammed
+    }
+
+    /// Whether ADC and SBC work in BCD: D is set on a chip that honours it.
+    pub fn decimal_mode(&self) -> bool {
+        self.flags.decimal && self.variant == Variant::Nmos
+    }
+
+    /// FNV-1a hash of the registers and all of memory. Stable across runs and
+    /// builds, so two instances can compare hashes to spot a desync.
+    pub fn state_hash(&self) -> u64 {
+        let regs = [
+            self.pc as u8,
+            (self.pc >> 8) as u8,
+            self.reg.a,
+            self.reg.x,
+            self.reg.y,
+            self.reg.sp,
+            u8::from(self.flags),
+        ];
+
+        regs.iter()
+            .chain(self.bus.memory.dump().iter())
+            .fold(0xcbf29ce484222325, |h, &b| {
+                (h ^ b as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
     pub fn reset(&mut self) {
+        self.halted = false;
+        self.jammed = false;
+        self.fault = None;
+        self.undocumented = None;
+        self.nmi_pending = false;
+        self.irq_pending = false;
         self.reg.a = 0;
         self.reg.x = 0;
         self.reg.y = 0;
         self.reg.sp = 0xfd;
         self.flags = Flag::from(0b100100_u8);
-        self.pc = self.bus.read(0xFFFC) as u16 | ((self.bus.read(0xFFFD) as u16) << 8);
+        let lo = self.bus.read(RESET_VECTOR);
+        let hi = self.bus.read(RESET_VECTOR + 1);
+        self.pc = instructions::join_bytes(lo, hi);
+    }
+
+    pub(crate) fn read_vector(&mut self, vector: u16) -> u16 {
+        instructions::join_bytes(self.read(vector), self.read(vector.wrapping_add(1)))
+    }
+
+    /// A read the running instruction makes. It takes a cycle of its own,
+    /// ticked before the access so devices are in step when it reaches them.
+    pub(crate) fn read(&mut self, adr: u16) -> u8 {
+        self.access_cycle();
+        self.bus.read(adr)
+    }
+
+    pub(crate) fn write(&mut self, adr: u16, data: u8) {
+        self.access_cycle();
+        self.bus.write(adr, data);
+    }
+
+    pub(crate) fn dummy_read(&mut self, adr: u16) {
+        self.access_cycle();
+        self.bus.dummy_read(adr);
+    }
+
+    pub(crate) fn dummy_write(&mut self, adr: u16, data: u8) {
+        self.access_cycle();
+        self.bus.dummy_write(adr, data);
+    }
+
+    fn access_cycle(&mut self) {
+        self.accesses += 1;
+        self.clock(1);
+    }
+
+    /// Raises a non-maskable interrupt, taken before the next instruction.
+    pub fn nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    /// Raises an interrupt request. It stays pending until the I flag is
+    /// clear before an instruction, then it is taken.
+    pub fn irq(&mut self) {
+        self.irq_pending = true;
+    }
+
+    /// Pushes the PC and the flags, with B clear, sets I and jumps through
+    /// the NMI vector, or the IRQ vector when no NMI is pending.
+    fn interrupt(&mut self) {
+        let vector = if std::mem::take(&mut self.nmi_pending) {
+            NMI_VECTOR
+        } else {
+            self.irq_pending = false;
+            IRQ_VECTOR
+        };
+
+        self.stack_push16(self.pc);
+        self.stack_push((u8::from(self.flags) & !0b10000) as u16);
+        self.flags.interrupt_disable = true;
+        self.pc = self.read_vector(vector);
+
+        self.clock(7 - self.accesses);
+        if self.bus.take_reset() {
+            self.reset();
+        }
+    }
+
+    /// Lets `n` cycles pass, ticking the bus for each one.
+    pub fn clock(&mut self, n: u8) {
+        for _ in 0..n {
+            self.cycles += 1;
+            self.bus.tick();
+        }
+    }
+
+    /// Runs one instruction, or takes an interrupt, and returns the cycles
+    /// it took with any page crossing and branch penalties.
+    pub fn step(&mut self) -> u64 {
+        let start = self.cycles;
+        self.exec();
+        self.cycles - start
     }
 
     pub fn exec(&mut self) {
-        let opcode = self.bus.read(self.pc);
+        self.accesses = 0;
+        if self.jammed {
+            self.clock(1);
+            if self.bus.take_reset() {
+                self.reset();
+            }
+            return;
+        }
+
+        if self.bus.take_nmi() {
+            self.nmi_pending = true;
+        }
+        // taking an interrupt is a step of its own, like an instruction
+        let irq = self.irq_pending || self.bus.irq();
+        if self.nmi_pending || irq && !self.flags.interrupt_disable {
+            self.interrupt();
+            return;
+        }
+
+        let start = self.pc;
+        if let Some(region) = self.bus.region_at(start) {
+            self.fault = Some(Fault {
+                pc: start,
+                access: None,
+                region,
+            });
+            return;
+        }
+        // accesses made between instructions aren't the program's
+        self.bus.take_touched();
+
+        let opcode = self.read(self.pc);
+        if self.strict && !disasm::documented(opcode) {
+            self.undocumented = Some(start);
+            return;
+        }
         let i = lookup_table::lookup(opcode);
 
-        match append_to_file(
-            "./log.txt",
-            &(uint_to_string_literal(self.pc).to_string() + "|" + uint_to_string_literal(opcode)),
-        ) {
-            Ok(_) => (),
-            Err(e) => panic!("Error: {}", e),
-        };
+        if let Some(trace) = self.trace.as_mut() {
+            // one write a line, so a rotating log never splits one
+            let line = format!("{:02X}|{:02X}\n", self.pc, opcode);
+            if let Err(e) = trace.write_all(line.as_bytes()) {
+                self.trace = None;
+                self.trace_error = Some(e);
+            }
+        }
 
         let (unpakt, pagecross) = i.mode.unpack(self);
-        if pagecross {
-            self.bus.tick(1);
+        if let Data::Address(adr) = unpakt {
+            if i.reads_uncarried(pagecross) {
+                // an index only ever carries forward by one page
+                let uncarried = if pagecross {
+                    adr.wrapping_sub(0x100)
+                } else {
+                    adr
+                };
+                self.dummy_read(uncarried);
+            }
         }
 
+        let fall_through = self.pc;
         (i.run)(unpakt, self);
+        if self.pc != fall_through && !self.jammed {
+            self.transfers.record(Transfer {
+                from: start,
+                to: self.pc.wrapping_add(1),
+                opcode,
+            });
+        }
         self.pc = self.pc.wrapping_add(1);
+        self.instructions += 1;
+        if let Some((access, region)) = self.bus.take_touched() {
+            self.fault = Some(Fault {
+                pc: start,
+                access: Some(access),
+                region,
+            });
+        }
+
+        // the cycles left over after the accesses, the chip's idle ones
+        let cycles = i.cycles + (pagecross && i.page_penalty()) as u8;
+        self.clock(cycles.saturating_sub(self.accesses));
+        if self.bus.take_reset() {
+            self.reset();
+        }
     }
 
     pub fn stack_push(&mut self, data: u16) {
@@ -114,14 +459,19 @@ impl CPU {
             self.stack_push(lo);
             return;
         }
-        self.bus
-            .write(self.stack_loc + self.reg.sp as u16, data as u8);
+        self.write(self.stack_loc + self.reg.sp as u16, data as u8);
         self.reg.sp = self.reg.sp.wrapping_sub(1);
     }
 
+    /// Pushes both bytes, high first, whatever the value.
+    pub fn stack_push16(&mut self, data: u16) {
+        self.stack_push(data >> 8);
+        self.stack_push(data & 0xFF);
+    }
+
     pub fn stack_pop(&mut self) -> u8 {
         self.reg.sp = self.reg.sp.wrapping_add(1);
-        self.bus.read(self.reg.sp as u16 | self.stack_loc)
+        self.read(self.reg.sp as u16 | self.stack_loc)
     }
 
     pub fn stack_pop16(&mut self) -> u16 {
@@ -133,19 +483,19 @@ impl CPU {
 
     pub fn u8_operand(&mut self) -> u8 {
         self.pc = self.pc.wrapping_add(1);
-        self.bus.read(self.pc)
+        self.read(self.pc)
     }
 
     pub fn i8_operand(&mut self) -> i8 {
         self.pc = self.pc.wrapping_add(1);
-        self.bus.read(self.pc) as i8
+        self.read(self.pc) as i8
     }
 
     pub fn u16_operand(&mut self) -> u16 {
         self.pc = self.pc.wrapping_add(1);
-        let lo = self.bus.read(self.pc) as u16;
+        let lo = self.read(self.pc) as u16;
         self.pc = self.pc.wrapping_add(1);
-        let hi = self.bus.read(self.pc) as u16;
+        let hi = self.read(self.pc) as u16;
 
         (hi << 8) | lo
     }
@@ -155,14 +505,16 @@ impl CPU {
             return;
         };
 
-        self.bus.tick(1);
+        self.clock(1);
 
-        let addr = self.pc.wrapping_add(w as u16);
-        if addr & 0xFF00 != self.pc & 0xFF00 {
-            self.bus.tick(1);
+        // the offset counts from the next instruction, and so does the page
+        let next = self.pc.wrapping_add(1);
+        let target = next.wrapping_add(w as u16);
+        if target & 0xFF00 != next & 0xFF00 {
+            self.clock(1);
         }
 
-        self.pc = addr;
+        self.pc = target.wrapping_sub(1);
     }
 }
 
@@ -173,7 +525,7 @@ mod tests {
 
     #[test]
     fn initialise_cpu() {
-        let b = Bus { memory: [0; 65535] };
+        let b = Bus::default();
         let mut pu = CPU::new(b);
         let game_code = vec![
             0x20, 0x06, 0x06, 0x20, 0x38, 0x06, 0x20, 0x0d, 0x06, 0x20, 0x2a, 0x06, 0x60, 0xa9,
@@ -221,4 +573,372 @@ mod tests {
 
         assert_eq!(q, u8::from(w));
     }
+
+    #[test]
+    fn magic_constant() {
+        let mut pu = CPU::new(Bus::default());
+        pu.magic = "ff".parse().unwrap();
+        pu.stop_on_brk = true;
+        pu.load(vec![
+            0xa2, 0x0f, // LDX #$0F
+            0x8b, 0x3c, // ANE #$3C     -> A = ($00 | $FF) & $0F & $3C
+            0x00,
+        ]);
+        pu.run(|_| {});
+        assert_eq!(pu.reg.a, 0x0c);
+
+        assert_eq!("$00".parse::<Magic>(), Ok(Magic::Fixed(0x00)));
+        assert_eq!("random".parse::<Magic>(), Ok(Magic::Random));
+        assert!("zz".parse::<Magic>().is_err());
+    }
+
+    #[test]
+    fn decimal_mode() {
+        let program = vec![
+            0xf8, // SED
+            0x18, // CLC
+            0xa9, 0x58, // LDA #$58
+            0x69, 0x46, // ADC #$46
+            0x85, 0x10, // STA $10
+            0xa9, 0x79, // LDA #$79
+            0x69, 0x00, // ADC #$00, with the carry from $58 + $46
+            0x85, 0x11, // STA $11
+            0x38, // SEC
+            0xa9, 0x12, // LDA #$12
+            0xe9, 0x21, // SBC #$21
+            0x85, 0x12, // STA $12
+            0xa9, 0xff, // LDA #$FF
+            0x6b, 0xff, // ARR #$FF
+            0x00,
+        ];
+
+        let mut pu = CPU::new(Bus::default());
+        pu.variant = Variant::Nmos;
+        pu.stop_on_brk = true;
+        pu.load(program.clone());
+        pu.run(|_| {});
+        assert_eq!(pu.bus.read(0x10), 0x04);
+        // N and V come from the sum before the high digit is adjusted
+        assert_eq!(pu.bus.read(0x11), 0x80);
+        assert_eq!(pu.bus.read(0x12), 0x91);
+        assert_eq!((pu.reg.a, pu.flags.carry), (0xd5, true));
+
+        // the 2A03 adds in binary whatever D says
+        let mut pu = CPU::new(Bus::default());
+        pu.stop_on_brk = true;
+        pu.load(program);
+        pu.run(|_| {});
+        assert_eq!((pu.bus.read(0x10), pu.bus.read(0x11)), (0x9e, 0x79));
+        assert_eq!(pu.bus.read(0x12), 0xf1);
+        assert_eq!(pu.reg.a, 0x7f);
+
+        assert_eq!("6502".parse(), Ok(Variant::Nmos));
+        assert!("65c02".parse::<Variant>().is_err());
+    }
+
+    #[test]
+    fn state_hash() {
+        let mut a = CPU::new(Bus::default());
+        let mut b = CPU::new(Bus::default());
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        a.bus.write(0x0200, 1);
+        assert_ne!(a.state_hash(), b.state_hash());
+
+        b.bus.write(0x0200, 1);
+        b.reg.x = 1;
+        assert_ne!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn limits() {
+        let lp = vec![
+            0xe8, // INX
+            0x4c, 0x00, 0x06, // JMP $0600
+        ];
+
+        let mut pu = CPU::new(Bus::default());
+        pu.load(lp.clone());
+        pu.limits.max_instructions = Some(10);
+        assert_eq!(pu.run(|_| {}), ExitReason::InstructionLimit);
+        assert_eq!(pu.instructions, 10);
+        assert_eq!(pu.reg.x, 5);
+
+        let mut pu = CPU::new(Bus::default());
+        pu.load(lp);
+        pu.limits.max_cycles = Some(50);
+        assert_eq!(pu.run(|_| {}), ExitReason::CycleLimit);
+        assert_eq!(pu.cycles, 50);
+
+        // a jam ends the run whatever the limits
+        let mut pu = CPU::new(Bus::default());
+        pu.load(vec![0x02]);
+        pu.limits.max_instructions = Some(100);
+        assert_eq!(pu.run(|_| {}), ExitReason::Jammed);
+        assert!(pu.is_jammed());
+        assert_eq!(pu.pc, 0x0600);
+
+        let mut pu = CPU::new(Bus::default());
+        pu.load(vec![0xe8, 0x02]); // INX, JAM
+        assert_eq!(pu.run(|_| {}), ExitReason::Jammed);
+        assert_eq!((pu.reg.x, pu.instructions), (1, 2));
+    }
+
+    #[test]
+    fn jam() {
+        let mut pu = CPU::new(Bus::default());
+        pu.load(vec![
+            0xa9, 0x01, // LDA #$01
+            0x02, // JAM
+            0xa9, 0x02, // LDA #$02
+        ]);
+
+        for _ in 0..4 {
+            pu.exec();
+        }
+        assert!(pu.is_jammed());
+        assert_eq!(pu.pc, 0x0602);
+        assert_eq!(pu.reg.a, 0x01);
+
+        pu.reset();
+        assert!(!pu.is_jammed());
+        assert_eq!(pu.pc, 0x0600);
+    }
+
+    #[test]
+    fn interrupts() {
+        let mut pu = CPU::new(Bus::default());
+        pu.load(vec![
+            0x78, // $0600 SEI
+            0xe8, // $0601 INX
+            0x58, // $0602 CLI
+            0xea, // $0603 NOP
+            0x00, // $0604 BRK
+            0xc8, // $0605 IRQ: INY
+            0x40, // $0606 RTI
+            0xa9, 0x80, // $0607 NMI: LDA #$80
+            0x40, // $0609 RTI
+        ]);
+        pu.bus
+            .memory
+            .load(NMI_VECTOR, &[0x07, 0x06, 0x00, 0x06, 0x05, 0x06]);
+
+        // I holds the IRQ back, an NMI goes through anyway
+        pu.exec();
+        pu.irq();
+        pu.nmi();
+        pu.exec();
+        assert_eq!((pu.pc, pu.reg.sp), (0x0607, 0xfa));
+        assert_eq!(pu.cycles, 9);
+        assert_eq!(pu.bus.read(0x01fb), 0b00100100); // I set, B clear
+        assert_eq!([pu.bus.read(0x01fc), pu.bus.read(0x01fd)], [0x01, 0x06]);
+
+        pu.exec();
+        pu.exec();
+        assert_eq!((pu.pc, pu.reg.sp), (0x0601, 0xfd));
+        assert!(pu.flags.interrupt_disable && !pu.flags.negative);
+
+        // the IRQ waits until CLI, then returns to the NOP
+        pu.exec();
+        pu.exec();
+        assert_eq!((pu.pc, pu.reg.x, pu.reg.y), (0x0603, 1, 0));
+        pu.exec();
+        assert_eq!(pu.pc, 0x0605);
+        pu.stop_on_brk = true;
+        pu.run(|_| {});
+        assert_eq!((pu.reg.y, pu.pc), (1, 0x0605));
+        assert!(!pu.irq_pending);
+    }
+
+    #[test]
+    fn brk() {
+        let program = vec![
+            0x00, 0xff, // $0600 BRK, and the byte it skips
+            0xe8, // $0602 INX
+            0x00, // $0603 BRK
+            0xc8, // $0604 handler: INY
+            0x40, // $0605 RTI
+        ];
+        let mut pu = CPU::new(Bus::default());
+        pu.load(program.clone());
+        pu.bus.memory.load(IRQ_VECTOR, &[0x04, 0x06]);
+
+        pu.exec();
+        assert_eq!((pu.pc, pu.reg.sp, pu.cycles), (0x0604, 0xfa, 7));
+        assert_eq!(pu.bus.read(0x01fb), 0b00110100); // B and I set
+        assert_eq!([pu.bus.read(0x01fc), pu.bus.read(0x01fd)], [0x02, 0x06]);
+        assert!(pu.flags.interrupt_disable);
+
+        pu.exec();
+        pu.exec();
+        pu.exec();
+        assert_eq!((pu.pc, pu.reg.sp), (0x0603, 0xfd));
+        assert_eq!((pu.reg.x, pu.reg.y), (1, 1));
+        assert!(!pu.halted);
+
+        // the easy6502 convention, BRK ends the program
+        let mut pu = CPU::new(Bus::default());
+        pu.load(program);
+        pu.stop_on_brk = true;
+        assert_eq!(pu.run(|_| {}), ExitReason::Halted);
+        assert_eq!((pu.pc, pu.reg.sp), (0x0601, 0xfd));
+    }
+
+    /// Counts its ticks, and reads back the count.
+    struct Ticks(u8);
+
+    impl crate::bus::Device for Ticks {
+        fn read(&mut self, _adr: u16) -> u8 {
+            self.0
+        }
+
+        fn write(&mut self, _adr: u16, _data: u8) {}
+
+        fn peek(&self, _adr: u16) -> u8 {
+            self.0
+        }
+
+        fn tick(&mut self) -> crate::bus::Lines {
+            self.0 = self.0.wrapping_add(1);
+            crate::bus::Lines::default()
+        }
+    }
+
+    /// Holds IRQ while bit 0 of the last byte written is set, NMI while
+    /// bit 1 is.
+    struct Pins(u8);
+
+    impl crate::bus::Device for Pins {
+        fn read(&mut self, _adr: u16) -> u8 {
+            self.0
+        }
+
+        fn write(&mut self, _adr: u16, data: u8) {
+            self.0 = data;
+        }
+
+        fn peek(&self, _adr: u16) -> u8 {
+            self.0
+        }
+
+        fn tick(&mut self) -> crate::bus::Lines {
+            crate::bus::Lines {
+                irq: self.0 & 1 != 0,
+                nmi: self.0 & 2 != 0,
+                reset: false,
+            }
+        }
+    }
+
+    #[test]
+    fn device_interrupts() {
+        let mut pu = CPU::new(Bus::default());
+        pu.bus.map(0xd0..=0xd0, Box::new(Pins(0)));
+        pu.load(vec![
+            0xa9, 0x02, // $0600 LDA #$02
+            0x8d, 0x00, 0xd0, // $0602 STA $D000, pulling NMI and holding it
+            0xe8, // $0605 INX
+            0xe8, // $0606 INX
+            0xa9, 0x01, // $0607 LDA #$01
+            0x8d, 0x00, 0xd0, // $0609 STA $D000, letting NMI go and holding IRQ
+            0x58, // $060C CLI
+            0xe8, // $060D INX
+            0x00, // $060E BRK
+        ]);
+        pu.bus.memory.load(0x0700, &[0xc8, 0x40]); // NMI: INY, RTI
+        pu.bus.memory.load(
+            0x0710,
+            &[
+                0xe6, 0x20, // IRQ: INC $20
+                0xa9, 0x00, // LDA #$00
+                0x8d, 0x00, 0xd0, // STA $D000, acknowledging it
+                0x40, // RTI
+            ],
+        );
+        pu.bus.memory.load(NMI_VECTOR, &[0x00, 0x07]);
+        pu.bus.memory.load(IRQ_VECTOR, &[0x10, 0x07]);
+        pu.stop_on_brk = true;
+
+        assert_eq!(pu.run(|_| {}), ExitReason::Halted);
+        // one NMI however long the line is held, one IRQ until it is let go
+        assert_eq!((pu.reg.x, pu.reg.y), (3, 1));
+        assert_eq!(pu.bus.read(0x20), 1);
+    }
+
+    #[test]
+    fn master_clock() {
+        let mut pu = CPU::new(Bus::default());
+        pu.bus.map(0xd0..=0xd0, Box::new(Ticks(0)));
+        pu.load(vec![
+            0xa2, 0x01, // $0600 LDX #$01
+            0xbd, 0xff, 0x06, // $0602 LDA $06FF,X, crossing a page
+            0xd0, 0x00, // $0605 BNE $0607, not taken
+            0xf0, 0x00, // $0607 BEQ $0609, taken
+            0xad, 0x00, 0xd0, // $0609 LDA $D000
+        ]);
+
+        let steps: Vec<u64> = (0..5).map(|_| pu.step()).collect();
+        assert_eq!(steps, [2, 5, 2, 3, 4]);
+        assert_eq!(pu.cycles, 16);
+        // ticked up to and including the cycle of the read itself
+        assert_eq!(pu.reg.a, 16);
+    }
+
+    #[test]
+    fn an_access_every_cycle() {
+        // X = Y = 5 and the pointer at $10 is $01FF, so ($10),Y crosses a page
+        let programs: [(&[u8], u64); 13] = [
+            (&[0xb5, 0x10], 4),       // LDA $10,X
+            (&[0xb6, 0x10], 4),       // LDX $10,Y
+            (&[0xa1, 0x10], 6),       // LDA ($10,X)
+            (&[0xb1, 0x10], 6),       // LDA ($10),Y
+            (&[0xbd, 0x00, 0x02], 4), // LDA $0200,X
+            (&[0xbd, 0xfe, 0x01], 5), // LDA $01FE,X
+            (&[0x9d, 0xfe, 0x01], 5), // STA $01FE,X
+            (&[0x9d, 0x00, 0x02], 5), // STA $0200,X
+            (&[0x91, 0x10], 6),       // STA ($10),Y
+            (&[0xe6, 0x10], 5),       // INC $10
+            (&[0x16, 0x10], 6),       // ASL $10,X
+            (&[0xfe, 0xfe, 0x01], 7), // INC $01FE,X
+            (&[0xd3, 0x10], 8),       // DCP ($10),Y
+        ];
+        for (program, cycles) in programs {
+            let mut pu = CPU::new(Bus::default());
+            pu.load(program.to_vec());
+            pu.bus.memory.load(0x10, &[0xff, 0x01]);
+            (pu.reg.x, pu.reg.y) = (5, 5);
+
+            pu.bus.start_log();
+            pu.exec();
+            let log = pu.bus.take_log();
+            assert_eq!(pu.cycles, cycles, "{:02X?}", program);
+            assert_eq!(log.len() as u64, cycles, "{:02X?}: {:?}", program, log);
+        }
+    }
+
+    #[test]
+    fn eztest() {
+        let mut c = CPU::new(Bus::default());
+        // let mut rng = rand::thread_rng();
+
+        let ezcode = vec![
+            0xa9, 0x10, // LDA #$10     -> A = #$10
+            0x85, 0x20, // STA $20      -> $20 = #$10
+            0xa9, 0x01, // LDA #$1      -> A = #$1
+            0x65, 0x20, // ADC $20      -> A = #$11
+            0x85, 0x21, // STA $21      -> $21=#$11
+            0xe6, 0x21, // INC $21      -> $21=#$12
+            0xa4, 0x21, // LDY $21      -> Y=#$12
+            0xc8, // INY          -> Y=#$13
+            0x00, // BRK
+        ];
+
+        c.load(ezcode);
+        c.stop_on_brk = true;
+        c.run(move |_cpu| {});
+        assert_eq!(c.bus.read(0x20), 0x10);
+        assert_eq!(c.bus.read(0x21), 0x12);
+        assert_eq!(c.reg.a, 0x11);
+        assert_eq!(c.reg.y, 0x13);
+    }
 }
@@ -0,0 +1,201 @@
+use crate::cpu::CPU;
+use crate::disasm;
+
+/// Instructions shown either side of the one that crashed.
+const BEFORE: usize = 5;
+const AFTER: usize = 3;
+
+/// Bytes after the PC checked for other undocumented opcodes.
+const LOOKAHEAD: u16 = 16;
+
+/// Addresses of up to `BEFORE` instructions leading up to `pc`. Code can't
+/// be decoded backwards, so every start up to three bytes per instruction
+/// back is tried and the one that lands on `pc` through the fewest
+/// undocumented opcodes wins, the longest on a tie.
+fn lead_in(cpu: &CPU, pc: u16) -> Vec<u16> {
+    let mut best: Option<(usize, Vec<u16>)> = None;
+
+    for back in 1..=3 * BEFORE as u16 {
+        let mut adr = pc.wrapping_sub(back);
+        let (mut addrs, mut bad) = (Vec::new(), 0);
+        while adr.wrapping_sub(pc.wrapping_sub(back)) < back {
+            let (_, n) = disasm::line(cpu, adr);
+            bad += (!disasm::documented(cpu.bus.memory.read(adr))) as usize;
+            addrs.push(adr);
+            adr = adr.wrapping_add(n);
+        }
+
+        let better = best
+            .as_ref()
+            .is_none_or(|(b, a)| bad < *b || bad == *b && addrs.len() >= a.len());
+        if adr == pc && better {
+            best = Some((bad, addrs));
+        }
+    }
+
+    let addrs = best.map(|(_, a)| a).unwrap_or_default();
+    addrs[addrs.len().saturating_sub(BEFORE)..].to_vec()
+}
+
+/// Reasons to think the PC is running through data rather than code.
+fn data_hints(cpu: &CPU, pc: u16) -> Vec<String> {
+    let mut hints = Vec::new();
+    let mem = &cpu.bus.memory;
+
+    match pc {
+        0x0000..=0x00FF => hints.push("the PC is in the zero page".to_string()),
+        0x0100..=0x01FF => hints.push("the PC is in the stack page".to_string()),
+        0x0200..=0x05FF => hints.push("the PC is in display memory ($0200-$05FF)".to_string()),
+        _ => (),
+    }
+
+    let ahead: Vec<u8> = (0..LOOKAHEAD)
+        .map(|i| mem.read(pc.wrapping_add(i)))
+        .collect();
+    let unknown = ahead.iter().filter(|&&b| !disasm::documented(b)).count();
+    if ahead.iter().all(|&b| b == ahead[0]) {
+        hints.push(format!(
+            "the {} bytes from here are all ${:02X}, as if never written",
+            LOOKAHEAD, ahead[0]
+        ));
+    } else if unknown > 2 {
+        hints.push(format!(
+            "{} of the {} bytes from here are undocumented opcodes too",
+            unknown, LOOKAHEAD
+        ));
+    }
+
+    // only worth blaming the way in once the PC does look lost
+    let last = cpu.transfers.recent().last().map(|t| t.opcode);
+    let kind = match last {
+        Some(0x60) => Some("an RTS, so the stack may be unbalanced"),
+        Some(0x6C) => Some("an indirect JMP, so the pointer may be wrong"),
+        _ => None,
+    };
+    if let Some(kind) = kind.filter(|_| !hints.is_empty()) {
+        hints.push(format!("the last transfer was {}", kind));
+    }
+
+    hints
+}
+
+/// A listing of the code around `pc`, marked with " > ", then the last
+/// jumps that were taken. Starts with a blank line.
+pub fn context(cpu: &CPU, pc: u16) -> Vec<String> {
+    let mut out = vec![String::new()];
+    for adr in lead_in(cpu, pc) {
+        out.push(format!("   {}", disasm::line(cpu, adr).0));
+    }
+    let (text, n) = disasm::line(cpu, pc);
+    out.push(format!(" > {}", text));
+    let mut adr = pc.wrapping_add(n);
+    for _ in 0..AFTER {
+        let (text, n) = disasm::line(cpu, adr);
+        out.push(format!("   {}", text));
+        adr = adr.wrapping_add(n);
+    }
+
+    let transfers = cpu.transfers.recent();
+    if !transfers.is_empty() {
+        out.push(String::new());
+        out.push("Last jumps, oldest first:".to_string());
+        for t in transfers {
+            let (name, _) = disasm::decode(t.opcode);
+            out.push(format!("   ${:04X}  {} -> ${:04X}", t.from, name, t.to));
+        }
+    }
+
+    out
+}
+
+/// A report on an undocumented opcode at the PC of a strict CPU: the code
+/// around it, whether the PC seems to have wandered into data, how it got
+/// there and what to try next.
+pub fn report(cpu: &CPU) -> String {
+    let pc = cpu.pc;
+    let opcode = cpu.bus.memory.read(pc);
+    let mut out = vec![format!(
+        "Undocumented instruction ${:02X} ({}) at ${:04X}",
+        opcode,
+        disasm::decode(opcode).0,
+        pc
+    )];
+
+    out.extend(context(cpu, pc));
+
+    let hints = data_hints(cpu, pc);
+    out.push(String::new());
+    if hints.is_empty() {
+        out.push("The code around the PC decodes cleanly, so this is probably a".to_string());
+        out.push("genuine undocumented opcode.".to_string());
+        out.push(String::new());
+        out.push("Suggestion: the program relies on undocumented opcodes; run it".to_string());
+        out.push("without --strict to emulate them.".to_string());
+    } else {
+        out.push("The PC looks like it has wandered into data:".to_string());
+        for h in hints {
+            out.push(format!("   - {}", h));
+        }
+        out.push(String::new());
+        out.push("Suggestion: look at where the last jump above came from; a bad".to_string());
+        out.push("address or an unbalanced stack is the usual cause.".to_string());
+    }
+
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::ExitReason;
+    use crate::crash::*;
+
+    #[test]
+    fn wandered_into_data() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa9, 0x01, // LDA #$01
+            0x4c, 0x00, 0x03, // JMP $0300
+        ]);
+        cpu.bus.memory.load(0x0300, &[0xff; 16]);
+        cpu.strict = true;
+        assert_eq!(cpu.run(|_| {}), ExitReason::Undocumented);
+        assert_eq!((cpu.undocumented, cpu.pc), (Some(0x0300), 0x0300));
+
+        let report = report(&cpu);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Undocumented instruction $FF (ISC) at $0300");
+        assert!(lines.contains(&" > $0300  FF FF FF  ISC $FFFF,X"));
+        assert!(lines.contains(&"   $0602  JMP -> $0300"));
+        assert!(lines.contains(&"   - the PC is in display memory ($0200-$05FF)"));
+        assert!(lines.contains(&"   - the 16 bytes from here are all $FF, as if never written"));
+    }
+
+    #[test]
+    fn undocumented_opcode_in_code() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa9, 0x01, // LDA #$01
+            0xa2, 0x02, // LDX #$02
+            0xa7, 0x10, // LAX $10, undocumented
+            0xe8, // INX
+            0x4c, 0x00, 0x06, // JMP $0600
+        ]);
+        cpu.strict = true;
+        cpu.exec();
+        cpu.exec();
+
+        let report = report(&cpu);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(
+            lines[5..9],
+            [
+                "   $0600  A9 01     LDA #$01",
+                "   $0602  A2 02     LDX #$02",
+                " > $0604  A7 10     LAX $10",
+                "   $0606  E8        INX",
+            ]
+        );
+        assert!(report.contains("genuine undocumented opcode"));
+    }
+}
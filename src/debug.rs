@@ -0,0 +1,105 @@
+use crate::cpu::CPU;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StackEntry {
+    /// A byte that doesn't belong to a return address.
+    Data(u8),
+    /// Low and high byte of an address pushed by JSR, which sits at `jsr`.
+    Return { lo: u8, hi: u8, jsr: u16 },
+}
+
+/// Decodes the used part of the stack page, from the top of stack upwards.
+/// An address is treated as a JSR return address when the three bytes
+/// before the byte it points at hold a JSR, since JSR pushes PC+2.
+pub fn stack_view(cpu: &CPU) -> Vec<(u16, StackEntry)> {
+    let mem = &cpu.bus.memory;
+    let mut entries = Vec::new();
+    let mut adr = cpu.stack_loc + cpu.reg.sp as u16 + 1;
+    let top = cpu.stack_loc + 0xFF;
+
+    while adr <= top {
+        let lo = mem.read(adr);
+        if adr < top {
+            let hi = mem.read(adr + 1);
+            let jsr = ((hi as u16) << 8 | lo as u16).wrapping_sub(2);
+            if mem.read(jsr) == 0x20 {
+                entries.push((adr, StackEntry::Return { lo, hi, jsr }));
+                adr += 2;
+                continue;
+            }
+        }
+
+        entries.push((adr, StackEntry::Data(lo)));
+        adr += 1;
+    }
+
+    entries
+}
+
+pub fn print_stack(cpu: &CPU) {
+    println!("Stack (SP={:02X})", cpu.reg.sp);
+    for (adr, entry) in stack_view(cpu) {
+        match entry {
+            StackEntry::Data(b) => println!("${:04X}  {:02X}     data", adr, b),
+            StackEntry::Return { lo, hi, jsr } => println!(
+                "${:04X}  {:02X} {:02X}  return to ${:04X} (JSR at ${:04X})",
+                adr,
+                lo,
+                hi,
+                jsr.wrapping_add(3),
+                jsr
+            ),
+        }
+    }
+}
+
+/// The interrupts waiting to be taken and the devices holding IRQ, for
+/// finding out why the CPU keeps ending up in a handler.
+pub fn print_interrupts(cpu: &CPU) {
+    let sources = cpu.bus.irq_sources();
+    if !sources.is_empty() {
+        let names: Vec<String> = sources.iter().map(|i| format!("device {}", i)).collect();
+        println!("IRQ held by {}", names.join(", "));
+    }
+    if cpu.irq_pending {
+        println!("IRQ raised, waiting for I to clear");
+    }
+    if cpu.nmi_pending {
+        println!("NMI pending");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::debug::*;
+
+    #[test]
+    fn decodes_return_addresses() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0x20, 0x00, 0x07, // $0600: JSR $0700
+        ]);
+
+        // JSR pushed $0602, then the subroutine pushed $42
+        cpu.bus.memory.load(0x01FB, &[0x42, 0x02, 0x06]);
+        cpu.reg.sp = 0xFA;
+
+        assert_eq!(
+            stack_view(&cpu),
+            vec![
+                (0x01FB, StackEntry::Data(0x42)),
+                (
+                    0x01FC,
+                    StackEntry::Return {
+                        lo: 0x02,
+                        hi: 0x06,
+                        jsr: 0x0600
+                    }
+                ),
+                (0x01FE, StackEntry::Data(0x00)),
+                (0x01FF, StackEntry::Data(0x00)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::bus::{Device, Lines};
+use crate::clock::Timing;
+use crate::cpu::CPU;
+
+/// A program built into the binary, so there is something to run without
+/// hunting for ROMs. It has no file behind it to reload or keep save
+/// states and cheats next to.
+pub struct Demo {
+    pub name: &'static str,
+    /// What it does
+    pub about: &'static str,
+    /// Loaded at $0600
+    pub image: &'static [u8],
+    /// Whether it needs the frame counter at $D000 pulling NMI
+    pub frames: bool,
+}
+
+pub const DEMOS: [Demo; 3] = [
+    Demo {
+        name: "snake",
+        about: "the easy6502 snake game, steer with WASD",
+        image: include_bytes!("../roms/snake.nes"),
+        frames: false,
+    },
+    Demo {
+        name: "hello",
+        about: "writes HELLO on the display",
+        image: include_bytes!("../roms/hello.bin"),
+        frames: false,
+    },
+    Demo {
+        name: "interrupt",
+        about: "moves a dot once a frame from the NMI handler",
+        image: include_bytes!("../roms/interrupt.bin"),
+        frames: true,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Demo> {
+    DEMOS.iter().find(|d| d.name == name)
+}
+
+pub fn print_list() {
+    println!("Demos:");
+    for d in &DEMOS {
+        println!("  {:10} {}", d.name, d.about);
+    }
+}
+
+impl Demo {
+    /// Maps what the demo needs and loads it.
+    pub fn load(&self, cpu: &mut CPU) {
+        if self.frames {
+            let frame = Timing::Ntsc.cycles_per_frame();
+            cpu.bus.map(0xd0..=0xd0, Box::new(Frames::new(frame)));
+        }
+        cpu.load(self.image.to_vec());
+    }
+}
+
+/// Pulls NMI at the start of every frame, as the NES's PPU does at
+/// vertical blank. Reads give the number of frames so far.
+pub struct Frames {
+    cycles: u64,
+    left: u64,
+    count: u8,
+}
+
+impl Frames {
+    pub fn new(cycles: u64) -> Self {
+        Frames {
+            cycles,
+            left: cycles,
+            count: 0,
+        }
+    }
+}
+
+impl Device for Frames {
+    fn read(&mut self, _adr: u16) -> u8 {
+        self.count
+    }
+
+    fn write(&mut self, _adr: u16, _data: u8) {}
+
+    fn peek(&self, _adr: u16) -> u8 {
+        self.count
+    }
+
+    fn tick(&mut self) -> Lines {
+        self.left -= 1;
+        let nmi = self.left == 0;
+        if nmi {
+            self.left = self.cycles;
+            self.count = self.count.wrapping_add(1);
+        }
+        Lines {
+            nmi,
+            ..Lines::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::demo::*;
+
+    #[test]
+    fn hello_draws() {
+        assert!(find("snake").is_some());
+        assert!(find("tetris").is_none());
+
+        let mut cpu = CPU::new(Bus::default());
+        find("hello").unwrap().load(&mut cpu);
+        for _ in 0..500 {
+            cpu.exec();
+        }
+
+        // top row of the H, and the gap after it
+        assert_eq!(cpu.bus.read(0x226), 1);
+        assert_eq!(cpu.bus.read(0x227), 0);
+        assert_eq!(cpu.bus.read(0x228), 1);
+        assert_eq!(cpu.bus.read(0x229), 0);
+        assert_eq!(cpu.pc, 0x060F);
+    }
+
+    #[test]
+    fn interrupt_moves_a_dot() {
+        let mut cpu = CPU::new(Bus::default());
+        find("interrupt").unwrap().load(&mut cpu);
+        let frame = Timing::Ntsc.cycles_per_frame();
+        while cpu.cycles < 3 * frame + 100 {
+            cpu.exec();
+        }
+
+        // three frames, three steps, and the main loop ran in between
+        assert_eq!(cpu.bus.read(0x00), 3);
+        assert_eq!(cpu.bus.read(0x202), 0);
+        assert_eq!(cpu.bus.read(0x203), 3);
+        assert!(cpu.reg.x > 0);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::cpu::instructions::Addrmode;
+use crate::cpu::lookup_table;
+use crate::cpu::CPU;
+
+/// Mnemonic of every opcode, the undocumented ones marked with a `*` as in
+/// nestest logs.
+#[rustfmt::skip]
+const MNEMONICS: [&str; 256] = [
+    "BRK", "ORA", "*JAM", "*SLO", "*NOP", "ORA", "ASL", "*SLO", "PHP", "ORA", "ASL", "*ANC", "*NOP", "ORA", "ASL", "*SLO",
+    "BPL", "ORA", "*JAM", "*SLO", "*NOP", "ORA", "ASL", "*SLO", "CLC", "ORA", "*NOP", "*SLO", "*NOP", "ORA", "ASL", "*SLO",
+    "JSR", "AND", "*JAM", "*RLA", "BIT", "AND", "ROL", "*RLA", "PLP", "AND", "ROL", "*ANC", "BIT", "AND", "ROL", "*RLA",
+    "BMI", "AND", "*JAM", "*RLA", "*NOP", "AND", "ROL", "*RLA", "SEC", "AND", "*NOP", "*RLA", "*NOP", "AND", "ROL", "*RLA",
+    "RTI", "EOR", "*JAM", "*SRE", "*NOP", "EOR", "LSR", "*SRE", "PHA", "EOR", "LSR", "*ALR", "JMP", "EOR", "LSR", "*SRE",
+    "BVC", "EOR", "*JAM", "*SRE", "*NOP", "EOR", "LSR", "*SRE", "CLI", "EOR", "*NOP", "*SRE", "*NOP", "EOR", "LSR", "*SRE",
+    "RTS", "ADC", "*JAM", "*RRA", "*NOP", "ADC", "ROR", "*RRA", "PLA", "ADC", "ROR", "*ARR", "JMP", "ADC", "ROR", "*RRA",
+    "BVS", "ADC", "*JAM", "*RRA", "*NOP", "ADC", "ROR", "*RRA", "SEI", "ADC", "*NOP", "*RRA", "*NOP", "ADC", "ROR", "*RRA",
+    "*NOP", "STA", "*NOP", "*SAX", "STY", "STA", "STX", "*SAX", "DEY", "*NOP", "TXA", "*ANE", "STY", "STA", "STX", "*SAX",
+    "BCC", "STA", "*JAM", "*SHA", "STY", "STA", "STX", "*SAX", "TYA", "STA", "TXS", "*TAS", "*SHY", "STA", "*SHX", "*SHA",
+    "LDY", "LDA", "LDX", "*LAX", "LDY", "LDA", "LDX", "*LAX", "TAY", "LDA", "TAX", "*LXA", "LDY", "LDA", "LDX", "*LAX",
+    "BCS", "LDA", "*JAM", "*LAX", "LDY", "LDA", "LDX", "*LAX", "CLV", "LDA", "TSX", "*LAS", "LDY", "LDA", "LDX", "*LAX",
+    "CPY", "CMP", "*NOP", "*DCP", "CPY", "CMP", "DEC", "*DCP", "INY", "CMP", "DEX", "*SBX", "CPY", "CMP", "DEC", "*DCP",
+    "BNE", "CMP", "*JAM", "*DCP", "*NOP", "CMP", "DEC", "*DCP", "CLD", "CMP", "*NOP", "*DCP", "*NOP", "CMP", "DEC", "*DCP",
+    "CPX", "SBC", "*NOP", "*ISC", "CPX", "SBC", "INC", "*ISC", "INX", "SBC", "NOP", "*SBC", "CPX", "SBC", "INC", "*ISC",
+    "BEQ", "SBC", "*JAM", "*ISC", "*NOP", "SBC", "INC", "*ISC", "SED", "SBC", "*NOP", "*ISC", "*NOP", "SBC", "INC", "*ISC",
+];
+
+/// Whether `opcode` is one of the 151 that MOS documented.
+pub fn documented(opcode: u8) -> bool {
+    !MNEMONICS[opcode as usize].starts_with('*')
+}
+
+/// Mnemonic and addressing mode of `opcode`.
+pub fn decode(opcode: u8) -> (&'static str, Addrmode) {
+    let name = MNEMONICS[opcode as usize].trim_start_matches('*');
+    (name, lookup_table::lookup(opcode).mode)
+}
+
+/// Bytes taken by an instruction in `mode`, opcode included.
+pub fn len(mode: Addrmode) -> u16 {
+    use Addrmode::*;
+    match mode {
+        A | Impl => 1,
+        Imm | Rel | Zpg | ZpgX | ZpgY | XInd | IndY => 2,
+        Abs | AbsX | AbsY | Ind => 3,
+    }
+}
+
+/// The operand as it is written in assembly, `pc` being the address of the
+/// opcode (branch targets are shown as absolute addresses).
+pub fn operand(mode: Addrmode, pc: u16, lo: u8, hi: u8) -> String {
+    use Addrmode::*;
+    let abs = (hi as u16) << 8 | lo as u16;
+    match mode {
+        A => "A".to_string(),
+        Impl => String::new(),
+        Imm => format!("#${:02X}", lo),
+        Rel => format!("${:04X}", pc.wrapping_add(2).wrapping_add(lo as i8 as u16)),
+        Zpg => format!("${:02X}", lo),
+        ZpgX => format!("${:02X},X", lo),
+        ZpgY => format!("${:02X},Y", lo),
+        XInd => format!("(${:02X},X)", lo),
+        IndY => format!("(${:02X}),Y", lo),
+        Abs => format!("${:04X}", abs),
+        AbsX => format!("${:04X},X", abs),
+        AbsY => format!("${:04X},Y", abs),
+        Ind => format!("(${:04X})", abs),
+    }
+}
+
+/// One line of disassembly for the instruction at `adr`, and its length.
+/// Reads memory directly, so devices never see the accesses.
+pub fn line(cpu: &CPU, adr: u16) -> (String, u16) {
+    let mem = &cpu.bus.memory;
+    let opcode = mem.read(adr);
+    let (lo, hi) = (mem.read(adr.wrapping_add(1)), mem.read(adr.wrapping_add(2)));
+
+    let (name, mode) = decode(opcode);
+    let n = len(mode);
+    let bytes = [opcode, lo, hi][..n as usize]
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let text = format!("{} {}", name, operand(mode, adr, lo, hi));
+    (format!("${:04X}  {:8}  {}", adr, bytes, text.trim_end()), n)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::disasm::*;
+
+    #[test]
+    fn lines() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa9, 0x01, // LDA #$01
+            0x9d, 0x00, 0x02, // STA $0200,X
+            0xd0, 0xf9, // BNE $0600
+            0xe8, // INX
+            0xa7, 0x10, // LAX $10, undocumented
+        ]);
+
+        let mut adr = 0x0600;
+        let mut out = Vec::new();
+        while adr < 0x060a {
+            let (text, n) = line(&cpu, adr);
+            out.push(text);
+            adr += n;
+        }
+
+        assert_eq!(
+            out,
+            [
+                "$0600  A9 01     LDA #$01",
+                "$0602  9D 00 02  STA $0200,X",
+                "$0605  D0 F9     BNE $0600",
+                "$0607  E8        INX",
+                "$0608  A7 10     LAX $10",
+            ]
+        );
+        assert!(documented(0xe8) && !documented(0xa7) && !documented(0xeb));
+    }
+}
@@ -0,0 +1,449 @@
+use crate::bus::{Access, BusCycle};
+use crate::cpu::instructions::Addrmode;
+use crate::cpu::lookup_table;
+use crate::cpu::registers::Registers;
+use crate::cpu::CPU;
+use crate::disasm;
+
+/// The CPU just before an instruction runs. The operand is resolved here,
+/// while the pointers it goes through still hold what the instruction saw,
+/// and the bus starts logging so the instruction's accesses can be shown.
+pub struct Before {
+    pc: u16,
+    opcode: u8,
+    reg: Registers,
+    flags: u8,
+    cycles: u64,
+    text: String,
+    resolved: String,
+    /// The address the instruction works on and the byte that was there.
+    target: Option<(u16, u8)>,
+}
+
+pub fn capture(cpu: &mut CPU) -> Before {
+    cpu.bus.start_log();
+    let mem = &cpu.bus.memory;
+    let opcode = mem.read(cpu.pc);
+    let (lo, hi) = (
+        mem.read(cpu.pc.wrapping_add(1)),
+        mem.read(cpu.pc.wrapping_add(2)),
+    );
+
+    let (_, mode) = disasm::decode(opcode);
+    let penalty = lookup_table::lookup(opcode).page_penalty();
+    let (resolved, target) = resolve(cpu, mode, penalty, lo, hi);
+
+    Before {
+        pc: cpu.pc,
+        opcode,
+        reg: cpu.reg,
+        flags: u8::from(cpu.flags),
+        cycles: cpu.cycles,
+        text: disasm::line(cpu, cpu.pc).0,
+        resolved,
+        target: target.map(|adr| (adr, mem.read(adr))),
+    }
+}
+
+fn mode_name(mode: Addrmode) -> &'static str {
+    use Addrmode::*;
+    match mode {
+        A => "accumulator",
+        Abs => "absolute",
+        AbsX => "absolute,X",
+        AbsY => "absolute,Y",
+        Imm => "immediate",
+        Impl => "implied",
+        Ind => "indirect",
+        XInd => "(zero page,X)",
+        IndY => "(zero page),Y",
+        Rel => "relative",
+        Zpg => "zero page",
+        ZpgX => "zero page,X",
+        ZpgY => "zero page,Y",
+    }
+}
+
+fn indexed(base: u16, reg: char, by: u8, penalty: bool) -> (String, Option<u16>) {
+    let adr = base.wrapping_add(by as u16);
+    let mut s = format!("${:04X} + {} (${:02X}) = ${:04X}", base, reg, by, adr);
+    if adr & 0xFF00 != base & 0xFF00 {
+        s += if penalty {
+            ", crossing a page, which costs a cycle"
+        } else {
+            ", crossing a page, a cycle writes spend anyway"
+        };
+    }
+    (s, Some(adr))
+}
+
+/// How `mode` finds the operand, and the address it lands on if any.
+/// `penalty` says whether crossing a page costs the instruction a cycle.
+fn resolve(cpu: &CPU, mode: Addrmode, penalty: bool, lo: u8, hi: u8) -> (String, Option<u16>) {
+    use Addrmode::*;
+    let mem = &cpu.bus.memory;
+    let abs = (hi as u16) << 8 | lo as u16;
+    let pointer =
+        |zp: u8| (mem.read(zp.wrapping_add(1) as u16) as u16) << 8 | mem.read(zp as u16) as u16;
+
+    match mode {
+        A => (
+            format!("the operand is A itself (${:02X})", cpu.reg.a),
+            None,
+        ),
+        Impl => (
+            "no operand, the opcode says what to work on".to_string(),
+            None,
+        ),
+        Imm => (
+            format!("the operand is the byte ${:02X} after the opcode", lo),
+            None,
+        ),
+        Rel => {
+            let next = cpu.pc.wrapping_add(2);
+            let dest = next.wrapping_add(lo as i8 as u16);
+            (
+                format!(
+                    "offset ${:02X} ({}) from the next instruction at ${:04X} gives ${:04X}",
+                    lo, lo as i8, next, dest
+                ),
+                None,
+            )
+        }
+        Zpg => (
+            format!("address ${:04X} in the zero page", lo),
+            Some(lo as u16),
+        ),
+        ZpgX | ZpgY => {
+            let (reg, by) = if mode == ZpgX {
+                ('X', cpu.reg.x)
+            } else {
+                ('Y', cpu.reg.y)
+            };
+            let adr = lo.wrapping_add(by) as u16;
+            (
+                format!(
+                    "${:02X} + {} (${:02X}) = ${:04X}, wrapping within the zero page",
+                    lo, reg, by, adr
+                ),
+                Some(adr),
+            )
+        }
+        Abs => (format!("address ${:04X}", abs), Some(abs)),
+        AbsX => indexed(abs, 'X', cpu.reg.x, penalty),
+        AbsY => indexed(abs, 'Y', cpu.reg.y, penalty),
+        Ind => {
+            // the high byte never carries into the next page
+            let hi_adr = abs & 0xFF00 | abs.wrapping_add(1) & 0x00FF;
+            let adr = (mem.read(hi_adr) as u16) << 8 | mem.read(abs) as u16;
+            let mut s = format!("the pointer at ${:04X} holds ${:04X}", abs, adr);
+            if lo == 0xFF {
+                s += &format!(", its high byte read from ${:04X}", hi_adr);
+            }
+            (s, Some(adr))
+        }
+        XInd => {
+            let zp = lo.wrapping_add(cpu.reg.x);
+            let adr = pointer(zp);
+            (
+                format!(
+                    "${:02X} + X (${:02X}) = ${:02X}, and the pointer there holds ${:04X}",
+                    lo, cpu.reg.x, zp, adr
+                ),
+                Some(adr),
+            )
+        }
+        IndY => {
+            let (s, adr) = indexed(pointer(lo), 'Y', cpu.reg.y, penalty);
+            (format!("the pointer at ${:02X} holds {}", lo, s), adr)
+        }
+    }
+}
+
+fn summary(name: &str) -> &'static str {
+    match name {
+        "ADC" => "adds the operand and the carry to A",
+        "AND" => "ANDs the operand into A, keeping bits set in both",
+        "ASL" => "shifts left one bit, bit 7 going into the carry",
+        "BCC" => "branches if the carry is clear",
+        "BCS" => "branches if the carry is set",
+        "BEQ" => "branches if the zero flag is set",
+        "BIT" => "tests A against the operand without changing either",
+        "BMI" => "branches if the negative flag is set",
+        "BNE" => "branches if the zero flag is clear",
+        "BPL" => "branches if the negative flag is clear",
+        "BRK" => "interrupts through the IRQ vector, or stops the program under --brk stop",
+        "BVC" => "branches if the overflow flag is clear",
+        "BVS" => "branches if the overflow flag is set",
+        "CLC" => "clears the carry flag",
+        "CLD" => "clears the decimal flag",
+        "CLI" => "clears the interrupt disable flag",
+        "CLV" => "clears the overflow flag",
+        "CMP" => "compares A with the operand by subtracting, keeping only the flags",
+        "CPX" => "compares X with the operand by subtracting, keeping only the flags",
+        "CPY" => "compares Y with the operand by subtracting, keeping only the flags",
+        "DEC" => "subtracts one from a byte in memory",
+        "DEX" => "subtracts one from X",
+        "DEY" => "subtracts one from Y",
+        "EOR" => "exclusive-ORs the operand into A, flipping the bits set in it",
+        "INC" => "adds one to a byte in memory",
+        "INX" => "adds one to X",
+        "INY" => "adds one to Y",
+        "JAM" => "locks up the CPU until it is reset",
+        "JMP" => "continues at another address",
+        "JSR" => "pushes the return address and calls a subroutine",
+        "LDA" => "loads the operand into A",
+        "LDX" => "loads the operand into X",
+        "LDY" => "loads the operand into Y",
+        "LSR" => "shifts right one bit, bit 0 going into the carry",
+        "NOP" => "does nothing",
+        "ORA" => "ORs the operand into A, setting the bits set in it",
+        "PHA" => "pushes A onto the stack",
+        "PHP" => "pushes the flags onto the stack",
+        "PLA" => "pulls A from the stack",
+        "PLP" => "pulls the flags from the stack",
+        "ROL" => "rotates left one bit through the carry",
+        "ROR" => "rotates right one bit through the carry",
+        "RTI" => "returns from an interrupt",
+        "RTS" => "pulls the return address and returns from a subroutine",
+        "SBC" => "subtracts the operand and the borrow (inverted carry) from A",
+        "SEC" => "sets the carry flag",
+        "SED" => "sets the decimal flag",
+        "SEI" => "sets the interrupt disable flag",
+        "STA" => "stores A in memory",
+        "STX" => "stores X in memory",
+        "STY" => "stores Y in memory",
+        "TAX" => "copies A into X",
+        "TAY" => "copies A into Y",
+        "TSX" => "copies the stack pointer into X",
+        "TXA" => "copies X into A",
+        "TXS" => "copies X into the stack pointer",
+        "TYA" => "copies Y into A",
+        // undocumented
+        "ALR" => "ANDs the operand into A, then shifts A right",
+        "ANC" => "ANDs the operand into A, copying bit 7 into the carry",
+        "ARR" => "ANDs the operand into A, then rotates A right through the carry",
+        "DCP" => "subtracts one from a byte in memory, then compares A with it",
+        "ISC" => "adds one to a byte in memory, then subtracts it from A",
+        "LAS" => "ANDs the operand with the stack pointer into A, X and the stack pointer",
+        "LAX" => "loads the operand into A and X",
+        "RLA" => "rotates a byte in memory left, then ANDs it into A",
+        "RRA" => "rotates a byte in memory right, then adds it to A",
+        "SAX" => "stores A AND X in memory",
+        "SBX" => "subtracts the operand from A AND X, leaving the result in X",
+        "SLO" => "shifts a byte in memory left, then ORs it into A",
+        "SRE" => "shifts a byte in memory right, then exclusive-ORs it into A",
+        "SHA" | "SHX" | "SHY" | "TAS" => {
+            "an unstable store, ANDed with the high byte of the address plus one"
+        }
+        "ANE" | "LXA" => {
+            "an unstable illegal opcode mixing A, X, the operand and a chip-specific constant"
+        }
+        _ => "is not something this CPU knows",
+    }
+}
+
+fn flag_reason(name: &str, flag: char, set: bool) -> &'static str {
+    let pick = |yes, no| if set { yes } else { no };
+    match (flag, name) {
+        (_, "PLP" | "RTI") => "pulled from the stack",
+        ('Z', "BIT") => pick("A AND the operand is zero", "A AND the operand is not zero"),
+        ('Z', "CMP" | "CPX" | "CPY" | "DCP") => pick("the two are equal", "the two differ"),
+        ('Z', _) => pick("the result is zero", "the result is not zero"),
+        ('N', "BIT") => "copied from bit 7 of the operand",
+        ('N', _) => pick("bit 7 of the result is 1", "bit 7 of the result is 0"),
+        ('V', "BIT") => "copied from bit 6 of the operand",
+        ('V', "ADC" | "SBC" | "RRA" | "ISC") => pick(
+            "the signed result doesn't fit in -128..127",
+            "the signed result fits in -128..127",
+        ),
+        ('C', "CMP" | "CPX" | "CPY" | "DCP" | "SBX") => pick(
+            "the register is greater than or equal to the operand",
+            "the register is less than the operand",
+        ),
+        ('C', "ADC" | "RRA") => pick("the sum carried out of bit 7", "the sum fits in 8 bits"),
+        ('C', "SBC" | "ISC") => pick("no borrow was needed", "the subtraction borrowed"),
+        ('C', "ASL" | "ROL" | "SLO" | "RLA") => {
+            pick("bit 7 shifted out was 1", "bit 7 shifted out was 0")
+        }
+        ('C', "LSR" | "ROR" | "SRE" | "ALR") => {
+            pick("bit 0 shifted out was 1", "bit 0 shifted out was 0")
+        }
+        _ => pick("set by the instruction", "cleared by the instruction"),
+    }
+}
+
+/// What a bus access was for, going by where it went.
+fn access_role(before: &Before, mode: Addrmode, cycle: &BusCycle) -> &'static str {
+    let offset = cycle.addr.wrapping_sub(before.pc);
+    let data = before.target.is_some_and(|(adr, _)| adr == cycle.addr);
+    match cycle.access {
+        Access::Read if cycle.dummy => "dummy read",
+        Access::Write if cycle.dummy => "dummy write",
+        Access::Read if offset == 0 => "opcode fetch",
+        Access::Read if offset < disasm::len(mode) => "operand fetch",
+        Access::Read if data => "data read",
+        Access::Write if data => "data write",
+        _ if cycle.addr & 0xFF00 == 0x0100 => match cycle.access {
+            Access::Read => "stack pull",
+            Access::Write => "stack push",
+        },
+        Access::Read if matches!(mode, Addrmode::Ind | Addrmode::XInd | Addrmode::IndY) => {
+            "pointer read"
+        }
+        Access::Read => "read",
+        Access::Write => "write",
+    }
+}
+
+/// Plain-English account of the instruction `before` was captured for,
+/// now that it has run: how its operand was found, what it does, every
+/// register, flag and byte of memory it changed, and the bus accesses it
+/// made, in order. Indexing and read-modify-write show the dummy accesses
+/// the chip makes along the way; the idle reads of implied and stack
+/// instructions aren't made, so those show fewer accesses than cycles.
+pub fn explain(before: &Before, cpu: &mut CPU) -> Vec<String> {
+    let log = cpu.bus.take_log();
+    let mut out = vec![before.text.clone()];
+    let (name, mode) = disasm::decode(before.opcode);
+
+    out.push(format!(
+        "  fetched opcode ${:02X}: {} in {} mode",
+        before.opcode,
+        name,
+        mode_name(mode)
+    ));
+    out.push(format!("  {}", before.resolved));
+    out.push(format!("  {} {}", name, summary(name)));
+
+    let regs = [
+        ("A", before.reg.a, cpu.reg.a),
+        ("X", before.reg.x, cpu.reg.x),
+        ("Y", before.reg.y, cpu.reg.y),
+        ("SP", before.reg.sp, cpu.reg.sp),
+    ];
+    for (reg, old, new) in regs {
+        if old != new {
+            out.push(format!("  {}: ${:02X} -> ${:02X}", reg, old, new));
+        }
+    }
+
+    if let Some((adr, old)) = before.target {
+        let new = cpu.bus.memory.read(adr);
+        if new != old {
+            out.push(format!(
+                "  memory ${:04X}: ${:02X} -> ${:02X}",
+                adr, old, new
+            ));
+        }
+    }
+    let pushed = before.reg.sp.wrapping_sub(cpu.reg.sp) as i8;
+    if pushed > 0 {
+        out.push(format!("  pushed {} byte(s) onto the stack", pushed));
+    } else if pushed < 0 {
+        out.push(format!("  pulled {} byte(s) from the stack", -pushed));
+    }
+
+    let flags = u8::from(cpu.flags);
+    for (bit, flag) in [(0, 'C'), (1, 'Z'), (2, 'I'), (3, 'D'), (6, 'V'), (7, 'N')] {
+        let (old, new) = (before.flags >> bit & 1, flags >> bit & 1);
+        if old != new {
+            let set = new == 1;
+            out.push(format!(
+                "  {} {}: {}",
+                flag,
+                if set { "set" } else { "cleared" },
+                flag_reason(name, flag, set)
+            ));
+        }
+    }
+
+    let next = before.pc.wrapping_add(disasm::len(mode));
+    if mode == Addrmode::Rel {
+        if cpu.pc == next {
+            out.push("  branch not taken".to_string());
+        } else {
+            out.push(format!("  branch taken to ${:04X}", cpu.pc));
+        }
+    } else if cpu.pc != next && !cpu.halted && !cpu.is_jammed() {
+        out.push(format!("  PC -> ${:04X}", cpu.pc));
+    }
+    out.push(format!("  took {} cycles", cpu.cycles - before.cycles));
+    for cycle in &log {
+        let (verb, arrow) = match cycle.access {
+            Access::Read => ("read ", "->"),
+            Access::Write => ("write", "<-"),
+        };
+        out.push(format!(
+            "    {} ${:04X} {} ${:02X}  {}",
+            verb,
+            cycle.addr,
+            arrow,
+            cycle.data,
+            access_role(before, mode, cycle)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::explain::*;
+
+    fn step(cpu: &mut CPU) -> Vec<String> {
+        let before = capture(cpu);
+        cpu.exec();
+        explain(&before, cpu)
+    }
+
+    #[test]
+    fn explains_steps() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa9, 0x80, // LDA #$80
+            0x9d, 0xfe, 0x01, // STA $01FE,X
+            0x30, 0xf9, // BMI $0600
+        ]);
+        cpu.reg.x = 5;
+
+        assert_eq!(
+            step(&mut cpu),
+            [
+                "$0600  A9 80     LDA #$80",
+                "  fetched opcode $A9: LDA in immediate mode",
+                "  the operand is the byte $80 after the opcode",
+                "  LDA loads the operand into A",
+                "  A: $00 -> $80",
+                "  N set: bit 7 of the result is 1",
+                "  took 2 cycles",
+                "    read  $0600 -> $A9  opcode fetch",
+                "    read  $0601 -> $80  operand fetch",
+            ]
+        );
+
+        assert_eq!(
+            step(&mut cpu),
+            [
+                "$0602  9D FE 01  STA $01FE,X",
+                "  fetched opcode $9D: STA in absolute,X mode",
+                "  $01FE + X ($05) = $0203, crossing a page, a cycle writes spend anyway",
+                "  STA stores A in memory",
+                "  memory $0203: $00 -> $80",
+                "  took 5 cycles",
+                "    read  $0602 -> $9D  opcode fetch",
+                "    read  $0603 -> $FE  operand fetch",
+                "    read  $0604 -> $01  operand fetch",
+                "    read  $0103 -> $00  dummy read",
+                "    write $0203 <- $80  data write",
+            ]
+        );
+
+        let lines = step(&mut cpu);
+        assert_eq!(
+            lines[2],
+            "  offset $F9 (-7) from the next instruction at $0607 gives $0600"
+        );
+        assert!(lines.contains(&"  branch taken to $0600".to_string()));
+    }
+}
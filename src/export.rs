@@ -0,0 +1,213 @@
+use std::collections::BTreeSet;
+
+use crate::cpu::instructions::Addrmode;
+use crate::cpu::CPU;
+use crate::disasm;
+
+/// Where programs are loaded, and where the exported source is based.
+pub const ORIGIN: u16 = 0x0600;
+
+/// Which addresses an instruction was fetched from.
+pub struct Coverage {
+    executed: Vec<bool>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage {
+            executed: vec![false; 0x10000],
+        }
+    }
+}
+
+impl Coverage {
+    pub fn mark(&mut self, pc: u16) {
+        self.executed[pc as usize] = true;
+    }
+
+    pub fn executed(&self, adr: u16) -> bool {
+        self.executed[adr as usize]
+    }
+}
+
+/// Start addresses of the instructions executed in `ORIGIN..end`, with
+/// their lengths. An instruction running past `end` or into the next one
+/// doesn't count, its bytes are left as data.
+fn instructions(cpu: &CPU, cov: &Coverage, end: u16) -> Vec<(u16, u16)> {
+    let mut found = Vec::new();
+    let mut adr = ORIGIN;
+    while adr < end {
+        let op = cpu.bus.memory.read(adr);
+        let n = disasm::len(disasm::decode(op).1);
+        let fits = cov.executed(adr) && adr as u32 + n as u32 <= end as u32;
+        if fits && !(1..n).any(|i| cov.executed(adr + i)) {
+            found.push((adr, n));
+            adr += n;
+        } else {
+            adr += 1;
+        }
+    }
+    found
+}
+
+fn label(adr: u16) -> String {
+    format!("L{:04X}", adr)
+}
+
+/// The operand in ca65 syntax, naming jump and branch targets that have a
+/// label and forcing absolute addressing where ca65 would pick zero page.
+fn operand(mode: Addrmode, pc: u16, lo: u8, hi: u8, labels: &BTreeSet<u16>) -> String {
+    use Addrmode::*;
+    let abs = (hi as u16) << 8 | lo as u16;
+    match mode {
+        Rel => {
+            let dest = pc.wrapping_add(2).wrapping_add(lo as i8 as u16);
+            if labels.contains(&dest) {
+                label(dest)
+            } else {
+                format!("${:04X}", dest)
+            }
+        }
+        Abs | Ind if labels.contains(&abs) => {
+            let l = label(abs);
+            if mode == Ind {
+                format!("({})", l)
+            } else {
+                l
+            }
+        }
+        Abs | AbsX | AbsY if abs < 0x100 => {
+            format!("a:{}", disasm::operand(mode, pc, lo, hi))
+        }
+        _ => disasm::operand(mode, pc, lo, hi),
+    }
+}
+
+fn bytes_line(bytes: &[u8]) -> String {
+    let list: Vec<String> = bytes.iter().map(|b| format!("${:02X}", b)).collect();
+    format!("        .byte {}", list.join(", "))
+}
+
+/// ca65 source for the program loaded at `ORIGIN..end`: code that was
+/// executed as instructions, with labels on every jump and branch target
+/// inside it, and everything else as `.byte`. Assembled at `ORIGIN`, as
+/// the front-end does, it rebuilds the same bytes.
+pub fn source(cpu: &CPU, cov: &Coverage, end: u16) -> String {
+    let mem = &cpu.bus.memory;
+    let code = instructions(cpu, cov, end);
+    let starts: BTreeSet<u16> = code.iter().map(|&(adr, _)| adr).collect();
+
+    let mut labels = BTreeSet::new();
+    for &(adr, _) in &code {
+        let (lo, hi) = (mem.read(adr.wrapping_add(1)), mem.read(adr.wrapping_add(2)));
+        let target = match disasm::decode(mem.read(adr)) {
+            (_, Addrmode::Rel) => adr.wrapping_add(2).wrapping_add(lo as i8 as u16),
+            ("JMP" | "JSR", Addrmode::Abs) => (hi as u16) << 8 | lo as u16,
+            _ => continue,
+        };
+        if starts.contains(&target) {
+            labels.insert(target);
+        }
+    }
+
+    let mut out = vec![
+        "; Disassembled by rusty6502 from the code a run executed; bytes that".to_string(),
+        format!("; never ran are data. Assemble at ${:04X}.", ORIGIN),
+        String::new(),
+    ];
+    let mut data = Vec::new();
+    let mut adr = ORIGIN;
+    let mut code = code.into_iter().peekable();
+
+    while adr < end {
+        let next_code = code.peek().map(|&(a, _)| a);
+        if next_code != Some(adr) {
+            data.push(mem.read(adr));
+            adr += 1;
+            if data.len() == 8 || next_code == Some(adr) || adr == end {
+                out.push(bytes_line(&data));
+                data.clear();
+            }
+            continue;
+        }
+
+        let (_, n) = code.next().unwrap();
+        let (op, lo, hi) = (
+            mem.read(adr),
+            mem.read(adr.wrapping_add(1)),
+            mem.read(adr.wrapping_add(2)),
+        );
+        let (name, mode) = disasm::decode(op);
+        if labels.contains(&adr) {
+            out.push(format!("{}:", label(adr)));
+        }
+
+        // ca65 only knows the documented opcodes unless told otherwise
+        if disasm::documented(op) {
+            let text = format!(
+                "{} {}",
+                name.to_lowercase(),
+                operand(mode, adr, lo, hi, &labels)
+            );
+            out.push(format!("        {}", text.trim_end()));
+        } else {
+            let text = format!(
+                "{} {}",
+                name.to_lowercase(),
+                disasm::operand(mode, adr, lo, hi)
+            );
+            let bytes = bytes_line(&[op, lo, hi][..n as usize]);
+            out.push(format!("{:32}; {}", bytes, text.trim_end()));
+        }
+        adr += n;
+    }
+
+    out.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::export::*;
+
+    #[test]
+    fn executed_code_and_data() {
+        let program = vec![
+            0xa2, 0x00, // $0600 LDX #$00
+            0xbd, 0x10, 0x00, // $0602 LDA $0010,X
+            0xe8, // $0605 INX
+            0xd0, 0xfa, // $0606 BNE $0602
+            0x4c, 0x0d, 0x06, // $0608 JMP $060D
+            0x8b, 0x3c, // $060B ANE #$3C, never run
+            0x00, // $060D BRK
+            0x12, 0x34, // data
+        ];
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(program.clone());
+
+        let mut cov = Coverage::default();
+        for adr in [0x0600, 0x0602, 0x0605, 0x0606, 0x0608, 0x060D] {
+            cov.mark(adr);
+        }
+        let end = ORIGIN + program.len() as u16;
+
+        assert_eq!(
+            source(&cpu, &cov, end).lines().skip(3).collect::<Vec<_>>(),
+            [
+                "        ldx #$00",
+                "L0602:",
+                "        lda a:$0010,X",
+                "        inx",
+                "        bne L0602",
+                "        jmp L060D",
+                "        .byte $8B, $3C",
+                "L060D:",
+                "        brk",
+                "        .byte $12, $34",
+            ]
+        );
+
+        cov.mark(0x060B);
+        assert!(source(&cpu, &cov, end).contains("        .byte $8B, $3C          ; ane #$3C\n"));
+    }
+}
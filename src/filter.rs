@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+
+/// Output pixels per display pixel when a filter is active.
+pub const SCALE: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Filter {
+    /// Nearest-neighbour scaling done by SDL
+    None,
+    /// Darken every other output row
+    Scanlines,
+    /// Scanlines plus horizontal bleed between neighbouring pixels
+    Phosphor,
+}
+
+fn scanline(y: usize, c: u8) -> u8 {
+    if y % 2 == 1 {
+        (c as u16 * 3 / 5) as u8
+    } else {
+        c
+    }
+}
+
+/// Upscales an RGB24 frame of `w`x`h` pixels by SCALE into `out`.
+pub fn apply(filter: Filter, src: &[u8], w: usize, h: usize, out: &mut Vec<u8>) {
+    let ow = w * SCALE;
+    out.resize(ow * h * SCALE * 3, 0);
+
+    for oy in 0..h * SCALE {
+        let row = &src[(oy / SCALE) * w * 3..][..w * 3];
+        for ox in 0..ow {
+            let x = ox / SCALE;
+            let sub = ox % SCALE;
+            let o = (oy * ow + ox) * 3;
+
+            for ch in 0..3 {
+                let mut c = row[x * 3 + ch];
+                if filter == Filter::Phosphor {
+                    // blend towards the neighbour over the outer 2 columns
+                    let neighbour = match sub {
+                        0 | 1 if x > 0 => Some(x - 1),
+                        s if s >= SCALE - 2 && x + 1 < w => Some(x + 1),
+                        _ => None,
+                    };
+                    if let Some(n) = neighbour {
+                        let weight = if sub == 0 || sub == SCALE - 1 { 2 } else { 1 };
+                        let nc = row[n * 3 + ch] as u16;
+                        c = ((c as u16 * (4 - weight) + nc * weight) / 4) as u8;
+                    }
+                }
+                if filter != Filter::None {
+                    c = scanline(oy, c);
+                }
+                out[o + ch] = c;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Palette {
+    /// Colours as the program wrote them
+    Normal,
+    /// Shift red/green differences for missing L cones
+    Protanopia,
+    /// Shift red/green differences for missing M cones
+    Deuteranopia,
+    /// Shift blue/yellow differences for missing S cones
+    Tritanopia,
+}
+
+type Matrix = [[f32; 3]; 3];
+
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+impl Palette {
+    /// Daltonization matrix: simulate the deficiency in LMS space, then add
+    /// the colour information that was lost back into channels that are
+    /// still seen.
+    fn matrix(self) -> Option<Matrix> {
+        const RGB_TO_LMS: Matrix = [
+            [17.8824, 43.5161, 4.11935],
+            [3.45565, 27.1554, 3.86714],
+            [0.0299566, 0.184309, 1.46709],
+        ];
+        const LMS_TO_RGB: Matrix = [
+            [0.08094445, -0.13050441, 0.116721066],
+            [-0.010248533, 0.05401933, -0.11361471],
+            [-0.00036529693, -0.0041216146, 0.6935114],
+        ];
+        const SHIFT: Matrix = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+
+        let simulate: Matrix = match self {
+            Palette::Normal => return None,
+            Palette::Protanopia => [[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            Palette::Deuteranopia => [[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]],
+            Palette::Tritanopia => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-0.395913, 0.801109, 0.0]],
+        };
+
+        // I + SHIFT * (I - seen)
+        let seen = mul(&LMS_TO_RGB, &mul(&simulate, &RGB_TO_LMS));
+        let mut lost = [[0.0; 3]; 3];
+        for (i, row) in lost.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (i == j) as u8 as f32 - seen[i][j];
+            }
+        }
+        let mut m = mul(&SHIFT, &lost);
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] += 1.0;
+        }
+        Some(m)
+    }
+}
+
+/// Recolours an RGB24 frame in place for the given palette.
+pub fn recolor(palette: Palette, rgb: &mut [u8]) {
+    let Some(m) = palette.matrix() else { return };
+    for px in rgb.chunks_exact_mut(3) {
+        let c = [px[0] as f32, px[1] as f32, px[2] as f32];
+        for (out, row) in px.iter_mut().zip(&m) {
+            let v: f32 = row.iter().zip(&c).map(|(a, b)| a * b).sum();
+            *out = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// A change in mean luminance this large (out of 1.0) counts as one flash
+/// transition, following the WCAG general flash threshold.
+const FLASH_DELTA: f32 = 0.1;
+
+fn mean_luma(rgb: &[u8]) -> f32 {
+    let sum: f32 = rgb
+        .chunks_exact(3)
+        .map(|p| 0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32)
+        .sum();
+    sum / (rgb.len() / 3).max(1) as f32 / 255.0
+}
+
+/// Caps full-screen flashes at `per_second`. A frame that would go over the
+/// cap is blended with the last shown frame so the luminance only moves by
+/// just under a flash.
+pub struct FlashLimiter {
+    per_second: usize,
+    transitions: VecDeque<Instant>,
+    shown: Vec<u8>,
+    luma: f32,
+}
+
+impl FlashLimiter {
+    pub fn new(per_second: usize) -> Self {
+        FlashLimiter {
+            per_second,
+            transitions: VecDeque::new(),
+            shown: Vec::new(),
+            luma: 0.0,
+        }
+    }
+
+    pub fn apply(&mut self, rgb: &mut [u8], now: Instant) {
+        if self.shown.len() != rgb.len() {
+            self.shown = rgb.to_vec();
+            self.luma = mean_luma(rgb);
+            return;
+        }
+
+        while self
+            .transitions
+            .front()
+            .is_some_and(|&t| now - t >= Duration::from_secs(1))
+        {
+            self.transitions.pop_front();
+        }
+
+        let luma = mean_luma(rgb);
+        let delta = (luma - self.luma).abs();
+        if delta >= FLASH_DELTA {
+            // a flash is a pair of opposing transitions
+            if self.transitions.len() >= self.per_second * 2 {
+                let k = FLASH_DELTA * 0.9 / delta;
+                for (new, old) in rgb.iter_mut().zip(&self.shown) {
+                    *new = (*old as f32 + (*new as f32 - *old as f32) * k).round() as u8;
+                }
+            } else {
+                self.transitions.push_back(now);
+            }
+        }
+
+        self.shown.copy_from_slice(rgb);
+        self.luma = mean_luma(rgb);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::*;
+
+    #[test]
+    fn scanlines_darken_odd_rows() {
+        let src = [200, 100, 0];
+        let mut out = Vec::new();
+        apply(Filter::Scanlines, &src, 1, 1, &mut out);
+
+        assert_eq!(out.len(), SCALE * SCALE * 3);
+        assert_eq!(&out[..3], &[200, 100, 0]);
+        assert_eq!(&out[SCALE * 3..][..3], &[120, 60, 0]);
+    }
+
+    #[test]
+    fn phosphor_bleeds_at_edges() {
+        let src = [0, 0, 0, 200, 200, 200];
+        let mut out = Vec::new();
+        apply(Filter::Phosphor, &src, 2, 1, &mut out);
+
+        // last column of the black pixel, then first of the white one
+        assert_eq!(out[(SCALE - 1) * 3], 100);
+        assert_eq!(out[(SCALE + 1) * 3], 150);
+        assert_eq!(out[(SCALE / 2) * 3], 0);
+    }
+
+    #[test]
+    fn palettes() {
+        let mut rgb = [255, 0, 0, 0, 255, 0, 10, 20, 30];
+        recolor(Palette::Normal, &mut rgb);
+        assert_eq!(rgb, [255, 0, 0, 0, 255, 0, 10, 20, 30]);
+
+        // greys carry no colour to lose
+        let mut grey = [128, 128, 128];
+        recolor(Palette::Deuteranopia, &mut grey);
+        assert!(grey.iter().all(|&c| c.abs_diff(128) <= 1));
+
+        // red and green stop looking alike once shifted
+        let mut shifted = rgb;
+        recolor(Palette::Protanopia, &mut shifted);
+        assert_ne!(shifted[..6], rgb[..6]);
+    }
+
+    #[test]
+    fn flash_limiter() {
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+        let mut limiter = FlashLimiter::new(1);
+
+        let mut frame = [0; 3];
+        limiter.apply(&mut frame, t0);
+
+        // one flash a second is fine
+        let mut white = [255; 3];
+        limiter.apply(&mut white, t0 + ms(100));
+        assert_eq!(white, [255; 3]);
+        let mut black = [0; 3];
+        limiter.apply(&mut black, t0 + ms(200));
+        assert_eq!(black, [0; 3]);
+
+        // the next one inside the same second is damped
+        let mut white = [255; 3];
+        limiter.apply(&mut white, t0 + ms(300));
+        assert!(mean_luma(&white) < FLASH_DELTA);
+
+        // and allowed again once the window has passed
+        let mut white = [255; 3];
+        limiter.apply(&mut white, t0 + ms(1300));
+        assert_eq!(white, [255; 3]);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::bus::{Access, BusCycle};
+use crate::cpu::CPU;
+use crate::crash;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// Any read, write or instruction fetch traps
+    Guard,
+    /// Only running code there traps
+    NoExecute,
+}
+
+/// An inclusive range of addresses that stops the CPU when touched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub start: u16,
+    pub end: u16,
+    pub kind: Kind,
+}
+
+impl Region {
+    pub fn contains(&self, adr: u16) -> bool {
+        (self.start..=self.end).contains(&adr)
+    }
+}
+
+/// `START-END` or a single `ADDR`, in hex, as a guard region.
+impl std::str::FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let adr = |a: &str| {
+            u16::from_str_radix(a.trim_start_matches('$'), 16)
+                .map_err(|_| format!("expected START-END in hex: {}", s))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((a, b)) => (adr(a)?, adr(b)?),
+            None => (adr(s)?, adr(s)?),
+        };
+        if start > end {
+            return Err(format!("range ends before it starts: {}", s));
+        }
+        Ok(Region {
+            start,
+            end,
+            kind: Kind::Guard,
+        })
+    }
+}
+
+/// A region being touched: the instruction at `pc` either started inside
+/// it (`access` is None) or read or wrote it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fault {
+    pub pc: u16,
+    pub access: Option<BusCycle>,
+    pub region: Region,
+}
+
+fn describe(region: &Region) -> String {
+    let kind = match region.kind {
+        Kind::Guard => "guard",
+        Kind::NoExecute => "no-execute",
+    };
+    if region.start == region.end {
+        format!("{} address ${:04X}", kind, region.start)
+    } else {
+        format!("{} region ${:04X}-${:04X}", kind, region.start, region.end)
+    }
+}
+
+/// What touched the region and the code and jumps that led there. An
+/// access is reported after its instruction ran; a fetch stops the CPU
+/// before the instruction runs.
+pub fn report(cpu: &CPU, fault: &Fault) -> String {
+    let what = match fault.access {
+        None => format!("executing ${:04X}", fault.pc),
+        Some(BusCycle {
+            addr,
+            data,
+            access: Access::Read,
+            ..
+        }) => format!("read ${:04X} -> ${:02X}", addr, data),
+        Some(BusCycle {
+            addr,
+            data,
+            access: Access::Write,
+            ..
+        }) => format!("write ${:04X} <- ${:02X}", addr, data),
+    };
+
+    let mut out = vec![format!(
+        "Guard hit: {} in {}",
+        what,
+        describe(&fault.region)
+    )];
+    if fault.access.is_some() {
+        out.push(format!("by the instruction at ${:04X}", fault.pc));
+    }
+    out.extend(crash::context(cpu, fault.pc));
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::ExitReason;
+    use crate::guard::*;
+
+    #[test]
+    fn stops_on_touch() {
+        assert_eq!(
+            "$0300-03ff".parse(),
+            Ok(Region {
+                start: 0x0300,
+                end: 0x03ff,
+                kind: Kind::Guard
+            })
+        );
+        assert!("0400-0300".parse::<Region>().is_err());
+
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.guard("0310".parse().unwrap());
+        cpu.load(vec![
+            0xa9, 0x05, // LDA #$05
+            0x8d, 0x10, 0x03, // STA $0310
+            0xe8, // INX
+        ]);
+        assert_eq!(cpu.run(|_| {}), ExitReason::Guard);
+        assert_eq!(cpu.pc, 0x0605);
+
+        let fault = cpu.fault.unwrap();
+        let text = report(&cpu, &fault);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[..2],
+            [
+                "Guard hit: write $0310 <- $05 in guard address $0310",
+                "by the instruction at $0602",
+            ]
+        );
+        assert!(lines.contains(&" > $0602  8D 10 03  STA $0310"));
+
+        // a jump into no-execute memory stops before anything runs there
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.guard(Region {
+            kind: Kind::NoExecute,
+            ..("0200-05ff".parse().unwrap())
+        });
+        cpu.load(vec![
+            0xa9, 0x05, // LDA #$05
+            0x8d, 0x00, 0x02, // STA $0200
+            0x4c, 0x00, 0x02, // JMP $0200
+        ]);
+        assert_eq!(cpu.run(|_| {}), ExitReason::Guard);
+        assert_eq!(cpu.instructions, 3);
+        assert_eq!(
+            report(&cpu, &cpu.fault.unwrap()).lines().next(),
+            Some("Guard hit: executing $0200 in no-execute region $0200-$05FF")
+        );
+    }
+}
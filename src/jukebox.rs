@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+
+use crate::assemble;
+
+// How often attract mode presses a key.
+const KEY_INTERVAL: Duration = Duration::from_millis(400);
+// Pressed at random when a program has no .attract file.
+const ATTRACT_KEYS: &[u8] = b"wasd";
+
+fn is_program(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "bin" || ext == "nes")
+        || path.to_str().is_some_and(assemble::is_source)
+}
+
+/// Every loadable program in `dir`, sorted by name.
+pub fn programs(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| is_program(p))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+/// Cycles through the programs in a folder, giving each a fixed slot of time.
+/// The folder is rescanned on every switch, so programs can be dropped in or
+/// removed while it runs.
+///
+/// While a program plays, keys from `<program>.attract` are fed to it one
+/// every KEY_INTERVAL, looping; programs without one get random WASD.
+pub struct Jukebox {
+    dir: PathBuf,
+    slot: Duration,
+    started: Instant,
+    current: Option<PathBuf>,
+    attract: Vec<u8>,
+    attract_pos: usize,
+    last_key: Instant,
+}
+
+impl Jukebox {
+    pub fn new(dir: &str, slot: Duration) -> Self {
+        Jukebox {
+            dir: PathBuf::from(dir),
+            slot,
+            started: Instant::now(),
+            current: None,
+            attract: Vec::new(),
+            attract_pos: 0,
+            last_key: Instant::now(),
+        }
+    }
+
+    /// Moves on to the program after the current one, wrapping around.
+    /// Returns None if the folder has no programs left.
+    pub fn advance(&mut self) -> Option<String> {
+        let programs = programs(&self.dir);
+        let next = match &self.current {
+            Some(cur) => programs
+                .iter()
+                .find(|p| *p > cur)
+                .or_else(|| programs.first()),
+            None => programs.first(),
+        }?
+        .clone();
+
+        let mut attract = next.clone().into_os_string();
+        attract.push(".attract");
+        self.attract = fs::read(attract).unwrap_or_default();
+        self.attract_pos = 0;
+        self.started = Instant::now();
+        self.current = Some(next.clone());
+
+        Some(next.to_string_lossy().into_owned())
+    }
+
+    /// Whether the current program has used up its slot.
+    pub fn expired(&self) -> bool {
+        self.started.elapsed() >= self.slot
+    }
+
+    /// The next attract-mode key, if one is due.
+    pub fn key(&mut self) -> Option<u8> {
+        if self.last_key.elapsed() < KEY_INTERVAL {
+            return None;
+        }
+        self.last_key = Instant::now();
+
+        if self.attract.is_empty() {
+            return ATTRACT_KEYS.choose(&mut rand::thread_rng()).copied();
+        }
+
+        let k = self.attract[self.attract_pos % self.attract.len()];
+        self.attract_pos += 1;
+        Some(k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jukebox::*;
+
+    #[test]
+    fn cycles_through_folder() {
+        let dir = std::env::temp_dir().join("rusty6502-jukebox");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        for name in ["b.bin", "a.nes", "notes.txt"] {
+            fs::write(dir.join(name), [0]).unwrap();
+        }
+        fs::write(dir.join("b.bin.attract"), b"ws").unwrap();
+
+        let mut j = Jukebox::new(dir.to_str().unwrap(), Duration::from_secs(60));
+        assert!(j.advance().unwrap().ends_with("a.nes"));
+        assert!(!j.expired());
+
+        // a program added mid-run is picked up on the next switch
+        fs::write(dir.join("ab.s"), [0]).unwrap();
+        assert!(j.advance().unwrap().ends_with("ab.s"));
+        assert!(j.advance().unwrap().ends_with("b.bin"));
+
+        j.last_key -= KEY_INTERVAL;
+        assert_eq!(j.key(), Some(b'w'));
+        assert_eq!(j.key(), None);
+
+        assert!(j.advance().unwrap().ends_with("a.nes"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
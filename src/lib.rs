@@ -0,0 +1,62 @@
+//! A 6502 CPU core with a pluggable bus, plus the parts of the emulator's
+//! front-ends that don't need SDL: displays, debugging aids, save states
+//! and the test-ROM and soak harnesses. The SDL front-end is the `nesemu`
+//! binary, built with the default features. Without them the library
+//! needs none of clap, zstd, libc or tokio; the `savestate`, `terminal` and
+//! `stream` features add the modules of those names.
+//!
+//! ```
+//! use nesemu::{Bus, CPU};
+//!
+//! let mut cpu = CPU::new(Bus::default());
+//! cpu.stop_on_brk = true;
+//! cpu.load(vec![0xa9, 0x42, 0x00]); // LDA #$42, BRK
+//! cpu.run(|_| {});
+//! assert_eq!(cpu.reg.a, 0x42);
+//! ```
+
+pub mod assemble;
+pub mod bus;
+pub mod cartridge;
+pub mod cheats;
+pub mod clock;
+pub mod compat;
+pub mod cpu;
+pub mod crash;
+pub mod debug;
+pub mod demo;
+pub mod disasm;
+pub mod explain;
+pub mod export;
+pub mod filter;
+pub mod guard;
+pub mod jukebox;
+pub mod machines;
+#[cfg(feature = "savestate")]
+pub mod memdiff;
+pub mod memory;
+pub mod pipe;
+pub mod profile;
+pub mod runner;
+#[cfg(feature = "savestate")]
+pub mod savestate;
+pub mod screen;
+pub mod script;
+#[cfg(feature = "savestate")]
+pub mod search;
+pub mod soak;
+#[cfg(feature = "stream")]
+pub mod stream;
+pub mod taint;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub mod testrom;
+pub mod text;
+pub mod touch;
+pub mod trace;
+pub mod vnc;
+pub mod watchdog;
+
+pub use bus::Bus;
+pub use cpu::registers::{Flag, Registers};
+pub use cpu::CPU;
@@ -0,0 +1,96 @@
+use std::thread;
+
+use crate::clock;
+use crate::cpu::CPU;
+
+/// Names a machine in a `Machines`. Stays valid for the life of the set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Id(usize);
+
+/// Machines run side by side in one process, for lockstep comparisons and
+/// the like. Nothing is shared between them: each CPU owns its bus, memory
+/// and trace log.
+#[derive(Default)]
+pub struct Machines {
+    cpus: Vec<CPU>,
+}
+
+impl Machines {
+    pub fn spawn(&mut self, cpu: CPU) -> Id {
+        self.cpus.push(cpu);
+        Id(self.cpus.len() - 1)
+    }
+
+    pub fn get(&self, id: Id) -> &CPU {
+        &self.cpus[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: Id) -> &mut CPU {
+        &mut self.cpus[id.0]
+    }
+
+    /// In the order they were spawned.
+    pub fn ids(&self) -> impl Iterator<Item = Id> {
+        (0..self.cpus.len()).map(Id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cpus.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cpus.is_empty()
+    }
+
+    /// Runs every machine for `cycles` more cycles, or until it halts or
+    /// jams, each on a thread of its own.
+    pub fn run_for(&mut self, cycles: u64) {
+        thread::scope(|s| {
+            for cpu in &mut self.cpus {
+                s.spawn(move || clock::advance_cycles(cpu, cycles));
+            }
+        });
+    }
+
+    /// Machines whose state hash differs from the first one's.
+    pub fn diverged(&self) -> Vec<Id> {
+        let Some(first) = self.cpus.first().map(CPU::state_hash) else {
+            return Vec::new();
+        };
+        self.ids()
+            .filter(|&id| self.get(id).state_hash() != first)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::machines::*;
+
+    #[test]
+    fn run_side_by_side() {
+        let machine = |step: u8| {
+            let mut cpu = CPU::new(Bus::default());
+            cpu.load(vec![
+                0xa5, 0x10, // $0600 LDA $10
+                0x69, step, // $0602 ADC #step
+                0x85, 0x10, // $0604 STA $10
+                0x4c, 0x00, 0x06, // $0606 JMP $0600
+            ]);
+            cpu
+        };
+
+        let mut machines = Machines::default();
+        let a = machines.spawn(machine(1));
+        let b = machines.spawn(machine(1));
+        machines.run_for(1_000);
+        assert_eq!(machines.get(a).cycles, machines.get(b).cycles);
+        assert_eq!(machines.diverged(), []);
+
+        let c = machines.spawn(machine(2));
+        machines.run_for(1_000);
+        assert_eq!(machines.diverged(), [c]);
+        assert_eq!(machines.ids().collect::<Vec<_>>(), [a, b, c]);
+    }
+}
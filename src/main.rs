@@ -1,20 +1,37 @@
 use rand::Rng;
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
 use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::video::{Window, WindowPos};
 use sdl2::EventPump;
 // use std::env;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
 use std::process;
+use std::time::{Duration, Instant, SystemTime};
 
 mod args;
-mod bus;
-mod cpu;
 
-use args::EmuArgs;
-use bus::Bus;
+use nesemu::{
+    assemble, cheats, compat, crash, debug, demo, explain, export, filter, guard, jukebox, memdiff,
+    pipe, profile, savestate, screen, search, soak, taint, terminal, testrom, text, touch, trace,
+    vnc, watchdog,
+};
+
+use args::{
+    Background, Brk, Command, DemoArgs, DiffArgs, EmuArgs, RunArgs, SearchArgs, SoakArgs,
+    StatusSource, TestArgs, Video,
+};
 use clap::Parser;
-use cpu::CPU;
+use filter::{Filter, Palette};
+use jukebox::Jukebox;
+use nesemu::bus::Bus;
+use nesemu::cpu::{ExitReason, Limits, CPU};
+use profile::{Phase, Profiler};
+use screen::{Display, Layout, PixelFormat, Screen};
+use text::TextDisplay;
 
 #[derive(Default)]
 pub struct Queue {
@@ -51,21 +68,142 @@ impl Queue {
     }
 }
 
-fn color(byte: u8) -> Color {
-    match byte {
-        0 => sdl2::pixels::Color::BLACK,
-        1 => sdl2::pixels::Color::WHITE,
-        2 | 9 => sdl2::pixels::Color::GREY,
-        3 | 10 => sdl2::pixels::Color::RED,
-        4 | 11 => sdl2::pixels::Color::GREEN,
-        5 | 12 => sdl2::pixels::Color::BLUE,
-        6 | 13 => sdl2::pixels::Color::MAGENTA,
-        7 | 14 => sdl2::pixels::Color::YELLOW,
-        _ => sdl2::pixels::Color::CYAN,
+fn quick_save(cpu: &CPU, path: &str) {
+    match savestate::save(cpu).and_then(|state| std::fs::write(path, state)) {
+        Ok(()) => println!("Saved state to {}", path),
+        Err(e) => println!("Could not save state: {}", e),
+    }
+}
+
+fn diff_quick_save(cpu: &CPU, path: &str) {
+    let changes = memdiff::read_memory(Path::new(path))
+        .and_then(|saved| memdiff::diff(&saved, &cpu.bus.memory.dump()));
+    match changes {
+        Ok(changes) => print!("Changes since {}:\n{}", path, memdiff::report(&changes)),
+        Err(e) => println!("Could not compare with {}: {}", path, e),
+    }
+}
+
+fn quick_load(cpu: &mut CPU, path: &str) {
+    match std::fs::read(path).and_then(|state| savestate::load(cpu, &state)) {
+        Ok(()) => println!("Loaded state from {}", path),
+        Err(e) => println!("Could not load state: {}", e),
+    }
+}
+
+/// Polls a file's modification time, at most every 250ms.
+struct Watch {
+    path: String,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Watch {
+    fn new(path: &str) -> Self {
+        Watch {
+            path: path.to_string(),
+            modified: Watch::mtime(path),
+            last_check: Instant::now(),
+        }
+    }
+
+    fn mtime(path: &str) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < Duration::from_millis(250) {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let modified = Watch::mtime(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            return true;
+        }
+        false
+    }
+}
+
+/// Loads the program at `path`, assembling it first if it is source, and
+/// returns its size in bytes.
+/// The program at `path`, assembled first if it is a source file.
+fn read_program(path: &str, assembler: Option<&str>) -> std::io::Result<Vec<u8>> {
+    if assemble::is_source(path) {
+        assemble::assemble(path, assembler)
+    } else {
+        std::fs::read(path)
+    }
+}
+
+fn load_program(cpu: &mut CPU, path: &str, assembler: Option<&str>) -> std::io::Result<usize> {
+    let program = read_program(path, assembler)?;
+    let size = program.len();
+    cpu.load(program);
+    Ok(size)
+}
+
+fn reload(cpu: &mut CPU, path: &str, assembler: Option<&str>) {
+    // devices stay mapped, only memory goes back to its power-on state
+    let init = cpu.bus.ram_init;
+    cpu.bus.power_on(init);
+    match load_program(cpu, path, assembler) {
+        Ok(_) => println!("Reloaded {}", path),
+        Err(e) => println!("Could not reload {}: {}", path, e),
     }
 }
 
-fn update_input(q: &mut Queue, event_pump: &mut EventPump) {
+fn compat_warning(db: &str, path: &str) {
+    let warning = std::fs::read(path).ok().and_then(|data| {
+        let db = compat::Database::load(db).ok()?;
+        db.warning(compat::rom_hash(&data))
+    });
+
+    if let Some(w) = warning {
+        println!("WARNING: {}", w);
+    }
+}
+
+fn load_cheats(path: &str, codes: &[String]) -> std::io::Result<Vec<cheats::Cheat>> {
+    let cht_path = format!("{}.cht", path);
+    let mut list = match cheats::load_file(&cht_path) {
+        Ok(list) => list,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    if codes.is_empty() {
+        return Ok(list);
+    }
+
+    for code in codes {
+        let cheat = cheats::parse_code(code)?;
+        match list.iter_mut().find(|c| c.code == cheat.code) {
+            Some(c) => c.enabled = true,
+            None => list.push(cheat),
+        }
+    }
+    cheats::save_file(&cht_path, &list)?;
+    Ok(list)
+}
+
+/// Front-end state toggled by window events and hotkeys.
+struct UiState {
+    cheats_on: bool,
+    overlay: bool,
+    focused: bool,
+    /// Width over height of the window while the touch d-pad is shown.
+    touch: Option<f32>,
+}
+
+fn update_input(
+    cpu: &mut CPU,
+    q: &mut Queue,
+    event_pump: &mut EventPump,
+    state_path: Option<&str>,
+    ui: &mut UiState,
+) {
     for event in event_pump.poll_iter() {
         let w = match event {
             Event::Quit { .. }
@@ -73,6 +211,59 @@ fn update_input(q: &mut Queue, event_pump: &mut EventPump) {
                 keycode: Some(Keycode::Escape),
                 ..
             } => std::process::exit(0),
+            Event::Window {
+                win_event: WindowEvent::FocusLost,
+                ..
+            } => {
+                ui.focused = false;
+                0x00
+            }
+            Event::Window {
+                win_event: WindowEvent::FocusGained,
+                ..
+            } => {
+                ui.focused = true;
+                0x00
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F2),
+                ..
+            } => {
+                debug::print_stack(cpu);
+                debug::print_interrupts(cpu);
+                0x00
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F3),
+                ..
+            } => {
+                ui.cheats_on = !ui.cheats_on;
+                println!("Cheats {}", if ui.cheats_on { "on" } else { "off" });
+                0x00
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F4),
+                ..
+            } => {
+                ui.overlay = !ui.overlay;
+                0x00
+            }
+            Event::KeyDown {
+                keycode: Some(key @ (Keycode::F5 | Keycode::F6 | Keycode::F9)),
+                ..
+            } => {
+                match (state_path, key) {
+                    (None, _) => println!("Built-in demos have no save states"),
+                    (Some(p), Keycode::F5) => quick_save(cpu, p),
+                    (Some(p), Keycode::F6) => diff_quick_save(cpu, p),
+                    (Some(p), _) => quick_load(cpu, p),
+                }
+                0x00
+            }
+            Event::FingerDown { x, y, .. } => ui
+                .touch
+                .and_then(|aspect| touch::key_at(aspect, x, y))
+                .unwrap_or(0x00),
             Event::KeyDown {
                 keycode: Some(Keycode::W),
                 ..
@@ -99,6 +290,62 @@ fn update_input(q: &mut Queue, event_pump: &mut EventPump) {
     }
 }
 
+/// Whole-number window zoom filling about half the height of the display
+/// `window` is on, so the window is a usable size from 1080p up to 4K.
+fn window_zoom(window: &Window, width: usize, height: usize) -> usize {
+    let bounds = window
+        .display_index()
+        .and_then(|i| window.subsystem().display_usable_bounds(i));
+    match bounds {
+        Ok(bounds) => (bounds.width() as usize / 2 / width)
+            .min(bounds.height() as usize / 2 / height)
+            .max(1),
+        Err(_) => (320 / width.max(height)).max(1),
+    }
+}
+
+/// Stacked bars of recent frame times along the bottom of the window, one
+/// pixel per millisecond.
+fn draw_profile(canvas: &mut WindowCanvas, profiler: &Profiler) {
+    let scale = canvas.scale();
+    let (_, height) = canvas.output_size().unwrap();
+    canvas.set_scale(1.0, 1.0).unwrap();
+
+    for (i, frame) in profiler.history.iter().enumerate() {
+        let mut y = height as i32;
+        for phase in Phase::ALL {
+            let h = frame[phase as usize].as_millis() as u32;
+            if h == 0 {
+                continue;
+            }
+            y -= h as i32;
+            let (r, g, b) = phase.rgb();
+            canvas.set_draw_color(sdl2::pixels::Color::RGB(r, g, b));
+            canvas.fill_rect(Rect::new(i as i32 * 2, y, 2, h)).unwrap();
+        }
+    }
+
+    canvas.set_scale(scale.0, scale.1).unwrap();
+}
+
+fn draw_touch(canvas: &mut WindowCanvas, aspect: f32) {
+    let scale = canvas.scale();
+    let (width, height) = canvas.output_size().unwrap();
+    canvas.set_scale(1.0, 1.0).unwrap();
+    canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+    canvas.set_draw_color(sdl2::pixels::Color::RGBA(0xFF, 0xFF, 0xFF, 0x50));
+
+    for (_, [x, y, w, h]) in touch::buttons(aspect) {
+        let (x, w) = (x * width as f32, w * width as f32);
+        let (y, h) = (y * height as f32, h * height as f32);
+        canvas
+            .fill_rect(Rect::new(x as i32, y as i32, w as u32, h as u32))
+            .unwrap();
+    }
+
+    canvas.set_scale(scale.0, scale.1).unwrap();
+}
+
 fn handle_user_input(cpu: &mut CPU, q: &mut Queue) {
     let w = q.pop();
     if w > 0 {
@@ -106,141 +353,619 @@ fn handle_user_input(cpu: &mut CPU, q: &mut Queue) {
     };
 }
 
-fn read_screen_state(cpu: &mut CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
-    let mut frame_idx = 0;
-    let mut update = false;
-    for i in 0x0200..0x600 {
-        let color_idx = cpu.bus.read(i as u16);
-        let (b1, b2, b3) = color(color_idx).rgb();
-        if frame[frame_idx] != b1 || frame[frame_idx + 1] != b2 || frame[frame_idx + 2] != b3 {
-            frame[frame_idx] = b1;
-            frame[frame_idx + 1] = b2;
-            frame[frame_idx + 2] = b3;
-            update = true;
+/// Says once that tracing stopped, if it did.
+fn report_trace_error(cpu: &mut CPU) {
+    if let Some(e) = cpu.trace_error.take() {
+        println!("IOERROR: trace stopped: {}", e);
+    }
+}
+
+fn exit_status(cpu: &CPU, reason: ExitReason, status: StatusSource) -> i32 {
+    match reason {
+        ExitReason::Halted => match status {
+            StatusSource::Address(adr) => cpu.bus.peek(adr) as i32,
+            StatusSource::A => cpu.reg.a as i32,
+        },
+        ExitReason::CycleLimit | ExitReason::InstructionLimit => {
+            println!(
+                "Execution limit reached after {} instructions ({} cycles)",
+                cpu.instructions, cpu.cycles
+            );
+            124
+        }
+        ExitReason::Guard => {
+            println!("{}", guard::report(cpu, &cpu.fault.unwrap()));
+            125
+        }
+        ExitReason::Jammed => {
+            println!("CPU jammed at ${:04X}", cpu.pc);
+            126
+        }
+        ExitReason::Undocumented => {
+            println!("{}", crash::report(cpu));
+            123
         }
-        frame_idx += 3;
     }
-    update
+}
+
+fn run_tests(args: &TestArgs) -> i32 {
+    let outcomes = match testrom::run_dir(&args.dir, args.max_cycles, args.jobs) {
+        Ok(o) => o,
+        Err(e) => {
+            println!("IOERROR: {}", e);
+            return 1;
+        }
+    };
+
+    testrom::print_report(&outcomes);
+
+    if let Some(junit) = &args.junit {
+        if let Err(e) = std::fs::write(junit, testrom::junit_xml(&outcomes)) {
+            println!("IOERROR: {}", e);
+            return 1;
+        }
+    }
+
+    if outcomes.iter().all(|o| o.passed) {
+        0
+    } else {
+        1
+    }
+}
+
+fn run_soak(args: &SoakArgs) -> i32 {
+    let results = match soak::soak_dir(&args.dir, args.frames, args.jobs) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("IOERROR: {}", e);
+            return 1;
+        }
+    };
+
+    soak::print_report(&results);
+
+    let updated = compat::Database::load(&args.compat_db).and_then(|mut db| {
+        db.record(&results);
+        db.save(&args.compat_db)
+    });
+    if let Err(e) = updated {
+        println!("IOERROR: {}", e);
+        return 1;
+    }
+
+    let reports = [
+        (&args.json, soak::json as fn(_) -> _),
+        (&args.csv, soak::csv),
+    ];
+    for (path, report) in reports {
+        if let Some(path) = path {
+            if let Err(e) = std::fs::write(path, report(&results)) {
+                println!("IOERROR: {}", e);
+                return 1;
+            }
+        }
+    }
+
+    0
+}
+
+fn run_diff(args: &DiffArgs) -> i32 {
+    let changes = memdiff::read_memory(&args.before).and_then(|before| {
+        let after = memdiff::read_memory(&args.after)?;
+        memdiff::diff(&before, &after)
+    });
+
+    match changes {
+        Ok(changes) => {
+            print!("{}", memdiff::report(&changes));
+            0
+        }
+        Err(e) => {
+            println!("IOERROR: {}", e);
+            1
+        }
+    }
+}
+
+/// Runs a program with its ports on stdin and stdout. Anything else goes to
+/// stderr, so as not to mix with the program's output.
+fn run_pipe(args: &RunArgs) -> i32 {
+    let mut cpu = CPU::new(Bus::default());
+    cpu.stop_on_brk = true;
+    let path = args.rom.to_string_lossy();
+    let loaded = read_program(&path, args.assembler.as_deref()).and_then(|data| {
+        soak::load(&mut cpu, data).map_err(|(_, detail)| Error::new(ErrorKind::InvalidData, detail))
+    });
+    if let Err(e) = loaded {
+        eprintln!("IOERROR: {}", e);
+        return 1;
+    }
+
+    // after loading, which may map a cartridge over the page
+    let output = std::io::BufWriter::new(std::io::stdout());
+    cpu.bus.map(
+        args.page..=args.page,
+        Box::new(pipe::Pipe::new(std::io::stdin(), output)),
+    );
+    while !cpu.halted && !cpu.is_jammed() && args.max_cycles.is_none_or(|m| cpu.cycles < m) {
+        cpu.exec();
+    }
+
+    let status = if cpu.halted {
+        0
+    } else if cpu.is_jammed() {
+        eprintln!("Jammed at ${:04X}", cpu.pc);
+        1
+    } else {
+        eprintln!("Execution limit reached after {} cycles", cpu.cycles);
+        1
+    };
+    // flushes what the program wrote, process::exit wouldn't
+    drop(cpu);
+    status
+}
+
+fn run_search(args: &SearchArgs) -> i32 {
+    let mut cpu = CPU::new(Bus::default());
+    cpu.stop_on_brk = true;
+    let loaded = std::fs::read(&args.rom).and_then(|data| {
+        soak::load(&mut cpu, data).map_err(|(_, detail)| Error::new(ErrorKind::InvalidData, detail))
+    });
+    let outcome = loaded.and_then(|_| {
+        search::search(
+            &mut cpu,
+            args.reach,
+            args.keys.as_bytes(),
+            args.seed,
+            args.frames,
+            args.states,
+        )
+    });
+
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("IOERROR: {}", e);
+            return 1;
+        }
+    };
+    print!("{}", search::report(args.reach, &outcome));
+
+    let Some(found) = &outcome.found else {
+        return 1;
+    };
+    if let Some(path) = &args.script {
+        // an absolute ROM path holds wherever the script is written
+        let rom = args.rom.canonicalize().unwrap_or_else(|_| args.rom.clone());
+        let text = search::script(found, args.reach, &rom.to_string_lossy(), args.seed);
+        if let Err(e) = std::fs::write(path, text) {
+            println!("IOERROR: {}", e);
+            return 1;
+        }
+    }
+    0
 }
 
 fn main() {
     // let args: Vec<String> = env::args().collect();
     let args = EmuArgs::parse();
 
-    let path = &args.file_name;
+    match &args.command {
+        Some(Command::Test(test_args)) => process::exit(run_tests(test_args)),
+        Some(Command::Soak(soak_args)) => process::exit(run_soak(soak_args)),
+        Some(Command::Diff(diff_args)) => process::exit(run_diff(diff_args)),
+        Some(Command::Search(search_args)) => process::exit(run_search(search_args)),
+        Some(Command::Run(run_args)) => process::exit(run_pipe(run_args)),
+        _ => (),
+    }
+
+    let demo = match &args.command {
+        Some(Command::Demo(DemoArgs { name: Some(name) })) => match demo::find(name) {
+            Some(d) => Some(d),
+            None => {
+                println!("No demo called {}", name);
+                demo::print_list();
+                process::exit(1);
+            }
+        },
+        Some(Command::Demo(_)) => {
+            demo::print_list();
+            process::exit(0);
+        }
+        _ => None,
+    };
+
+    let mut path = match demo {
+        Some(d) => d.name.to_string(),
+        None => args.file_name.clone().unwrap(),
+    };
+    if demo.is_some() && (args.watch || args.jukebox.is_some()) {
+        println!("--watch and --jukebox need files, not a built-in demo");
+    }
+    let mut jukebox = args
+        .jukebox
+        .filter(|_| demo.is_none())
+        .map(|secs| Jukebox::new(&path, Duration::from_secs(secs)));
+    if let Some(j) = jukebox.as_mut() {
+        match j.advance() {
+            Some(first) => path = first,
+            None => {
+                println!("IOERROR: no programs in {}", path);
+                process::exit(1);
+            }
+        }
+    }
+    let path = &path;
 
     println!("Initialising CPU");
-    let mut c = CPU::new(Bus { memory: [0; 65535] });
+    let mut c = CPU::new(Bus::default());
+    let rotation = (args.trace_rotate > 0).then_some(trace::Rotation {
+        max_bytes: args.trace_rotate << 20,
+        keep: args.trace_keep,
+    });
+    match args.trace.open(0, rotation) {
+        Ok(trace) => c.trace = trace,
+        Err(e) => {
+            println!("IOERROR: trace: {}", e);
+            process::exit(1);
+        }
+    }
+    c.bus.power_on(args.ram_init);
+    if let Some(dog) = args.watchdog {
+        let page = (dog.addr >> 8) as u8;
+        c.bus
+            .map(page..=page, Box::new(watchdog::Watchdog::new(dog.cycles)));
+    }
+    for &region in &args.guard {
+        c.bus.guard(region);
+    }
+    for &region in &args.no_exec {
+        c.bus.guard(guard::Region {
+            kind: guard::Kind::NoExecute,
+            ..region
+        });
+    }
+    c.magic = args.magic;
+    c.variant = args.cpu;
+    c.strict = args.strict;
+    c.stop_on_brk = args.brk == Brk::Stop;
+    c.limits = Limits {
+        max_cycles: args.max_cycles,
+        max_instructions: args.max_instructions,
+    };
     // let path = "roms/snake.nes";
-    match c.load_rom_file(path) {
-        Ok(()) => println!("Loaded {}", path),
-        _ => {
-            println!("IOERROR: File not found");
+    let loaded = match demo {
+        Some(d) => {
+            d.load(&mut c);
+            Ok(d.image.len())
+        }
+        None => load_program(&mut c, path, args.assembler.as_deref()),
+    };
+    let size = match loaded {
+        Ok(size) => {
+            println!("Loaded {}", path);
+            size
+        }
+        Err(e) => {
+            println!("IOERROR: {}", e);
+            process::exit(1);
+        }
+    };
+    if demo.is_none() {
+        compat_warning(&args.compat_db, path);
+    }
+
+    // a demo's cheats last for the run, there is no file to keep them in
+    let cheat_list = match demo {
+        Some(_) => args.cheat.iter().map(|c| cheats::parse_code(c)).collect(),
+        None => load_cheats(path, &args.cheat),
+    };
+    let cheat_list = match cheat_list {
+        Ok(list) => list,
+        Err(e) => {
+            println!("IOERROR: {}", e);
             process::exit(1);
         }
     };
 
+    if args.headless {
+        let mut before = args.explain.then(|| explain::capture(&mut c));
+        let mut coverage = args.export_asm.is_some().then(export::Coverage::default);
+        let mut taint = args.taint.map(|r| taint::Taint::new(r.start..=r.end));
+        if let Some(t) = taint.as_mut() {
+            t.capture(&mut c);
+        }
+        let mut pc = c.pc;
+        let mut patches = cheats::Patches::default();
+        let reason = c.run(|cpu| {
+            if let Some(t) = taint.as_mut() {
+                t.update(cpu);
+            }
+            if let Some(b) = before.as_ref() {
+                for line in explain::explain(b, cpu) {
+                    println!("{}", line);
+                }
+            }
+            if let Some(cov) = coverage.as_mut() {
+                cov.mark(pc);
+            }
+            patches.apply(&cheat_list, cpu);
+            if let Some(b) = before.as_mut() {
+                *b = explain::capture(cpu);
+            }
+            if let Some(t) = taint.as_mut() {
+                t.capture(cpu);
+            }
+            pc = cpu.pc;
+        });
+
+        report_trace_error(&mut c);
+        if let Some(t) = &taint {
+            print!("{}", t.report());
+        }
+
+        if let (Some(out), Some(cov)) = (&args.export_asm, &coverage) {
+            let end = (export::ORIGIN as usize + size).min(0xFFFF) as u16;
+            if let Err(e) = std::fs::write(out, export::source(&c, cov, end)) {
+                println!("IOERROR: {}", e);
+                process::exit(1);
+            }
+        }
+        process::exit(exit_status(&c, reason, args.status));
+    }
+
+    let renderer: Option<terminal::Renderer> = match args.video {
+        Video::Sdl | Video::Vnc => None,
+        Video::Ansi => Some(terminal::render_ansi),
+        Video::Braille => Some(terminal::render_braille),
+        Video::Sixel => Some(terminal::render_sixel),
+    };
+
+    let run = match renderer {
+        Some(render) => Some(terminal::run(&mut c, &cheat_list, render)),
+        None if args.video == Video::Vnc => {
+            Some(vnc::run(&mut c, &cheat_list, &args.vnc_addr, args.filter))
+        }
+        None => None,
+    };
+
+    if let Some(run) = run {
+        report_trace_error(&mut c);
+        match run {
+            Ok(ExitReason::Halted) => return,
+            Ok(reason) => process::exit(exit_status(&c, reason, args.status)),
+            Err(e) => {
+                println!("IOERROR: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
     println!("Initialising SDL2");
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("6502emu", (32.0 * 10.0) as u32, (32.0 * 10.0) as u32)
+    let mut display: Box<dyn Screen> = match (args.text, args.charrom.as_deref()) {
+        (Some(text), Some(rom)) => match TextDisplay::from_file(text, rom, PixelFormat::Rgb24) {
+            Ok(d) => Box::new(d),
+            Err(e) => {
+                println!("IOERROR: {}", e);
+                process::exit(1);
+            }
+        },
+        _ => Box::new(Display::with_layout(
+            args.bitmap.unwrap_or(Layout::EASY6502),
+            PixelFormat::Rgb24,
+        )),
+    };
+    let (width, height) = display.dimensions();
+    let mut window = video_subsystem
+        .window("6502emu", width as u32, height as u32)
         .position_centered()
+        .allow_highdpi()
+        .hidden()
         .build()
         .unwrap();
+    // sized once it is open, for whichever display it opened on
+    let zoom = args
+        .zoom
+        .unwrap_or_else(|| window_zoom(&window, width, height));
+    window
+        .set_size((width * zoom) as u32, (height * zoom) as u32)
+        .unwrap();
+    window.set_position(WindowPos::Centered, WindowPos::Centered);
+    window.show();
 
     let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    // on high-DPI displays the drawable has more pixels than the window has
+    // points; scale by whole pixels so the image stays crisp
+    let (out_w, out_h) = canvas.output_size().unwrap();
+    let zoom = (out_w as usize / width).min(out_h as usize / height).max(1);
     let mut event_pump = sdl_context.event_pump().unwrap();
-    canvas.set_scale(10.0, 10.0).unwrap();
+    let filter = args.filter;
+    let scale = if filter == Filter::None {
+        1
+    } else {
+        filter::SCALE
+    };
+    canvas
+        .set_scale(zoom as f32 / scale as f32, zoom as f32 / scale as f32)
+        .unwrap();
 
     let creator = canvas.texture_creator();
     let mut texture = creator
-        .create_texture_target(PixelFormatEnum::RGB24, 32, 32)
+        .create_texture_target(
+            PixelFormatEnum::RGB24,
+            (width * scale) as u32,
+            (height * scale) as u32,
+        )
         .unwrap();
 
-    let mut screen_state = [0_u8; 32 * 3 * 32];
+    let mut filtered = Vec::new();
+    let mut recolored = Vec::new();
+    let mut flash_limiter = args.flash_limit.map(filter::FlashLimiter::new);
     let mut rng = rand::thread_rng();
 
     let mut key_queue = Queue::default();
+    let state_path = demo.is_none().then(|| format!("{}.state", path));
+    let state_path = state_path.as_deref();
+    let mut watch = (args.watch && demo.is_none()).then(|| Watch::new(path));
+    let mut ui = UiState {
+        cheats_on: true,
+        overlay: false,
+        focused: true,
+        touch: args.touch.then_some(width as f32 / height as f32),
+    };
+
+    let profiler = args
+        .profile
+        .as_ref()
+        .map(std::fs::File::create)
+        .transpose()
+        .and_then(Profiler::new);
+    let mut profiler = match profiler {
+        Ok(p) => p,
+        Err(e) => {
+            println!("IOERROR: {}", e);
+            process::exit(1);
+        }
+    };
+    let mut patches = cheats::Patches::default();
+    let mut shown = None;
 
     println!("Running main loop");
-    c.run(move |cpu| {
-        update_input(&mut key_queue, &mut event_pump);
-        handle_user_input(cpu, &mut key_queue);
-        cpu.bus.write(0xfe, rng.gen_range(1, 16));
+    let reason = c.run(move |cpu| {
+        profiler.lap(Phase::Cpu);
+        report_trace_error(cpu);
+
+        if let Some(w) = watch.as_mut() {
+            // a stopped program keeps the window open until the next edit
+            if cpu.is_jammed() {
+                println!("CPU jammed at ${:04X}, waiting for an edit", cpu.pc);
+            }
+            loop {
+                if w.changed() {
+                    reload(cpu, &w.path, args.assembler.as_deref());
+                    break;
+                }
+                if !cpu.halted && !cpu.is_jammed() {
+                    break;
+                }
+                update_input(cpu, &mut key_queue, &mut event_pump, state_path, &mut ui);
+                ::std::thread::sleep(Duration::from_millis(50));
+            }
+        }
 
-        if read_screen_state(cpu, &mut screen_state) {
-            texture.update(None, &screen_state, 32 * 3).unwrap();
-            canvas.copy(&texture, None, None).unwrap();
-            canvas.present();
+        if let Some(j) = jukebox.as_mut() {
+            if cpu.halted || cpu.is_jammed() || j.expired() {
+                if let Some(next) = j.advance() {
+                    reload(cpu, &next, args.assembler.as_deref());
+                }
+            }
+            if let Some(k) = j.key() {
+                key_queue.push(k);
+            }
         }
 
-        ::std::thread::sleep(std::time::Duration::new(0, 70_000));
-    })
-}
+        update_input(cpu, &mut key_queue, &mut event_pump, state_path, &mut ui);
+        while !ui.focused && args.background == Background::Pause {
+            ::std::thread::sleep(Duration::from_millis(50));
+            update_input(cpu, &mut key_queue, &mut event_pump, state_path, &mut ui);
+        }
+        handle_user_input(cpu, &mut key_queue);
+        cpu.bus.write(0xfe, rng.gen_range(1, 16));
+        if ui.cheats_on {
+            patches.apply(&cheat_list, cpu);
+        } else {
+            patches.restore(cpu);
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        profiler.lap(Phase::Input);
 
-    #[test]
-    fn eztest() {
-        let mut c = CPU::new(Bus { memory: [0; 65535] });
-        // let mut rng = rand::thread_rng();
-
-        let ezcode = vec![
-            0xa9, 0x10, // LDA #$10     -> A = #$10
-            0x85, 0x20, // STA $20      -> $20 = #$10
-            0xa9, 0x01, // LDA #$1      -> A = #$1
-            0x65, 0x20, // ADC $20      -> A = #$11
-            0x85, 0x21, // STA $21      -> $21=#$11
-            0xe6, 0x21, // INC $21      -> $21=#$12
-            0xa4, 0x21, // LDY $21      -> Y=#$12
-            0xc8, // INY          -> Y=#$13
-            0x00, // BRK
-        ];
+        // read once a frame of cycles, and when the program stops
+        let frame = cpu.cycles / soak::CYCLES_PER_FRAME;
+        if shown != Some(frame) || cpu.halted || cpu.is_jammed() {
+            shown = Some(frame);
+            display.update(cpu);
+        }
+        if display.take_dirty() {
+            let mut pixels = display.pixels();
+            if args.palette != Palette::Normal || flash_limiter.is_some() {
+                recolored.clear();
+                recolored.extend_from_slice(pixels);
+                filter::recolor(args.palette, &mut recolored);
+                if let Some(limiter) = flash_limiter.as_mut() {
+                    limiter.apply(&mut recolored, Instant::now());
+                }
+                pixels = &recolored;
+            }
 
-        c.load(ezcode);
-        c.run(move |_cpu| {});
-        assert_eq!(c.bus.read(0x20), 0x10);
-        assert_eq!(c.bus.read(0x21), 0x12);
-        assert_eq!(c.reg.a, 0x11);
-        assert_eq!(c.reg.y, 0x13);
-    }
+            if filter == Filter::None {
+                texture.update(None, pixels, width * 3).unwrap();
+            } else {
+                filter::apply(filter, pixels, width, height, &mut filtered);
+                texture.update(None, &filtered, width * scale * 3).unwrap();
+            }
+            canvas.copy(&texture, None, None).unwrap();
+            if ui.overlay {
+                draw_profile(&mut canvas, &profiler);
+            }
+            if let Some(aspect) = ui.touch {
+                draw_touch(&mut canvas, aspect);
+            }
+            canvas.present();
 
-    fn run_testrom(romname: &str) {
-        let mut c = CPU::new(Bus { memory: [0; 65535] });
-        let mut file = String::from("./test_roms/");
-        file.push_str(romname);
+            let title = format!("6502emu [{:016x}]", cpu.state_hash());
+            canvas.window_mut().set_title(&title).unwrap();
 
-        match c.load_rom_file(&file) {
-            Ok(()) => (),
-            Err(_) => {
-                panic!("IOERROR: File not found");
+            profiler.lap(Phase::Render);
+            if let Err(e) = profiler.end_frame() {
+                println!("Could not write profile: {}", e);
             }
         }
+        profiler.lap(Phase::Render);
 
-        c.run(move |_cpu| {});
-        assert_eq!(c.bus.read(0x6000), 0)
-    }
-
-    #[test]
-    fn implied() {
-        run_testrom("01-implied.nes");
-    }
+        if !ui.focused && args.background == Background::Throttle {
+            ::std::thread::sleep(Duration::from_millis(1));
+        } else {
+            ::std::thread::sleep(std::time::Duration::new(0, 70_000));
+        }
+        profiler.lap(Phase::Sleep);
+    });
 
-    #[test]
-    fn immediate() {
-        run_testrom("02-immediate.nes");
+    if reason != ExitReason::Halted {
+        process::exit(exit_status(&c, reason, args.status));
     }
+}
 
-    #[test]
-    fn zero_page() {
-        run_testrom("03-zero_page.nes");
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
-    fn zp_xy() {
-        run_testrom("04-zp_xy.nes");
+    fn status_from_program() {
+        let mut c = CPU::new(Bus::default());
+        c.stop_on_brk = true;
+        c.load(vec![
+            0xa9, 0x07, // LDA #$07
+            0x8d, 0x00, 0x60, // STA $6000
+            0xa9, 0x03, // LDA #$03
+            0x00, // BRK
+        ]);
+        let reason = c.run(|_| {});
+
+        assert_eq!("$6000".parse(), Ok(StatusSource::Address(0x6000)));
+        assert_eq!("A".parse(), Ok(StatusSource::A));
+        assert!("$zz".parse::<StatusSource>().is_err());
+        assert_eq!(exit_status(&c, reason, StatusSource::Address(0x6000)), 7);
+        assert_eq!(exit_status(&c, reason, StatusSource::A), 3);
+        assert_eq!(
+            exit_status(&c, ExitReason::CycleLimit, StatusSource::A),
+            124
+        );
+        assert_eq!(exit_status(&c, ExitReason::Jammed, StatusSource::A), 126);
+        assert_eq!(
+            exit_status(&c, ExitReason::Undocumented, StatusSource::A),
+            123
+        );
     }
 }
@@ -0,0 +1,139 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::savestate;
+
+/// Bytes of each side shown per changed range before eliding the rest.
+const SHOWN: usize = 8;
+
+/// A run of consecutive bytes that differ between two images.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub start: u16,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// Memory held by a save state, or a raw dump as it is.
+pub fn memory(data: &[u8]) -> io::Result<Vec<u8>> {
+    if savestate::is_state(data) {
+        let mut cpu = CPU::new(Bus::default());
+        savestate::load(&mut cpu, data)?;
+        Ok(cpu.bus.memory.dump())
+    } else if data.len() <= 0x10000 {
+        Ok(data.to_vec())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            "memory dump is bigger than 64K",
+        ))
+    }
+}
+
+pub fn read_memory(path: &Path) -> io::Result<Vec<u8>> {
+    memory(&fs::read(path)?)
+}
+
+pub fn diff(before: &[u8], after: &[u8]) -> io::Result<Vec<Change>> {
+    if before.len() != after.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "images differ in size ({} and {} bytes)",
+                before.len(),
+                after.len()
+            ),
+        ));
+    }
+
+    let mut changes: Vec<Change> = Vec::new();
+    for (adr, (&b, &a)) in before.iter().zip(after).enumerate() {
+        if a == b {
+            continue;
+        }
+        match changes.last_mut() {
+            Some(c) if c.start as usize + c.before.len() == adr => {
+                c.before.push(b);
+                c.after.push(a);
+            }
+            _ => changes.push(Change {
+                start: adr as u16,
+                before: vec![b],
+                after: vec![a],
+            }),
+        }
+    }
+    Ok(changes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s: Vec<String> = bytes
+        .iter()
+        .take(SHOWN)
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    if bytes.len() > SHOWN {
+        s.push("..".to_string());
+    }
+    s.join(" ")
+}
+
+pub fn report(changes: &[Change]) -> String {
+    if changes.is_empty() {
+        return "No changes\n".to_string();
+    }
+
+    let mut out = String::new();
+    for c in changes {
+        let end = c.start as usize + c.before.len() - 1;
+        let range = if c.before.len() == 1 {
+            format!("${:04X}", c.start)
+        } else {
+            format!("${:04X}-${:04X}", c.start, end)
+        };
+        out += &format!("{:12} {} -> {}\n", range, hex(&c.before), hex(&c.after));
+    }
+    let bytes: usize = changes.iter().map(|c| c.before.len()).sum();
+    out += &format!("{} bytes changed in {} ranges\n", bytes, changes.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memdiff::*;
+
+    #[test]
+    fn ranges_and_states() {
+        let before = vec![0; 0x20];
+        let mut after = before.clone();
+        after[0x03] = 1;
+        after[0x10..0x1b].copy_from_slice(&[9; 11]);
+
+        let changes = diff(&before, &after).unwrap();
+        assert_eq!(
+            changes[0],
+            Change {
+                start: 0x03,
+                before: vec![0],
+                after: vec![1],
+            }
+        );
+        assert_eq!(
+            report(&changes),
+            "$0003        00 -> 01\n\
+             $0010-$001A  00 00 00 00 00 00 00 00 .. -> 09 09 09 09 09 09 09 09 ..\n\
+             12 bytes changed in 2 ranges\n"
+        );
+        assert!(diff(&before, &after[1..]).is_err());
+
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.write(0x0042, 3);
+        let state = savestate::save(&cpu).unwrap();
+        let mem = memory(&state).unwrap();
+        assert_eq!((mem.len(), mem[0x42]), (0x10000, 3));
+        assert_eq!(report(&diff(&mem, &mem).unwrap()), "No changes\n");
+    }
+}
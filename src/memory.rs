@@ -0,0 +1,270 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+type Page = Arc<[u8; 0x100]>;
+
+/// A frozen copy of memory, held as 256 shared pages.
+#[derive(Clone)]
+pub struct Snapshot {
+    pages: Vec<Page>,
+}
+
+impl Snapshot {
+    pub fn read(&self, adr: u16) -> u8 {
+        self.pages[(adr >> 8) as usize][(adr & 0xFF) as usize]
+    }
+}
+
+/// Backing store behind the Bus. Implementations decide how the 64K address
+/// space is held (a flat array, a snapshot store, ...), the Bus only needs
+/// byte reads and writes.
+pub trait Memory: Send {
+    fn read(&self, adr: u16) -> u8;
+
+    fn write(&mut self, adr: u16, data: u8);
+
+    /// Copy `data` in starting at `start`, wrapping at the top of memory.
+    fn load(&mut self, start: u16, data: &[u8]) {
+        for (i, &b) in data.iter().enumerate() {
+            self.write(start.wrapping_add(i as u16), b);
+        }
+    }
+
+    /// Fill `out` from memory starting at `start`, wrapping at the top.
+    fn read_into(&self, start: u16, out: &mut [u8]) {
+        for (i, b) in out.iter_mut().enumerate() {
+            *b = self.read(start.wrapping_add(i as u16));
+        }
+    }
+
+    /// The full 64K address space as a flat buffer.
+    fn dump(&self) -> Vec<u8> {
+        (0..=0xFFFF).map(|adr| self.read(adr)).collect()
+    }
+
+    /// Freeze the current contents. The default copies every page.
+    fn snapshot(&self) -> Snapshot {
+        let dump = self.dump();
+        Snapshot {
+            pages: dump
+                .chunks(0x100)
+                .map(|p| Arc::new(p.try_into().unwrap()))
+                .collect(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        for (i, page) in snapshot.pages.iter().enumerate() {
+            self.load((i as u16) << 8, &page[..]);
+        }
+    }
+}
+
+/// Plain linear RAM covering the whole address space.
+pub struct Ram {
+    data: Box<[u8; 0x10000]>,
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Ram {
+            data: Box::new([0; 0x10000]),
+        }
+    }
+}
+
+// A u16 index into a 64K array can never be out of bounds, so these have no
+// bounds check. The bus holds a Ram by value, see `Backing`, so they inline
+// into its accesses; benches/bus.rs measures what an access costs.
+impl Memory for Ram {
+    #[inline]
+    fn read(&self, adr: u16) -> u8 {
+        self.data[adr as usize]
+    }
+
+    #[inline]
+    fn write(&mut self, adr: u16, data: u8) {
+        self.data[adr as usize] = data
+    }
+
+    // a run up to the top of memory at a time, then on from $0000
+    fn load(&mut self, start: u16, data: &[u8]) {
+        let mut at = start as usize;
+        let mut rest = data;
+        while !rest.is_empty() {
+            let n = rest.len().min(self.data.len() - at);
+            self.data[at..at + n].copy_from_slice(&rest[..n]);
+            rest = &rest[n..];
+            at = 0;
+        }
+    }
+
+    fn read_into(&self, start: u16, out: &mut [u8]) {
+        let mut at = start as usize;
+        let mut rest = out;
+        while !rest.is_empty() {
+            let n = rest.len().min(self.data.len() - at);
+            let (head, tail) = rest.split_at_mut(n);
+            head.copy_from_slice(&self.data[at..at + n]);
+            rest = tail;
+            at = 0;
+        }
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+}
+
+/// What the Bus keeps memory in. Plain `Ram` is held by value, so the bus
+/// can index it directly; any other `Memory` is reached through a trait
+/// object. Both deref to `dyn Memory` for everyone else.
+pub enum Backing {
+    Ram(Ram),
+    Other(Box<dyn Memory>),
+}
+
+// Inherent, so they are found before the deref and a Ram access inlines.
+impl Backing {
+    #[inline]
+    pub fn read(&self, adr: u16) -> u8 {
+        match self {
+            Backing::Ram(ram) => ram.read(adr),
+            Backing::Other(memory) => memory.read(adr),
+        }
+    }
+
+    #[inline]
+    pub fn write(&mut self, adr: u16, data: u8) {
+        match self {
+            Backing::Ram(ram) => ram.write(adr, data),
+            Backing::Other(memory) => memory.write(adr, data),
+        }
+    }
+}
+
+impl Deref for Backing {
+    type Target = dyn Memory;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Backing::Ram(ram) => ram,
+            Backing::Other(memory) => memory.as_ref(),
+        }
+    }
+}
+
+impl DerefMut for Backing {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Backing::Ram(ram) => ram,
+            Backing::Other(memory) => memory.as_mut(),
+        }
+    }
+}
+
+/// RAM split into 256-byte copy-on-write pages. Taking a snapshot only bumps
+/// reference counts, and a page is copied the first time it is written while
+/// a snapshot still holds it, so frequent snapshots cost only the pages that
+/// actually changed.
+pub struct CowRam {
+    pages: Vec<Page>,
+}
+
+impl Default for CowRam {
+    fn default() -> Self {
+        CowRam {
+            pages: (0..0x100).map(|_| Arc::new([0; 0x100])).collect(),
+        }
+    }
+}
+
+impl Memory for CowRam {
+    #[inline]
+    fn read(&self, adr: u16) -> u8 {
+        self.pages[(adr >> 8) as usize][(adr & 0xFF) as usize]
+    }
+
+    fn write(&mut self, adr: u16, data: u8) {
+        Arc::make_mut(&mut self.pages[(adr >> 8) as usize])[(adr & 0xFF) as usize] = data
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pages: self.pages.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.pages.clone_from(&snapshot.pages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::*;
+
+    #[test]
+    fn ram_covers_address_space() {
+        let mut ram = Ram::default();
+        ram.write(0xFFFF, 0x12);
+        ram.load(0xFFFE, &[0x34, 0x56]);
+
+        assert_eq!(ram.read(0xFFFF), 0x56);
+        assert_eq!(ram.dump().len(), 0x10000);
+
+        let mut wrapped = [0; 3];
+        ram.read_into(0xFFFE, &mut wrapped);
+        assert_eq!(wrapped, [0x34, 0x56, 0]);
+    }
+
+    #[test]
+    fn both_wrap_at_the_top() {
+        let memories: [Box<dyn Memory>; 2] =
+            [Box::new(Ram::default()), Box::new(CowRam::default())];
+        for mut memory in memories {
+            memory.load(0xFFFF, &[1, 2, 3]);
+            assert_eq!([0xFFFF, 0x0000, 0x0001].map(|a| memory.read(a)), [1, 2, 3]);
+
+            let mut wrapped = [0; 3];
+            memory.read_into(0xFFFF, &mut wrapped);
+            assert_eq!(wrapped, [1, 2, 3]);
+
+            // past a full lap, the way the default methods go
+            let mut lap = vec![0; 0x10002];
+            memory.read_into(0xFFFF, &mut lap);
+            assert_eq!(lap[..3], [1, 2, 3]);
+            assert_eq!(lap[0x10000..], [1, 2]);
+        }
+    }
+
+    #[test]
+    fn cow_snapshots_share_untouched_pages() {
+        let mut ram = CowRam::default();
+        ram.write(0x0200, 1);
+
+        let snap = ram.snapshot();
+        ram.write(0x0200, 2);
+
+        assert_eq!(snap.read(0x0200), 1);
+        assert_eq!(ram.read(0x0200), 2);
+        assert!(Arc::ptr_eq(&snap.pages[0], &ram.pages[0]));
+        assert!(!Arc::ptr_eq(&snap.pages[2], &ram.pages[2]));
+
+        ram.restore(&snap);
+        assert_eq!(ram.read(0x0200), 1);
+    }
+
+    #[test]
+    fn default_snapshot_copies() {
+        let mut ram = Ram::default();
+        ram.write(0x1234, 5);
+
+        let snap = ram.snapshot();
+        ram.write(0x1234, 6);
+        assert_eq!(snap.read(0x1234), 5);
+
+        ram.restore(&snap);
+        assert_eq!(ram.read(0x1234), 5);
+    }
+}
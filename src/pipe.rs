@@ -0,0 +1,117 @@
+use std::io::{Read, Write};
+
+use crate::bus::Device;
+
+/// Where the `run` subcommand maps the ports unless told otherwise.
+pub const DEFAULT_PAGE: u8 = 0xD0;
+
+/// Offsets of the ports within their page.
+pub const DATA: u16 = 0x00;
+pub const STATUS: u16 = 0x01;
+
+/// STATUS bit set once the input has run out.
+pub const EOF: u8 = 0x80;
+
+/// Byte-stream ports, so a program can be used as a filter in a shell
+/// pipeline. Reading DATA takes the next input byte, 0 once it has run out;
+/// writing DATA sends a byte to the output. STATUS reads $80 at the end of
+/// the input and 0 while there is more, so `BIT STATUS` / `BMI` loops until
+/// EOF. Finding out may wait for input, and the output is flushed first so
+/// an interactive program's prompt shows.
+pub struct Pipe<R, W> {
+    input: R,
+    output: W,
+    next: Option<u8>,
+    eof: bool,
+    /// Set when the reader has gone away, after which output is dropped
+    closed: bool,
+}
+
+impl<R: Read, W: Write> Pipe<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Pipe {
+            input,
+            output,
+            next: None,
+            eof: false,
+            closed: false,
+        }
+    }
+
+    fn fill(&mut self) {
+        if self.next.is_some() || self.eof {
+            return;
+        }
+        if self.output.flush().is_err() {
+            self.closed = true;
+        }
+        let mut b = [0];
+        match self.input.read_exact(&mut b) {
+            Ok(()) => self.next = Some(b[0]),
+            Err(_) => self.eof = true,
+        }
+    }
+}
+
+impl<R: Read + Send, W: Write + Send> Device for Pipe<R, W> {
+    fn read(&mut self, adr: u16) -> u8 {
+        self.fill();
+        match adr & 0xFF {
+            DATA => self.next.take().unwrap_or(0),
+            STATUS if self.eof => EOF,
+            _ => 0,
+        }
+    }
+
+    /// Only what has been read ahead already; finding out more would wait.
+    fn peek(&self, adr: u16) -> u8 {
+        match adr & 0xFF {
+            DATA => self.next.unwrap_or(0),
+            STATUS if self.eof => EOF,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, adr: u16, data: u8) {
+        if adr & 0xFF == DATA && !self.closed && self.output.write_all(&[data]).is_err() {
+            self.closed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::CPU;
+    use crate::pipe::*;
+    use crate::trace::Buffer;
+
+    #[test]
+    fn filters_input_to_output() {
+        let output = Buffer::default();
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.map(
+            DEFAULT_PAGE..=DEFAULT_PAGE,
+            Box::new(Pipe::new(&b"Hello, 6502\n"[..], output.clone())),
+        );
+        cpu.stop_on_brk = true;
+        cpu.load(vec![
+            0x2c, 0x01, 0xd0, // $0600 BIT $D001
+            0x30, 0x13, // $0603 BMI $0618 at EOF
+            0xad, 0x00, 0xd0, // $0605 LDA $D000
+            0xc9, 0x61, // $0608 CMP #'a'
+            0x90, 0x06, // $060A BCC $0612
+            0xc9, 0x7b, // $060C CMP #'z'+1
+            0xb0, 0x02, // $060E BCS $0612
+            0x29, 0xdf, // $0610 AND #$DF
+            0x8d, 0x00, 0xd0, // $0612 STA $D000
+            0x4c, 0x00, 0x06, // $0615 JMP $0600
+            0x00, // $0618 BRK
+        ]);
+        cpu.run(|_| {});
+
+        assert_eq!(output.contents(), "HELLO, 6502\n");
+        assert_eq!(cpu.pc, 0x0619);
+        assert_eq!(cpu.bus.read(0xd000), 0);
+    }
+}
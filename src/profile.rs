@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+/// Frames kept for the overlay graph.
+pub const HISTORY: usize = 120;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Cpu,
+    Input,
+    Render,
+    Sleep,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [Phase::Cpu, Phase::Input, Phase::Render, Phase::Sleep];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Cpu => "cpu",
+            Phase::Input => "input",
+            Phase::Render => "render",
+            Phase::Sleep => "sleep",
+        }
+    }
+
+    /// Colour of the phase in the overlay graph.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Phase::Cpu => (0xE0, 0x40, 0x40),
+            Phase::Input => (0x40, 0xC0, 0x40),
+            Phase::Render => (0x40, 0x80, 0xF0),
+            Phase::Sleep => (0x60, 0x60, 0x60),
+        }
+    }
+}
+
+/// Splits wall-clock time between the phases of the front-end loop, frame by
+/// frame. The loop calls `lap` after each piece of work with the phase that
+/// work belonged to, and `end_frame` whenever it presents.
+///
+/// Frames can be streamed to a chrome://tracing file. Each frame becomes a
+/// "frame" span with one child span per phase holding that phase's total for
+/// the frame; the phases interleave far too finely to record individually.
+/// The JSON array is never closed, which the trace viewers accept, so the file
+/// stays valid however the process exits.
+pub struct Profiler {
+    epoch: Instant,
+    mark: Instant,
+    frame_start: Instant,
+    current: [Duration; 4],
+    pub history: VecDeque<[Duration; 4]>,
+    trace: Option<BufWriter<File>>,
+}
+
+impl Profiler {
+    pub fn new(trace: Option<File>) -> io::Result<Profiler> {
+        let now = Instant::now();
+        let mut trace = trace.map(BufWriter::new);
+        if let Some(t) = trace.as_mut() {
+            t.write_all(b"[\n")?;
+        }
+
+        Ok(Profiler {
+            epoch: now,
+            mark: now,
+            frame_start: now,
+            current: [Duration::ZERO; 4],
+            history: VecDeque::with_capacity(HISTORY),
+            trace,
+        })
+    }
+
+    /// Charges the time since the last lap to `phase`.
+    pub fn lap(&mut self, phase: Phase) {
+        self.lap_at(phase, Instant::now());
+    }
+
+    fn lap_at(&mut self, phase: Phase, now: Instant) {
+        self.current[phase as usize] += now - self.mark;
+        self.mark = now;
+    }
+
+    pub fn end_frame(&mut self) -> io::Result<()> {
+        let frame = std::mem::replace(&mut self.current, [Duration::ZERO; 4]);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame);
+
+        let start = self.frame_start;
+        self.frame_start = self.mark;
+        match self.trace.as_mut() {
+            Some(t) => {
+                t.write_all(trace_events(start - self.epoch, &frame).as_bytes())?;
+                t.flush()
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn event(name: &str, ts: Duration, dur: Duration) -> String {
+    format!(
+        "{{\"name\": \"{}\", \"ph\": \"X\", \"pid\": 1, \"tid\": 1, \"ts\": {}, \"dur\": {}}},\n",
+        name,
+        ts.as_micros(),
+        dur.as_micros()
+    )
+}
+
+fn trace_events(start: Duration, frame: &[Duration; 4]) -> String {
+    let total = frame.iter().sum();
+    let mut out = event("frame", start, total);
+
+    let mut ts = start;
+    for phase in Phase::ALL {
+        let dur = frame[phase as usize];
+        out += &event(phase.name(), ts, dur);
+        ts += dur;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profile::*;
+
+    #[test]
+    fn laps_and_history() {
+        let mut p = Profiler::new(None).unwrap();
+        let t0 = p.mark;
+        p.lap_at(Phase::Cpu, t0 + Duration::from_millis(3));
+        p.lap_at(Phase::Render, t0 + Duration::from_millis(4));
+        p.lap_at(Phase::Cpu, t0 + Duration::from_millis(6));
+        p.end_frame().unwrap();
+
+        let ms = Duration::from_millis;
+        assert_eq!(p.history[0], [ms(5), ms(0), ms(1), ms(0)]);
+        assert_eq!(p.current, [Duration::ZERO; 4]);
+    }
+
+    #[test]
+    fn chrome_trace() {
+        let us = Duration::from_micros;
+        let events = trace_events(us(100), &[us(5), us(1), us(2), us(0)]);
+
+        assert_eq!(
+            events.lines().collect::<Vec<_>>(),
+            [
+                "{\"name\": \"frame\", \"ph\": \"X\", \"pid\": 1, \"tid\": 1, \"ts\": 100, \"dur\": 8},",
+                "{\"name\": \"cpu\", \"ph\": \"X\", \"pid\": 1, \"tid\": 1, \"ts\": 100, \"dur\": 5},",
+                "{\"name\": \"input\", \"ph\": \"X\", \"pid\": 1, \"tid\": 1, \"ts\": 105, \"dur\": 1},",
+                "{\"name\": \"render\", \"ph\": \"X\", \"pid\": 1, \"tid\": 1, \"ts\": 106, \"dur\": 2},",
+                "{\"name\": \"sleep\", \"ph\": \"X\", \"pid\": 1, \"tid\": 1, \"ts\": 108, \"dur\": 0},",
+            ]
+        );
+    }
+}
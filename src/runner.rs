@@ -0,0 +1,193 @@
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use crate::cpu::CPU;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Step,
+    Peek(u16),
+    Poke(u16, u8),
+    Stop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Response {
+    Done,
+    Stepped(u16),
+    /// A step asked of a jammed CPU, with the PC it jammed at
+    Jammed(u16),
+    Byte(u8),
+}
+
+/// Owns a CPU on a background thread. Every command gets exactly one
+/// response, so callers can treat `send` as a synchronous request. The CPU
+/// runs until it halts or jams, and then the thread waits for commands.
+pub struct Runner {
+    commands: Sender<Command>,
+    responses: Receiver<Response>,
+    handle: JoinHandle<CPU>,
+}
+
+impl Runner {
+    /// Starts the CPU paused.
+    pub fn spawn(mut cpu: CPU) -> Runner {
+        let (commands, command_rx) = channel();
+        let (response_tx, responses) = channel();
+
+        let handle = thread::spawn(move || {
+            let mut running = false;
+            loop {
+                let cmd = if running && !cpu.halted && !cpu.is_jammed() {
+                    match command_rx.try_recv() {
+                        Ok(cmd) => cmd,
+                        Err(TryRecvError::Empty) => {
+                            cpu.exec();
+                            continue;
+                        }
+                        Err(TryRecvError::Disconnected) => return cpu,
+                    }
+                } else {
+                    match command_rx.recv() {
+                        Ok(cmd) => cmd,
+                        Err(_) => return cpu,
+                    }
+                };
+
+                let response = match cmd {
+                    Command::Pause => {
+                        running = false;
+                        Response::Done
+                    }
+                    Command::Resume => {
+                        running = true;
+                        Response::Done
+                    }
+                    Command::Step if cpu.is_jammed() => Response::Jammed(cpu.pc),
+                    Command::Step => {
+                        if !cpu.halted {
+                            cpu.exec();
+                        }
+                        Response::Stepped(cpu.pc)
+                    }
+                    Command::Peek(adr) => Response::Byte(cpu.bus.peek(adr)),
+                    Command::Poke(adr, data) => {
+                        cpu.bus.write(adr, data);
+                        Response::Done
+                    }
+                    Command::Stop => return cpu,
+                };
+
+                if response_tx.send(response).is_err() {
+                    return cpu;
+                }
+            }
+        });
+
+        Runner {
+            commands,
+            responses,
+            handle,
+        }
+    }
+
+    /// Sends a command and waits for its response.
+    pub fn send(&self, cmd: Command) -> Response {
+        self.commands.send(cmd).expect("Runner thread has stopped");
+        self.responses.recv().expect("Runner thread has stopped")
+    }
+
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.send(Command::Resume);
+    }
+
+    /// Runs one instruction and returns the PC after it, or None once the
+    /// CPU has jammed.
+    pub fn step(&self) -> Option<u16> {
+        match self.send(Command::Step) {
+            Response::Stepped(pc) => Some(pc),
+            Response::Jammed(_) => None,
+            r => panic!("Unexpected response to step: {:?}", r),
+        }
+    }
+
+    pub fn peek(&self, adr: u16) -> u8 {
+        match self.send(Command::Peek(adr)) {
+            Response::Byte(b) => b,
+            r => panic!("Unexpected response to peek: {:?}", r),
+        }
+    }
+
+    pub fn poke(&self, adr: u16, data: u8) {
+        self.send(Command::Poke(adr, data));
+    }
+
+    /// Stops the background thread and hands the CPU back.
+    pub fn stop(self) -> CPU {
+        // the thread exits without replying to Stop
+        let _ = self.commands.send(Command::Stop);
+        self.handle.join().expect("Runner thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::runner::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn core_is_send() {
+        assert_send::<CPU>();
+        assert_send::<Bus>();
+    }
+
+    #[test]
+    fn step_peek_poke() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa5, 0x10, // LDA $10
+            0x85, 0x11, // STA $11
+            0x00, // BRK
+        ]);
+
+        let runner = Runner::spawn(cpu);
+        runner.poke(0x10, 0x42);
+        assert_eq!(runner.step(), Some(0x0602));
+        assert_eq!(runner.peek(0x11), 0x00);
+
+        runner.resume();
+        while runner.peek(0x11) != 0x42 {}
+        runner.pause();
+
+        let cpu = runner.stop();
+        assert_eq!(cpu.bus.memory.read(0x11), 0x42);
+    }
+
+    #[test]
+    fn jam_stops_the_thread() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xe6, 0x10, // INC $10
+            0x02, // JAM
+        ]);
+
+        let runner = Runner::spawn(cpu);
+        runner.resume();
+        while runner.step().is_some() {}
+
+        // a jammed CPU that kept running would burn a cycle an exec
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let cpu = runner.stop();
+        assert!(cpu.is_jammed());
+        assert_eq!(cpu.bus.memory.read(0x10), 1);
+        assert!(cpu.cycles < 20, "{} cycles", cpu.cycles);
+    }
+}
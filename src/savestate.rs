@@ -0,0 +1,291 @@
+use std::io::{self, Error, ErrorKind};
+
+use crate::bus::RamInit;
+use crate::cpu::registers::Flag;
+use crate::cpu::{Variant, CPU};
+
+// Container layout:
+//   magic (8) | version u16 | zstd( chunk* )
+// where each chunk is a 4-byte id, a u32 length and `length` bytes of data.
+// Readers skip chunk ids they don't know and bytes past the end of a chunk
+// they do, so states with more in them still load. The version only goes up
+// when that no longer holds.
+const MAGIC: &[u8; 8] = b"R6502SS\x1a";
+const VERSION: u16 = 1;
+
+const CPU_CHUNK: &[u8; 4] = b"CPU ";
+const RAM_CHUNK: &[u8; 4] = b"RAM ";
+const INIT_CHUNK: &[u8; 4] = b"INIT";
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn put_chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+fn cpu_chunk(cpu: &CPU) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&cpu.pc.to_le_bytes());
+    data.push(cpu.reg.a);
+    data.push(cpu.reg.x);
+    data.push(cpu.reg.y);
+    data.push(cpu.reg.sp);
+    data.push(u8::from(cpu.flags));
+    data.push(cpu.halted as u8);
+    data.push(cpu.jammed as u8);
+    data.push(cpu.nmi_pending as u8 | (cpu.irq_pending as u8) << 1);
+    data.extend_from_slice(&cpu.cycles.to_le_bytes());
+    data.extend_from_slice(&cpu.instructions.to_le_bytes());
+    data.push(match cpu.variant {
+        Variant::Ricoh2A03 => 0,
+        Variant::Nmos => 1,
+    });
+    data
+}
+
+/// The counters and the variant, which older states don't have.
+struct Extra {
+    cycles: u64,
+    instructions: u64,
+    variant: Variant,
+}
+
+/// A CPU chunk read back, to be applied once the whole state has checked out.
+struct CpuState {
+    pc: u16,
+    regs: [u8; 4],
+    flags: u8,
+    halted: bool,
+    jammed: bool,
+    pending: u8,
+    extra: Option<Extra>,
+}
+
+fn read_cpu(data: &[u8]) -> io::Result<CpuState> {
+    if data.len() < 9 {
+        return Err(invalid("truncated CPU chunk"));
+    }
+
+    let extra = match data.get(10..27) {
+        Some(e) => Some(Extra {
+            cycles: u64::from_le_bytes(e[..8].try_into().unwrap()),
+            instructions: u64::from_le_bytes(e[8..16].try_into().unwrap()),
+            variant: match e[16] {
+                0 => Variant::Ricoh2A03,
+                1 => Variant::Nmos,
+                _ => return Err(invalid("unknown CPU variant")),
+            },
+        }),
+        None if data.len() > 10 => return Err(invalid("truncated CPU chunk")),
+        None => None,
+    };
+    Ok(CpuState {
+        pc: u16::from_le_bytes([data[0], data[1]]),
+        regs: [data[2], data[3], data[4], data[5]],
+        flags: data[6],
+        halted: data[7] != 0,
+        jammed: data[8] != 0,
+        // older states have no pending interrupts byte
+        pending: data.get(9).copied().unwrap_or(0),
+        extra,
+    })
+}
+
+fn restore_cpu(cpu: &mut CPU, s: CpuState) {
+    cpu.pc = s.pc;
+    [cpu.reg.a, cpu.reg.x, cpu.reg.y, cpu.reg.sp] = s.regs;
+    cpu.flags = Flag::from(s.flags);
+    cpu.halted = s.halted;
+    cpu.jammed = s.jammed;
+    cpu.nmi_pending = s.pending & 1 != 0;
+    cpu.irq_pending = s.pending & 2 != 0;
+    if let Some(e) = s.extra {
+        cpu.cycles = e.cycles;
+        cpu.instructions = e.instructions;
+        cpu.variant = e.variant;
+    }
+}
+
+// kind byte followed by the fill byte or seed as a u64
+fn init_chunk(init: RamInit) -> Vec<u8> {
+    let (kind, arg) = match init {
+        RamInit::Fill(b) => (0, b as u64),
+        RamInit::Alternating => (1, 0),
+        RamInit::Random(seed) => (2, seed),
+    };
+
+    let mut data = vec![kind];
+    data.extend_from_slice(&arg.to_le_bytes());
+    data
+}
+
+fn restore_init(data: &[u8]) -> io::Result<RamInit> {
+    if data.len() < 9 {
+        return Err(invalid("truncated INIT chunk"));
+    }
+
+    let arg = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    match data[0] {
+        0 => Ok(RamInit::Fill(arg as u8)),
+        1 => Ok(RamInit::Alternating),
+        2 => Ok(RamInit::Random(arg)),
+        _ => Err(invalid("unknown RAM init pattern")),
+    }
+}
+
+pub fn is_state(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn save(cpu: &CPU) -> io::Result<Vec<u8>> {
+    let mut chunks = Vec::new();
+    put_chunk(&mut chunks, CPU_CHUNK, &cpu_chunk(cpu));
+    put_chunk(&mut chunks, RAM_CHUNK, &cpu.bus.memory.dump());
+    put_chunk(&mut chunks, INIT_CHUNK, &init_chunk(cpu.bus.ram_init));
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&zstd::encode_all(&chunks[..], 0)?);
+    Ok(out)
+}
+
+/// Restores `cpu` from `state`. The whole state is read and checked first,
+/// so on an error the CPU is left as it was.
+pub fn load(cpu: &mut CPU, state: &[u8]) -> io::Result<()> {
+    if state.len() < MAGIC.len() + 2 || !is_state(state) {
+        return Err(invalid("not a save state"));
+    }
+    let version = u16::from_le_bytes([state[MAGIC.len()], state[MAGIC.len() + 1]]);
+    if version != VERSION {
+        return Err(invalid(&format!(
+            "save state version {}, this build reads version {}",
+            version, VERSION
+        )));
+    }
+
+    let chunks = zstd::decode_all(&state[MAGIC.len() + 2..])?;
+    let (mut regs, mut ram, mut init) = (None, None, None);
+    let mut rest = &chunks[..];
+    while !rest.is_empty() {
+        if rest.len() < 8 {
+            return Err(invalid("truncated chunk header"));
+        }
+
+        let id = &rest[..4];
+        let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let data = rest
+            .get(8..8 + len)
+            .ok_or_else(|| invalid("truncated chunk"))?;
+
+        match id {
+            id if id == CPU_CHUNK => regs = Some(read_cpu(data)?),
+            id if id == RAM_CHUNK && data.len() == 0x10000 => ram = Some(data),
+            id if id == RAM_CHUNK => return Err(invalid("RAM chunk is not 64K")),
+            id if id == INIT_CHUNK => init = Some(restore_init(data)?),
+            _ => (), // chunk from a newer version
+        }
+
+        rest = &rest[8 + len..];
+    }
+
+    if let Some(s) = regs {
+        restore_cpu(cpu, s);
+    }
+    if let Some(data) = ram {
+        cpu.bus.memory.load(0, data);
+    }
+    if let Some(init) = init {
+        cpu.bus.ram_init = init;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::savestate::*;
+
+    #[test]
+    fn round_trip() {
+        let mut a = CPU::new(Bus::default());
+        a.bus.power_on(RamInit::Random(7));
+        a.stop_on_brk = true;
+        a.variant = Variant::Nmos;
+        a.load(vec![0xa9, 0x42, 0x85, 0x10, 0x00]);
+        a.run(|_| {});
+        a.irq();
+
+        let state = save(&a).unwrap();
+        let mut b = CPU::new(Bus::default());
+        load(&mut b, &state).unwrap();
+
+        assert_eq!(b.pc, a.pc);
+        assert_eq!(b.reg.a, 0x42);
+        assert_eq!(b.bus.read(0x10), 0x42);
+        assert_eq!(b.bus.read(0x20), a.bus.read(0x20));
+        assert_eq!(b.bus.ram_init, RamInit::Random(7));
+        assert!(b.halted);
+        assert!(b.irq_pending && !b.nmi_pending);
+        assert_eq!((b.cycles, b.instructions), (a.cycles, 3));
+        assert_eq!(b.variant, Variant::Nmos);
+    }
+
+    #[test]
+    fn bad_state_changes_nothing() {
+        let mut a = CPU::new(Bus::default());
+        a.load(vec![0xa9, 0x42]);
+        a.exec();
+        let state = save(&a).unwrap();
+
+        let mut b = CPU::new(Bus::default());
+        b.bus.write(0x10, 0x99);
+        let mut newer = state.clone();
+        newer[MAGIC.len()] = 2;
+        assert!(load(&mut b, &newer).is_err());
+
+        // a CPU chunk that reads fine, then a RAM chunk cut short
+        let mut chunks = Vec::new();
+        put_chunk(&mut chunks, CPU_CHUNK, &cpu_chunk(&a));
+        put_chunk(&mut chunks, RAM_CHUNK, &[0; 0x100]);
+        let mut cut = MAGIC.to_vec();
+        cut.extend_from_slice(&VERSION.to_le_bytes());
+        cut.extend_from_slice(&zstd::encode_all(&chunks[..], 0).unwrap());
+        assert!(load(&mut b, &cut).is_err());
+
+        assert_eq!((b.pc, b.reg.a, b.cycles), (0, 0, 0));
+        assert_eq!(b.bus.read(0x10), 0x99);
+        load(&mut b, &state).unwrap();
+        assert_eq!((b.pc, b.reg.a, b.cycles), (0x0602, 0x42, 2));
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let mut chunks = Vec::new();
+        put_chunk(&mut chunks, b"NEW!", &[1, 2, 3]);
+        put_chunk(
+            &mut chunks,
+            CPU_CHUNK,
+            &[0x34, 0x12, 7, 0, 0, 0xfd, 0x24, 0, 0],
+        );
+
+        let mut state = MAGIC.to_vec();
+        state.extend_from_slice(&VERSION.to_le_bytes());
+        state.extend_from_slice(&zstd::encode_all(&chunks[..], 0).unwrap());
+
+        let mut cpu = CPU::new(Bus::default());
+        load(&mut cpu, &state).unwrap();
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.reg.a, 7);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut cpu = CPU::new(Bus::default());
+        assert!(load(&mut cpu, b"nonsense state").is_err());
+    }
+}
@@ -0,0 +1,336 @@
+use crate::cpu::CPU;
+
+/// RGBA for every possible byte in display memory, built once at compile
+/// time so conversion is a table lookup per pixel.
+const PALETTE: [[u8; 4]; 256] = {
+    let mut lut = [[0, 0xFF, 0xFF, 0xFF]; 256]; // cyan
+    let colors: [(&[usize], [u8; 4]); 8] = [
+        (&[0], [0, 0, 0, 0xFF]),
+        (&[1], [0xFF, 0xFF, 0xFF, 0xFF]),
+        (&[2, 9], [0x80, 0x80, 0x80, 0xFF]),
+        (&[3, 10], [0xFF, 0, 0, 0xFF]),
+        (&[4, 11], [0, 0xFF, 0, 0xFF]),
+        (&[5, 12], [0, 0, 0xFF, 0xFF]),
+        (&[6, 13], [0xFF, 0, 0xFF, 0xFF]),
+        (&[7, 14], [0xFF, 0xFF, 0, 0xFF]),
+    ];
+
+    let mut c = 0;
+    while c < colors.len() {
+        let (bytes, rgba) = colors[c];
+        let mut i = 0;
+        while i < bytes.len() {
+            lut[bytes[i]] = rgba;
+            i += 1;
+        }
+        c += 1;
+    }
+    lut
+};
+
+pub const WIDTH: usize = 32;
+pub const HEIGHT: usize = 32;
+const BASE: u16 = 0x0200;
+
+/// Layout of the pixels handed out by `read_frame`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelFormat {
+    Rgb24,
+    /// R, G, B, A bytes per pixel, alpha always opaque.
+    Rgba8888,
+    /// The raw colour indices as stored in display memory.
+    Indexed,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Rgba8888 => 4,
+            PixelFormat::Indexed => 1,
+        }
+    }
+}
+
+/// Where a bitmap display lives in memory and how its pixels are packed.
+/// Pixel values index the same palette as the easy6502 display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub base: u16,
+    pub width: usize,
+    pub height: usize,
+    /// 1, 2 or 8 bits per pixel; narrower pixels are packed MSB first.
+    pub bpp: usize,
+    /// Bytes from the start of one row to the next.
+    pub pitch: usize,
+}
+
+impl Layout {
+    /// The easy6502 display: one byte per pixel, 32x32 at $0200.
+    pub const EASY6502: Layout = Layout {
+        base: BASE,
+        width: WIDTH,
+        height: HEIGHT,
+        bpp: 8,
+        pitch: WIDTH,
+    };
+
+    fn row_bytes(&self) -> usize {
+        (self.width * self.bpp).div_ceil(8)
+    }
+
+    /// Unpacks every pixel value into `out`, using `row` (row_bytes long) as
+    /// scratch.
+    fn read_indices(&self, cpu: &CPU, row: &mut [u8], out: &mut [u8]) {
+        let mask = ((1_u16 << self.bpp) - 1) as u8;
+        for (y, line) in out.chunks_exact_mut(self.width).enumerate() {
+            let start = self.base.wrapping_add((y * self.pitch) as u16);
+            if self.bpp == 8 {
+                cpu.bus.memory.read_into(start, line);
+                continue;
+            }
+
+            cpu.bus.memory.read_into(start, row);
+            for (x, px) in line.iter_mut().enumerate() {
+                let bit = x * self.bpp;
+                *px = (row[bit / 8] >> (8 - self.bpp - bit % 8)) & mask;
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Layout {
+    type Err = String;
+
+    /// WIDTHxHEIGHT@BASE:BPP[:PITCH], base in hex, e.g. 100x75@2000:8:128.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("expected WIDTHxHEIGHT@BASE:BPP[:PITCH]: {}", s);
+        let (size, rest) = s.split_once('@').ok_or_else(bad)?;
+        let (width, height) = size.split_once('x').ok_or_else(bad)?;
+        let mut fields = rest.split(':');
+        let base = fields.next().ok_or_else(bad)?;
+        let bpp = fields.next().ok_or_else(bad)?;
+
+        let mut layout = Layout {
+            base: u16::from_str_radix(base.trim_start_matches('$'), 16).map_err(|_| bad())?,
+            width: width.parse().map_err(|_| bad())?,
+            height: height.parse().map_err(|_| bad())?,
+            bpp: bpp.parse().map_err(|_| bad())?,
+            pitch: 0,
+        };
+        if ![1, 2, 8].contains(&layout.bpp) {
+            return Err(format!("bits per pixel must be 1, 2 or 8: {}", s));
+        }
+        if layout.width == 0 || layout.height == 0 {
+            return Err(bad());
+        }
+
+        layout.pitch = match fields.next() {
+            Some(p) => p.parse().map_err(|_| bad())?,
+            None => layout.row_bytes(),
+        };
+        if fields.next().is_some() || layout.pitch < layout.row_bytes() {
+            return Err(bad());
+        }
+        Ok(layout)
+    }
+}
+
+/// Palette lookup for a whole frame of pixel values.
+pub fn convert(indices: &[u8], format: PixelFormat, out: &mut [u8]) {
+    match format {
+        PixelFormat::Indexed => out.copy_from_slice(indices),
+        PixelFormat::Rgb24 => {
+            for (o, &i) in out.chunks_exact_mut(3).zip(indices) {
+                o.copy_from_slice(&PALETTE[i as usize][..3]);
+            }
+        }
+        PixelFormat::Rgba8888 => {
+            for (o, &i) in out.chunks_exact_mut(4).zip(indices) {
+                o.copy_from_slice(&PALETTE[i as usize]);
+            }
+        }
+    }
+}
+
+/// Copies `converted` into `frame` if they differ, returning whether they did.
+pub fn replace_if_changed(frame: &mut [u8], converted: &[u8]) -> bool {
+    let frame = &mut frame[..converted.len()];
+    if frame == converted {
+        return false;
+    }
+    frame.copy_from_slice(converted);
+    true
+}
+
+/// Converts the display at $0200-$05FF into `frame` in the given format,
+/// returning whether anything changed. `frame` must hold
+/// WIDTH * HEIGHT * bytes_per_pixel bytes.
+pub fn read_frame(cpu: &mut CPU, format: PixelFormat, frame: &mut [u8]) -> bool {
+    let mut indices = [0; WIDTH * HEIGHT];
+    Layout::EASY6502.read_indices(cpu, &mut [], &mut indices);
+
+    // convert the whole frame, then compare and copy it in one go
+    let mut converted = [0; WIDTH * HEIGHT * 4];
+    let converted = &mut converted[..WIDTH * HEIGHT * format.bytes_per_pixel()];
+    convert(&indices, format, converted);
+    replace_if_changed(frame, converted)
+}
+
+/// Something that hands out frames for an embedder to upload as a texture.
+pub trait FrameSource {
+    fn dimensions(&self) -> (usize, usize);
+
+    fn format(&self) -> PixelFormat;
+
+    fn pixels(&self) -> &[u8];
+
+    /// Whether the pixels changed since the last call.
+    fn take_dirty(&mut self) -> bool;
+}
+
+/// A display that draws itself from the machine's memory.
+pub trait Screen: FrameSource {
+    fn update(&mut self, cpu: &mut CPU);
+}
+
+/// A bitmap display (the easy6502 one unless given a layout), converted into
+/// an owned buffer.
+pub struct Display {
+    layout: Layout,
+    format: PixelFormat,
+    buffer: Vec<u8>,
+    dirty: bool,
+    // scratch space for update, kept to avoid allocating every instruction
+    row: Vec<u8>,
+    indices: Vec<u8>,
+    converted: Vec<u8>,
+}
+
+impl Display {
+    pub fn new(format: PixelFormat) -> Self {
+        Display::with_layout(Layout::EASY6502, format)
+    }
+
+    pub fn with_layout(layout: Layout, format: PixelFormat) -> Self {
+        let pixels = layout.width * layout.height;
+        Display {
+            layout,
+            format,
+            buffer: vec![0; pixels * format.bytes_per_pixel()],
+            dirty: true,
+            row: vec![0; layout.row_bytes()],
+            indices: vec![0; pixels],
+            converted: vec![0; pixels * format.bytes_per_pixel()],
+        }
+    }
+}
+
+impl Screen for Display {
+    fn update(&mut self, cpu: &mut CPU) {
+        self.layout
+            .read_indices(cpu, &mut self.row, &mut self.indices);
+        convert(&self.indices, self.format, &mut self.converted);
+        if replace_if_changed(&mut self.buffer, &self.converted) {
+            self.dirty = true;
+        }
+    }
+}
+
+impl FrameSource for Display {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.layout.width, self.layout.height)
+    }
+
+    fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    fn pixels(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+}
+
+pub fn read_screen_state(cpu: &mut CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
+    read_frame(cpu, PixelFormat::Rgb24, frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::screen::*;
+
+    #[test]
+    fn pixel_formats() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.write(0x0200, 1);
+        cpu.bus.write(0x0201, 5);
+
+        let mut indexed = [0; WIDTH * HEIGHT];
+        assert!(read_frame(&mut cpu, PixelFormat::Indexed, &mut indexed));
+        assert_eq!(&indexed[..3], &[1, 5, 0]);
+        assert!(!read_frame(&mut cpu, PixelFormat::Indexed, &mut indexed));
+
+        let mut rgba = [0; WIDTH * HEIGHT * 4];
+        assert!(read_frame(&mut cpu, PixelFormat::Rgba8888, &mut rgba));
+        assert_eq!(&rgba[..8], &[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0xFF, 0xFF]);
+
+        let mut rgb = [0; WIDTH * HEIGHT * 3];
+        assert!(read_screen_state(&mut cpu, &mut rgb));
+        assert_eq!(&rgb[..6], &[0xFF, 0xFF, 0xFF, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn display_dirty_flag() {
+        let mut cpu = CPU::new(Bus::default());
+        let mut display = Display::new(PixelFormat::Indexed);
+        assert!(display.take_dirty());
+
+        display.update(&mut cpu);
+        assert!(!display.take_dirty());
+
+        cpu.bus.write(0x0205, 3);
+        display.update(&mut cpu);
+        assert!(display.take_dirty());
+        assert_eq!(display.pixels()[5], 3);
+        assert_eq!(display.dimensions(), (32, 32));
+    }
+
+    #[test]
+    fn bitmap_layouts() {
+        assert_eq!(
+            "100x75@2000:8:128".parse(),
+            Ok(Layout {
+                base: 0x2000,
+                width: 100,
+                height: 75,
+                bpp: 8,
+                pitch: 128
+            })
+        );
+        assert_eq!("12x2@$300:2".parse::<Layout>().unwrap().pitch, 3);
+        assert!("12x2@300:4".parse::<Layout>().is_err());
+        assert!("12x2@300:8:4".parse::<Layout>().is_err());
+
+        // 2bpp, 4 pixels a byte, rows 4 bytes apart
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.memory.load(0x3000, &[0b00_01_10_11, 0b11_00_00_00]);
+        cpu.bus.memory.load(0x3004, &[0b01_00_00_00]);
+
+        let layout = Layout {
+            base: 0x3000,
+            width: 5,
+            height: 2,
+            bpp: 2,
+            pitch: 4,
+        };
+        let mut display = Display::with_layout(layout, PixelFormat::Indexed);
+        display.update(&mut cpu);
+        assert_eq!(display.pixels(), &[0, 1, 2, 3, 3, 1, 0, 0, 0, 0]);
+        assert_eq!(display.dimensions(), (5, 2));
+    }
+}
@@ -0,0 +1,467 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::bus::Bus;
+use crate::clock::Timing;
+use crate::cpu::CPU;
+use crate::soak::{self, CYCLES_PER_FRAME};
+use crate::testrom::Outcome;
+
+/// A register a script can check.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reg {
+    A,
+    X,
+    Y,
+    Sp,
+    /// The status byte
+    P,
+    Pc,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Check {
+    Reg(Reg, u16),
+    /// Bytes expected from an address on
+    Memory(u16, Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// A key press, written to $FF as the front-end does
+    Press(u8),
+    Expect(Vec<Check>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub cycle: u64,
+    /// "frame 30", "cycle 1000" or "250 ms", as the script put it
+    pub when: String,
+    pub action: Action,
+}
+
+/// An end-to-end test: a program, the keys pressed while it runs and what
+/// memory and the registers must hold at given frames, cycles or
+/// milliseconds of emulated NTSC time. Written
+/// in a small subset of TOML:
+///
+/// ```toml
+/// rom = "snake.nes"   # relative to the script
+/// seed = 7            # for the random byte at $FE, 0 if left out
+///
+/// [[input]]
+/// frame = 5
+/// key = "d"
+///
+/// [[expect]]
+/// frame = 30
+/// a = 0x10
+/// "$0010" = [0x11, 0x10]
+/// ```
+///
+/// An expectation may name a, x, y, sp, p and pc, and any number of
+/// addresses each with a byte or a list of bytes. Strings take TOML's
+/// backslash escapes, and a `#` inside one is not a comment.
+#[derive(Debug, PartialEq)]
+pub struct Script {
+    pub rom: PathBuf,
+    pub seed: u64,
+    /// In the order they happen
+    pub events: Vec<Event>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Int(u64),
+    Str(String),
+    List(Vec<u64>),
+}
+
+/// A table's keys and values, with the lines they are on.
+type Keys = Vec<(usize, String, Value)>;
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, msg))
+}
+
+fn int(s: &str) -> Option<u64> {
+    let s = s.trim().replace('_', "");
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// The line up to a `#` that isn't inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+/// The text of a TOML basic string, between its quotes.
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        out.push(match c {
+            '"' => return None,
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                '"' => '"',
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                }
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(out)
+}
+
+/// `s` as a TOML basic string, quotes and all, for writing scripts.
+pub fn quote(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\t' => out += "\\t",
+            '\r' => out += "\\r",
+            c if c.is_control() => out += &format!("\\u{:04X}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn value(s: &str) -> Option<Value> {
+    let s = s.trim();
+    if let Some(text) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        unescape(text).map(Value::Str)
+    } else if let Some(items) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        items
+            .split(',')
+            .filter(|i| !i.trim().is_empty())
+            .map(int)
+            .collect::<Option<_>>()
+            .map(Value::List)
+    } else {
+        int(s).map(Value::Int)
+    }
+}
+
+fn address(key: &str) -> Option<u16> {
+    let hex = key.strip_prefix('$').or_else(|| key.strip_prefix("0x"))?;
+    u16::from_str_radix(hex, 16).ok()
+}
+
+fn byte(v: u64, line: usize) -> io::Result<u8> {
+    u8::try_from(v).map_err(|_| invalid(line, &format!("{} doesn't fit in a byte", v)))
+}
+
+/// Turns one `[[input]]` or `[[expect]]` table into an event.
+fn event(table: &str, keys: Keys, line: usize) -> io::Result<Event> {
+    let mut at = None;
+    let mut key = None;
+    let mut checks = Vec::new();
+
+    for (line, name, v) in keys {
+        match (name.as_str(), v) {
+            ("frame", Value::Int(n)) => {
+                at = Some((n * CYCLES_PER_FRAME, format!("frame {}", n)));
+            }
+            ("cycle", Value::Int(n)) => at = Some((n, format!("cycle {}", n))),
+            ("ms", Value::Int(n)) => {
+                let cycles = Timing::Ntsc.cycles(Duration::from_millis(n));
+                at = Some((cycles, format!("{} ms", n)));
+            }
+            ("key", Value::Str(s)) if table == "input" && s.len() == 1 => {
+                key = Some(s.as_bytes()[0]);
+            }
+            (r @ ("a" | "x" | "y" | "sp" | "p"), Value::Int(n)) if table == "expect" => {
+                let reg = match r {
+                    "a" => Reg::A,
+                    "x" => Reg::X,
+                    "y" => Reg::Y,
+                    "sp" => Reg::Sp,
+                    _ => Reg::P,
+                };
+                checks.push(Check::Reg(reg, byte(n, line)? as u16));
+            }
+            ("pc", Value::Int(n)) if table == "expect" => {
+                let pc = u16::try_from(n).map_err(|_| invalid(line, "pc is 16 bits"))?;
+                checks.push(Check::Reg(Reg::Pc, pc));
+            }
+            (name, v) if table == "expect" && address(name).is_some() => {
+                let bytes = match v {
+                    Value::Int(n) => vec![byte(n, line)?],
+                    Value::List(l) => l
+                        .into_iter()
+                        .map(|n| byte(n, line))
+                        .collect::<io::Result<_>>()?,
+                    Value::Str(_) => return Err(invalid(line, "expected a byte or a list")),
+                };
+                checks.push(Check::Memory(address(name).unwrap(), bytes));
+            }
+            (name, _) => {
+                return Err(invalid(
+                    line,
+                    &format!("unexpected {} in [[{}]]", name, table),
+                ))
+            }
+        }
+    }
+
+    let (cycle, when) = at.ok_or_else(|| invalid(line, "needs a frame, a cycle or ms"))?;
+    let action = match table {
+        "input" => Action::Press(key.ok_or_else(|| invalid(line, "needs a one letter key"))?),
+        _ => Action::Expect(checks),
+    };
+    Ok(Event {
+        cycle,
+        when,
+        action,
+    })
+}
+
+pub fn parse(text: &str) -> io::Result<Script> {
+    let mut rom = None;
+    let mut seed = 0;
+    let mut events = Vec::new();
+    // the table being read: its name, where it started and its keys so far
+    let mut table: Option<(String, usize, Keys)> = None;
+
+    for (i, raw) in text.lines().enumerate() {
+        let n = i + 1;
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            if !matches!(name, "input" | "expect") {
+                return Err(invalid(n, &format!("unknown table [[{}]]", name)));
+            }
+            if let Some((t, start, keys)) = table.take() {
+                events.push(event(&t, keys, start)?);
+            }
+            table = Some((name.to_string(), n, Vec::new()));
+            continue;
+        }
+
+        let (key, v) = line
+            .split_once('=')
+            .ok_or_else(|| invalid(n, "expected key = value"))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let v = value(v).ok_or_else(|| invalid(n, "can't read the value"))?;
+
+        match (&mut table, key.as_str(), v) {
+            (Some((_, _, keys)), _, v) => keys.push((n, key, v)),
+            (None, "rom", Value::Str(s)) => rom = Some(PathBuf::from(s)),
+            (None, "seed", Value::Int(s)) => seed = s,
+            (None, key, _) => return Err(invalid(n, &format!("unexpected {}", key))),
+        }
+    }
+    if let Some((t, start, keys)) = table {
+        events.push(event(&t, keys, start)?);
+    }
+
+    // stable, so a key pressed and checked at the same time goes in order
+    events.sort_by_key(|e| e.cycle);
+    Ok(Script {
+        rom: rom.ok_or_else(|| Error::new(ErrorKind::InvalidData, "no rom given"))?,
+        seed,
+        events,
+    })
+}
+
+/// What failed, or None if `check` holds.
+fn failure(cpu: &CPU, check: &Check) -> Option<String> {
+    match check {
+        Check::Reg(reg, want) => {
+            let got = match reg {
+                Reg::A => cpu.reg.a as u16,
+                Reg::X => cpu.reg.x as u16,
+                Reg::Y => cpu.reg.y as u16,
+                Reg::Sp => cpu.reg.sp as u16,
+                Reg::P => u8::from(cpu.flags) as u16,
+                Reg::Pc => cpu.pc,
+            };
+            let width = if *reg == Reg::Pc { 4 } else { 2 };
+            (got != *want).then(|| {
+                format!(
+                    "{} is ${:0w$X}, expected ${:0w$X}",
+                    format!("{:?}", reg).to_uppercase(),
+                    got,
+                    want,
+                    w = width
+                )
+            })
+        }
+        Check::Memory(start, want) => {
+            let got: Vec<u8> = (0..want.len() as u16)
+                .map(|i| cpu.bus.memory.read(start.wrapping_add(i)))
+                .collect();
+            let hex = |b: &[u8]| {
+                b.iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            (got != *want)
+                .then(|| format!("${:04X} holds {}, expected {}", start, hex(&got), hex(want)))
+        }
+    }
+}
+
+/// The next "random" byte for $FE, 1 to 15 as the front-end writes. An
+/// xorshift, so the bytes repeat from run to run.
+pub fn random_byte(x: &mut u64) -> u8 {
+    *x ^= *x << 13;
+    *x ^= *x >> 7;
+    *x ^= *x << 17;
+    (*x % 15) as u8 + 1
+}
+
+/// Runs `script` with its program in `cpu`, giving up after `max_cycles`.
+/// A program that halts or jams before an expectation is checked as it
+/// stopped. Returns the failures, each with when it was checked.
+fn play(cpu: &mut CPU, script: &Script, max_cycles: u64) -> Vec<String> {
+    let mut failures = Vec::new();
+    let mut x = script.seed | 1;
+
+    for e in &script.events {
+        while cpu.cycles < e.cycle.min(max_cycles) && !cpu.halted && !cpu.is_jammed() {
+            cpu.bus.write(0xfe, random_byte(&mut x));
+            cpu.exec();
+        }
+        if cpu.cycles < e.cycle && cpu.cycles >= max_cycles {
+            failures.push(format!("{}: gave up at cycle {}", e.when, cpu.cycles));
+            break;
+        }
+
+        match &e.action {
+            Action::Press(key) => cpu.bus.write(0xff, *key),
+            Action::Expect(checks) => failures.extend(
+                checks
+                    .iter()
+                    .filter_map(|c| failure(cpu, c))
+                    .map(|f| format!("{}: {}", e.when, f)),
+            ),
+        }
+    }
+    failures
+}
+
+/// Runs the script at `path` as a test, for the `test` subcommand.
+pub fn run(path: &Path, max_cycles: u64) -> io::Result<Outcome> {
+    let script = parse(&fs::read_to_string(path)?)?;
+    let rom = path.parent().unwrap_or(Path::new("")).join(&script.rom);
+
+    // scripts run programs as the front-end does by default
+    let mut cpu = CPU::new(Bus::default());
+    cpu.stop_on_brk = true;
+    soak::load(&mut cpu, fs::read(&rom)?)
+        .map_err(|(_, detail)| Error::new(ErrorKind::InvalidData, detail))?;
+
+    let checks: usize = script
+        .events
+        .iter()
+        .map(|e| match &e.action {
+            Action::Expect(c) => c.len(),
+            Action::Press(_) => 0,
+        })
+        .sum();
+    let failures = play(&mut cpu, &script, max_cycles);
+    let (passed, status, output) = match failures.first() {
+        None => (true, format!("{} checks passed", checks), String::new()),
+        Some(first) => (false, first.clone(), failures.join("\n")),
+    };
+
+    Ok(Outcome {
+        name: path.file_name().unwrap().to_string_lossy().to_string(),
+        passed,
+        status,
+        output,
+        cycles: cpu.cycles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::*;
+
+    #[test]
+    fn parse_and_play() {
+        let script = parse(
+            "rom = \"count.bin\"\n\
+             \n\
+             [[expect]]   # after the key\n\
+             cycle = 0x10\n\
+             x = 3\n\
+             \"$0010\" = [0x64, 0]\n\
+             \n\
+             [[input]]\n\
+             cycle = 5\n\
+             key = \"d\"\n",
+        )
+        .unwrap();
+        assert_eq!(script.rom, PathBuf::from("count.bin"));
+        assert_eq!(script.events[0].action, Action::Press(b'd'));
+        assert_eq!(script.events[1].when, "cycle 16");
+        let later = parse("rom = \"a\"\n[[expect]]\nms = 1000\n").unwrap();
+        assert_eq!(
+            (later.events[0].cycle, &*later.events[0].when),
+            (1_789_773, "1000 ms")
+        );
+
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa5, 0xff, // LDA $FF
+            0x85, 0x10, // STA $10
+            0xe8, // INX
+            0x4c, 0x00, 0x06, // JMP $0600
+        ]);
+        assert_eq!(
+            play(&mut cpu, &script, 1000),
+            ["cycle 16: X is $01, expected $03"]
+        );
+
+        let odd = "roms/#1 \"odd\"\\name.bin";
+        let script = parse(&format!("rom = {}  # a comment\n", quote(odd))).unwrap();
+        assert_eq!(script.rom, PathBuf::from(odd));
+        assert_eq!(quote("a\tb"), "\"a\\tb\"");
+        assert!(parse("rom = \"a\"b\"\n").is_err());
+
+        assert!(parse("[[input]]\nframe = 1\n").is_err());
+        assert!(parse("rom = \"a\"\n[[input]]\nkey = \"d\"\n").is_err());
+        assert!(parse("rom = \"a\"\n[[expect]]\nframe = 1\n\"$10\" = 256\n").is_err());
+    }
+
+    #[test]
+    fn hello_script() {
+        let outcome = run(Path::new("roms/hello.toml"), 1_000_000).unwrap();
+        assert!(outcome.passed, "{}", outcome.output);
+        assert_eq!(outcome.status, "2 checks passed");
+    }
+}
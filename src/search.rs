@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+use std::io;
+use std::iter;
+
+use crate::cpu::CPU;
+use crate::savestate;
+use crate::script::{self, random_byte};
+use crate::soak::CYCLES_PER_FRAME;
+
+/// What the search is after.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    /// The PC arriving at an address
+    Pc(u16),
+    /// A byte of memory holding a value
+    Memory(u16, u8),
+}
+
+/// `pc=ADDR` or `ADDR=VALUE`, in hex.
+impl std::str::FromStr for Goal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("expected pc=ADDR or ADDR=VALUE in hex: {}", s);
+        let hex = |h: &str| h.trim().trim_start_matches('$').to_string();
+        let (lhs, rhs) = s.split_once('=').ok_or_else(bad)?;
+
+        if lhs.trim().eq_ignore_ascii_case("pc") {
+            return u16::from_str_radix(&hex(rhs), 16)
+                .map(Goal::Pc)
+                .map_err(|_| bad());
+        }
+        Ok(Goal::Memory(
+            u16::from_str_radix(&hex(lhs), 16).map_err(|_| bad())?,
+            u8::from_str_radix(&hex(rhs), 16).map_err(|_| bad())?,
+        ))
+    }
+}
+
+impl std::fmt::Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Goal::Pc(pc) => write!(f, "PC ${:04X}", pc),
+            Goal::Memory(adr, v) => write!(f, "${:04X} = ${:02X}", adr, v),
+        }
+    }
+}
+
+impl Goal {
+    fn reached(&self, cpu: &CPU) -> bool {
+        match *self {
+            Goal::Pc(pc) => cpu.pc == pc,
+            Goal::Memory(adr, v) => cpu.bus.memory.read(adr) == v,
+        }
+    }
+}
+
+/// A machine the search got to, the random byte generator's state there,
+/// and the key pressed at the start of each frame on the way.
+struct Node {
+    state: Vec<u8>,
+    rng: u64,
+    keys: Vec<Option<u8>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Found {
+    /// The key pressed at the start of each frame, None where none was
+    pub keys: Vec<Option<u8>>,
+    /// When the goal was met
+    pub cycle: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub found: Option<Found>,
+    /// Distinct states reached at the ends of frames
+    pub states: usize,
+    /// Whether states were dropped to stay in bounds, so a miss may not
+    /// mean the goal can't be reached
+    pub pruned: bool,
+}
+
+/// Breadth-first search, from `cpu` as just loaded, for the shortest run
+/// of key presses that meets `goal` within `frames` frames. Every state is
+/// carried into the next frame once with no key pressed and once with each
+/// of `keys` written to $FF, as the front-end would. States already seen
+/// are dropped, as are any beyond `per_frame` in a frame. The byte at $FE
+/// comes from `seed` as in a test script, so what is found replays.
+pub fn search(
+    cpu: &mut CPU,
+    goal: Goal,
+    keys: &[u8],
+    seed: u64,
+    frames: u64,
+    per_frame: usize,
+) -> io::Result<Outcome> {
+    let mut outcome = Outcome {
+        found: None,
+        states: 0,
+        pruned: false,
+    };
+    if goal.reached(cpu) {
+        outcome.found = Some(Found {
+            keys: Vec::new(),
+            cycle: cpu.cycles,
+        });
+        return Ok(outcome);
+    }
+
+    let choices: Vec<Option<u8>> = iter::once(None)
+        .chain(keys.iter().map(|&k| Some(k)))
+        .collect();
+    let mut frontier = vec![Node {
+        state: savestate::save(cpu)?,
+        rng: seed | 1,
+        keys: Vec::new(),
+    }];
+    let mut seen = HashSet::new();
+
+    for frame in 1..=frames {
+        let mut next = Vec::new();
+        for node in &frontier {
+            for &key in &choices {
+                savestate::load(cpu, &node.state)?;
+                if let Some(k) = key {
+                    cpu.bus.write(0xff, k);
+                }
+
+                let mut rng = node.rng;
+                let mut keys = node.keys.clone();
+                keys.push(key);
+                while cpu.cycles < frame * CYCLES_PER_FRAME && !cpu.halted && !cpu.is_jammed() {
+                    cpu.bus.write(0xfe, random_byte(&mut rng));
+                    cpu.exec();
+                    if goal.reached(cpu) {
+                        outcome.found = Some(Found {
+                            keys,
+                            cycle: cpu.cycles,
+                        });
+                        return Ok(outcome);
+                    }
+                }
+
+                let stopped = cpu.halted || cpu.is_jammed();
+                if stopped || !seen.insert((cpu.state_hash(), cpu.cycles, rng)) {
+                    continue;
+                }
+                outcome.states += 1;
+                if next.len() == per_frame {
+                    outcome.pruned = true;
+                    continue;
+                }
+                next.push(Node {
+                    state: savestate::save(cpu)?,
+                    rng,
+                    keys,
+                });
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    Ok(outcome)
+}
+
+/// A test script replaying what was found, for the `test` subcommand.
+pub fn script(found: &Found, goal: Goal, rom: &str, seed: u64) -> String {
+    let mut out = format!(
+        "# Found by `nesemu search`: reaches {} at cycle {}\nrom = {}\nseed = {}\n",
+        goal,
+        found.cycle,
+        script::quote(rom),
+        seed
+    );
+    for (frame, key) in found.keys.iter().enumerate() {
+        if let Some(k) = key {
+            let key = script::quote(&(*k as char).to_string());
+            out += &format!("\n[[input]]\nframe = {}\nkey = {}\n", frame, key);
+        }
+    }
+
+    out += &format!("\n[[expect]]\ncycle = {}\n", found.cycle);
+    match goal {
+        Goal::Pc(pc) => out += &format!("pc = 0x{:04X}\n", pc),
+        Goal::Memory(adr, v) => out += &format!("\"${:04X}\" = 0x{:02X}\n", adr, v),
+    }
+    out
+}
+
+pub fn report(goal: Goal, outcome: &Outcome) -> String {
+    let Some(found) = &outcome.found else {
+        let why = if outcome.pruned {
+            "; states were dropped to stay in bounds, try a higher --states"
+        } else {
+            ""
+        };
+        return format!(
+            "{} not reached after {} states{}\n",
+            goal, outcome.states, why
+        );
+    };
+
+    let presses: Vec<String> = found
+        .keys
+        .iter()
+        .enumerate()
+        .filter_map(|(frame, k)| k.map(|k| format!("frame {} '{}'", frame, k as char)))
+        .collect();
+    format!(
+        "Reached {} at cycle {} after {} states\nKeys: {}\n",
+        goal,
+        found.cycle,
+        outcome.states,
+        if presses.is_empty() {
+            "none".to_string()
+        } else {
+            presses.join(", ")
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::script;
+    use crate::search::*;
+
+    #[test]
+    fn finds_key_sequence() {
+        assert_eq!("pc=$0612".parse(), Ok(Goal::Pc(0x0612)));
+        assert_eq!("0010=5".parse(), Ok(Goal::Memory(0x0010, 0x05)));
+        assert!("pc".parse::<Goal>().is_err());
+
+        let program = vec![
+            0xa5, 0xff, // $0600 LDA $FF
+            0xc9, 0x64, // $0602 CMP #'d'
+            0xd0, 0xfa, // $0604 BNE $0600
+            0xa5, 0xff, // $0606 LDA $FF
+            0xc9, 0x77, // $0608 CMP #'w'
+            0xd0, 0xfa, // $060A BNE $0606
+            0xe6, 0x10, // $060C INC $10
+            0x4c, 0x0c, 0x06, // $060E JMP $060C
+        ];
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(program.clone());
+
+        let outcome = search(&mut cpu, Goal::Pc(0x060c), b"wasd", 0, 5, 100).unwrap();
+        let found = outcome.found.unwrap();
+        assert_eq!(found.keys, [Some(b'd'), Some(b'w')]);
+        assert!(found.cycle > CYCLES_PER_FRAME && found.cycle < 2 * CYCLES_PER_FRAME);
+
+        let text = script(&found, Goal::Pc(0x060c), "keys.bin", 0);
+        let parsed = script::parse(&text).unwrap();
+        assert_eq!(parsed.events.len(), 3);
+        assert_eq!(parsed.events[1].action, script::Action::Press(b'w'));
+
+        // without a 'w' to press the second loop never ends
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(program);
+        let outcome = search(&mut cpu, Goal::Memory(0x0010, 0x03), b"d", 0, 2, 100).unwrap();
+        assert_eq!(outcome.found, None);
+        assert_eq!(
+            report(Goal::Memory(0x0010, 0x03), &outcome),
+            format!("$0010 = $03 not reached after {} states\n", outcome.states)
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::cartridge::Cartridge;
+use crate::clock::Timing;
+use crate::compat;
+use crate::cpu::CPU;
+use crate::disasm;
+use crate::screen::{self, PixelFormat, HEIGHT, WIDTH};
+use crate::testrom;
+
+/// CPU cycles in one NTSC frame, as the harnesses and scripts count them.
+pub const CYCLES_PER_FRAME: u64 = Timing::Ntsc.cycles_per_frame();
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    /// Still running when its frames were up
+    Ok,
+    Halted,
+    Jammed,
+    IllegalOpcode,
+    UnsupportedMapper,
+    LoadError,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Halted => "halted",
+            Status::Jammed => "jammed",
+            Status::IllegalOpcode => "illegal-opcode",
+            Status::UnsupportedMapper => "unsupported-mapper",
+            Status::LoadError => "load-error",
+        }
+    }
+}
+
+pub struct SoakResult {
+    pub name: String,
+    /// compat::rom_hash of the file, None if it couldn't be read.
+    pub hash: Option<u64>,
+    pub status: Status,
+    pub detail: String,
+    pub cycles: u64,
+    /// Whether the display never showed more than one colour. None for iNES
+    /// images, which have no display to look at without a PPU.
+    pub blank: Option<bool>,
+}
+
+/// Loads `data` the way the front-end would: iNES images through the
+/// cartridge, 64K images as a full memory image, anything else at $0600.
+pub fn load(cpu: &mut CPU, data: Vec<u8>) -> Result<bool, (Status, String)> {
+    if Cartridge::is_ines(&data) {
+        let cart = Cartridge::from_ines(&data).map_err(|e| match e.kind() {
+            io::ErrorKind::Unsupported => (Status::UnsupportedMapper, e.to_string()),
+            _ => (Status::LoadError, e.to_string()),
+        })?;
+        cart.insert(cpu);
+        Ok(true)
+    } else if data.len() == 0x10000 {
+        cpu.bus.memory.load(0, &data);
+        cpu.reset();
+        Ok(false)
+    } else {
+        cpu.load(data);
+        Ok(false)
+    }
+}
+
+/// Runs one ROM for `frames` frames worth of cycles and triages the result.
+pub fn soak_rom(path: &Path, frames: u64) -> SoakResult {
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    let mut result = SoakResult {
+        name,
+        hash: None,
+        status: Status::Ok,
+        detail: String::new(),
+        cycles: 0,
+        blank: None,
+    };
+
+    let data = match fs::read(path) {
+        Ok(d) => d,
+        Err(e) => {
+            (result.status, result.detail) = (Status::LoadError, e.to_string());
+            return result;
+        }
+    };
+
+    result.hash = Some(compat::rom_hash(&data));
+    let mut cpu = CPU::new(Bus::default());
+    cpu.stop_on_brk = true;
+    let ines = match load(&mut cpu, data) {
+        Ok(ines) => ines,
+        Err((status, detail)) => {
+            (result.status, result.detail) = (status, detail);
+            return result;
+        }
+    };
+
+    let max_cycles = frames * CYCLES_PER_FRAME;
+    let mut frame = vec![0; WIDTH * HEIGHT * 3];
+    let mut next_frame = 0;
+    let mut blank = true;
+    let mut sample = |cpu: &mut CPU| {
+        screen::read_frame(cpu, PixelFormat::Rgb24, &mut frame);
+        blank &= frame.chunks_exact(3).all(|px| px == &frame[..3]);
+    };
+
+    // stops at the first undocumented opcode, which would run but is worth
+    // triaging; JAMs are left to jam, they have a status of their own
+    let mut illegal = None;
+    while cpu.cycles < max_cycles && !cpu.halted && !cpu.is_jammed() {
+        let op = cpu.bus.memory.read(cpu.pc);
+        if !disasm::documented(op) && disasm::decode(op).0 != "JAM" {
+            illegal = Some(op);
+            break;
+        }
+        cpu.exec();
+
+        if !ines && cpu.cycles >= next_frame {
+            next_frame += CYCLES_PER_FRAME;
+            sample(&mut cpu);
+        }
+    }
+    if !ines {
+        sample(&mut cpu);
+    }
+
+    (result.status, result.detail) = match illegal {
+        Some(op) => (
+            Status::IllegalOpcode,
+            format!("${:02X} at ${:04X}", op, cpu.pc),
+        ),
+        None if cpu.is_jammed() => (Status::Jammed, format!("at ${:04X}", cpu.pc)),
+        None if cpu.halted => (Status::Halted, format!("at ${:04X}", cpu.pc)),
+        None => (Status::Ok, String::new()),
+    };
+    result.cycles = cpu.cycles;
+    result.blank = (!ines).then_some(blank);
+    result
+}
+
+/// Soaks every .nes/.bin file in `dir`, sorted by name, one ROM per thread.
+pub fn soak_dir(dir: &Path, frames: u64, jobs: Option<usize>) -> io::Result<Vec<SoakResult>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == "nes" || ext == "bin")
+        })
+        .collect();
+    paths.sort();
+
+    Ok(testrom::par_map(&paths, jobs, |p| soak_rom(p, frames)))
+}
+
+fn blank_str(blank: Option<bool>) -> &'static str {
+    match blank {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "",
+    }
+}
+
+pub fn print_report(results: &[SoakResult]) {
+    let width = results.iter().map(|r| r.name.len()).max().unwrap_or(4);
+    println!(
+        "{:width$}  {:18}  {:>12}  {:5}  DETAIL",
+        "ROM", "STATUS", "CYCLES", "BLANK"
+    );
+    for r in results {
+        println!(
+            "{:width$}  {:18}  {:>12}  {:5}  {}",
+            r.name,
+            r.status.as_str(),
+            r.cycles,
+            blank_str(r.blank),
+            r.detail
+        );
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn csv(results: &[SoakResult]) -> String {
+    let mut out = String::from("rom,status,cycles,blank,detail\n");
+    for r in results {
+        out += &format!(
+            "{},{},{},{},{}\n",
+            csv_field(&r.name),
+            r.status.as_str(),
+            r.cycles,
+            blank_str(r.blank),
+            csv_field(&r.detail)
+        );
+    }
+    out
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+pub fn json(results: &[SoakResult]) -> String {
+    let entries: Vec<String> = results
+        .iter()
+        .map(|r| {
+            let blank = match r.blank {
+                Some(b) => b.to_string(),
+                None => "null".to_string(),
+            };
+            format!(
+                "  {{\"rom\": {}, \"status\": \"{}\", \"cycles\": {}, \"blank\": {}, \"detail\": {}}}",
+                json_string(&r.name),
+                r.status.as_str(),
+                r.cycles,
+                blank,
+                json_string(&r.detail)
+            )
+        })
+        .collect();
+
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::soak::*;
+
+    fn soak_bytes(name: &str, data: &[u8]) -> SoakResult {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, data).unwrap();
+        let result = soak_rom(&path, 1);
+        fs::remove_file(path).unwrap();
+        result
+    }
+
+    #[test]
+    fn triage() {
+        // LDA #$01, STA $0200, BRK
+        let r = soak_bytes(
+            "rusty6502-soak-halt.bin",
+            &[0xa9, 0x01, 0x8d, 0x00, 0x02, 0x00],
+        );
+        assert_eq!(r.status, Status::Halted);
+        assert_eq!(r.blank, Some(false));
+
+        // JMP $0600
+        let r = soak_bytes("rusty6502-soak-loop.bin", &[0x4c, 0x00, 0x06]);
+        assert_eq!(r.status, Status::Ok);
+        assert_eq!(r.cycles, CYCLES_PER_FRAME);
+        assert_eq!(r.blank, Some(true));
+
+        let r = soak_bytes("rusty6502-soak-jam.bin", &[0x02]);
+        assert_eq!(r.status, Status::Jammed);
+
+        // LDA #$01, SLO $10
+        let r = soak_bytes("rusty6502-soak-slo.bin", &[0xa9, 0x01, 0x07, 0x10]);
+        assert_eq!(r.status, Status::IllegalOpcode);
+        assert_eq!(r.detail, "$07 at $0602");
+
+        let mut ines = b"NES\x1a\x01\x00\x10\x00".to_vec();
+        ines.resize(16 + 0x4000, 0);
+        let r = soak_bytes("rusty6502-soak-mmc1.nes", &ines);
+        assert_eq!(r.status, Status::UnsupportedMapper);
+        assert_eq!(r.blank, None);
+    }
+
+    #[test]
+    fn reports() {
+        let results = vec![SoakResult {
+            name: "a,b.bin".to_string(),
+            hash: None,
+            status: Status::IllegalOpcode,
+            detail: "$FF at \"$0600\"".to_string(),
+            cycles: 12,
+            blank: None,
+        }];
+
+        assert_eq!(
+            csv(&results),
+            "rom,status,cycles,blank,detail\n\"a,b.bin\",illegal-opcode,12,,\"$FF at \"\"$0600\"\"\"\n"
+        );
+        assert_eq!(
+            json(&results),
+            "[\n  {\"rom\": \"a,b.bin\", \"status\": \"illegal-opcode\", \"cycles\": 12, \"blank\": null, \"detail\": \"$FF at \\\"$0600\\\"\"}\n]\n"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use rand::Rng;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::cpu::CPU;
+use crate::screen::read_screen_state;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Jammed(u16),
+    Halted,
+}
+
+/// One rendered screen, plus anything notable that happened since the last one.
+pub struct Frame {
+    pub screen: [u8; 32 * 3 * 32],
+    pub events: Vec<Event>,
+}
+
+/// Runs `cpu` as the easy6502 machine on a blocking tokio task. Frames are
+/// yielded whenever the screen changes; key codes sent on the returned channel
+/// are written to $FF. The stream ends when the CPU halts or jams, and the
+/// emulator stops once the stream is dropped.
+///
+/// Must be called from within a tokio runtime.
+pub fn frames(mut cpu: CPU) -> (ReceiverStream<Frame>, mpsc::Sender<u8>) {
+    let (frame_tx, frame_rx) = mpsc::channel(4);
+    let (input_tx, mut input_rx) = mpsc::channel(32);
+
+    tokio::task::spawn_blocking(move || {
+        let mut rng = rand::thread_rng();
+        let mut screen = [0_u8; 32 * 3 * 32];
+
+        // checked every instruction, as a program may never change the screen
+        while !frame_tx.is_closed() {
+            while let Ok(key) = input_rx.try_recv() {
+                cpu.bus.write(0xff, key);
+            }
+            cpu.bus.write(0xfe, rng.gen_range(1, 16));
+            cpu.exec();
+
+            let mut events = Vec::new();
+            if cpu.is_jammed() {
+                events.push(Event::Jammed(cpu.pc));
+            }
+            if cpu.halted {
+                events.push(Event::Halted);
+            }
+
+            let done = !events.is_empty();
+            if read_screen_state(&mut cpu, &mut screen) || done {
+                let frame = Frame { screen, events };
+                if frame_tx.blocking_send(frame).is_err() || done {
+                    return;
+                }
+            }
+        }
+    });
+
+    (ReceiverStream::new(frame_rx), input_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use crate::bus::Bus;
+    use crate::stream::*;
+
+    #[tokio::test]
+    async fn yields_frames() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa9, 0x01, // LDA #$01
+            0x8d, 0x00, 0x02, // STA $0200
+            0xa5, 0xff, // LDA $FF
+            0xf0, 0xfc, // BEQ -4
+            0x8d, 0x01, 0x02, // STA $0201
+            0x00, // BRK
+        ]);
+
+        cpu.stop_on_brk = true;
+        let (mut frames, input) = frames(cpu);
+
+        let first = frames.next().await.unwrap();
+        assert_eq!(&first.screen[..3], &[0xff, 0xff, 0xff]);
+        assert!(first.events.is_empty());
+
+        input.send(0x05).await.unwrap();
+        let second = frames.next().await.unwrap();
+        assert_eq!(&second.screen[3..6], &[0x00, 0x00, 0xff]);
+
+        let last = frames.next().await.unwrap();
+        assert_eq!(last.events, vec![Event::Halted]);
+        assert!(frames.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn stops_when_dropped() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![0x4c, 0x00, 0x06]); // JMP $0600, screen never changes
+
+        let (frames, input) = frames(cpu);
+        drop(frames);
+        // the task drops its end of the input channel once it returns
+        let stopped = tokio::time::timeout(std::time::Duration::from_secs(5), input.closed());
+        assert!(stopped.await.is_ok());
+    }
+}
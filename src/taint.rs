@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use crate::bus::{Access, BusCycle};
+use crate::cpu::instructions::Addrmode;
+use crate::cpu::CPU;
+use crate::disasm;
+
+/// Where tainted data came from: the source address it was first read
+/// from, or None for clean data.
+pub type Label = Option<u16>;
+
+/// A branch or jump whose direction or target came from tainted data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Site {
+    pub text: String,
+    /// What decided it: a flag, or "target" for a jump.
+    pub on: &'static str,
+    pub origin: u16,
+    pub seen: u64,
+    pub taken: u64,
+}
+
+/// The instruction about to run, captured by `Taint::capture`.
+struct Pending {
+    pc: u16,
+    opcode: u8,
+    instructions: u64,
+}
+
+/// Shadow state for tracing data read from `source` through registers,
+/// flags and memory to the branches and jumps it decides. Values read
+/// through a tainted index or pointer are tainted too, so table lookups
+/// keyed on input carry it on; a store through one is not.
+pub struct Taint {
+    source: RangeInclusive<u16>,
+    mem: Vec<Label>,
+    a: Label,
+    x: Label,
+    y: Label,
+    n: Label,
+    z: Label,
+    c: Label,
+    v: Label,
+    pending: Option<Pending>,
+    pub sites: BTreeMap<u16, Site>,
+}
+
+impl Taint {
+    pub fn new(source: RangeInclusive<u16>) -> Self {
+        Taint {
+            source,
+            mem: vec![None; 0x10000],
+            a: None,
+            x: None,
+            y: None,
+            n: None,
+            z: None,
+            c: None,
+            v: None,
+            pending: None,
+            sites: BTreeMap::new(),
+        }
+    }
+
+    fn label(&self, adr: u16) -> Label {
+        if self.source.contains(&adr) {
+            Some(adr)
+        } else {
+            self.mem[adr as usize]
+        }
+    }
+
+    /// Notes the instruction at the PC and starts logging the bus, so
+    /// `update` can see what it read and wrote.
+    pub fn capture(&mut self, cpu: &mut CPU) {
+        cpu.bus.start_log();
+        self.pending = Some(Pending {
+            pc: cpu.pc,
+            opcode: cpu.bus.memory.read(cpu.pc),
+            instructions: cpu.instructions,
+        });
+    }
+
+    /// Moves the taint along for the instruction captured last, now that
+    /// it has run.
+    pub fn update(&mut self, cpu: &mut CPU) {
+        // dummy accesses neither carry nor change what's in memory
+        let log: Vec<BusCycle> = cpu
+            .bus
+            .take_log()
+            .into_iter()
+            .filter(|c| !c.dummy)
+            .collect();
+        let Some(p) = self.pending.take() else {
+            return;
+        };
+        let (reads, writes): (Vec<BusCycle>, Vec<BusCycle>) =
+            log.into_iter().partition(|c| c.access == Access::Read);
+
+        // an interrupt or a jam ran instead, only its pushes count
+        if cpu.instructions == p.instructions {
+            for w in writes {
+                self.mem[w.addr as usize] = None;
+            }
+            return;
+        }
+
+        let (name, mode) = disasm::decode(p.opcode);
+        let len = disasm::len(mode);
+        let mut data: Vec<Label> = reads
+            .iter()
+            .filter(|r| r.addr.wrapping_sub(p.pc) >= len)
+            .map(|r| self.label(r.addr))
+            .collect();
+
+        // the pointer and the index taint what is found through them
+        let pointer = match mode {
+            Addrmode::Ind | Addrmode::XInd | Addrmode::IndY => {
+                let ptr: Vec<Label> = data.drain(..2.min(data.len())).collect();
+                ptr[0].or(ptr.get(1).copied().flatten())
+            }
+            _ => None,
+        };
+        let index = match mode {
+            Addrmode::ZpgX | Addrmode::AbsX | Addrmode::XInd => self.x,
+            Addrmode::ZpgY | Addrmode::AbsY | Addrmode::IndY => self.y,
+            _ => None,
+        };
+        let operand = data
+            .iter()
+            .fold(None, |l, d| l.or(*d))
+            .or(pointer)
+            .or(index);
+
+        let mut stored = None;
+        match name {
+            "LDA" | "PLA" => (self.a, self.n, self.z) = (operand, operand, operand),
+            "LDX" => (self.x, self.n, self.z) = (operand, operand, operand),
+            "LDY" => (self.y, self.n, self.z) = (operand, operand, operand),
+            "LAX" | "LAS" => {
+                (self.a, self.x) = (operand, operand);
+                (self.n, self.z) = (operand, operand);
+            }
+            "STA" => stored = self.a,
+            "STX" => stored = self.x,
+            "STY" => stored = self.y,
+            "SAX" | "SHA" | "TAS" => stored = self.a.or(self.x),
+            "SHX" => stored = self.x,
+            "SHY" => stored = self.y,
+            "PHA" => stored = self.a,
+            "PHP" => stored = self.n.or(self.z).or(self.c).or(self.v),
+            "PLP" => (self.n, self.z, self.c, self.v) = (operand, operand, operand, operand),
+            "RTI" => {
+                let flags = data.first().copied().flatten();
+                (self.n, self.z, self.c, self.v) = (flags, flags, flags, flags);
+                let target = data.iter().skip(1).fold(None, |l, d| l.or(*d));
+                self.site(cpu, &p, "target", target, true);
+            }
+            "AND" | "ORA" | "EOR" => {
+                self.a = self.a.or(operand);
+                (self.n, self.z) = (self.a, self.a);
+            }
+            "ANC" | "ALR" | "ARR" => {
+                self.a = self.a.or(operand);
+                (self.n, self.z, self.c) = (self.a, self.a, self.a);
+                if name == "ARR" {
+                    self.v = self.a;
+                }
+            }
+            "ANE" => {
+                self.a = self.a.or(self.x).or(operand);
+                (self.n, self.z) = (self.a, self.a);
+            }
+            "ADC" | "SBC" => {
+                self.a = self.a.or(operand).or(self.c);
+                (self.n, self.z, self.c, self.v) = (self.a, self.a, self.a, self.a);
+            }
+            "CMP" => self.compare(self.a.or(operand)),
+            "CPX" => self.compare(self.x.or(operand)),
+            "CPY" => self.compare(self.y.or(operand)),
+            "BIT" => (self.n, self.v, self.z) = (operand, operand, self.a.or(operand)),
+            "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC" => {
+                let carry = if matches!(name, "ROL" | "ROR") {
+                    self.c
+                } else {
+                    None
+                };
+                let result = if mode == Addrmode::A {
+                    self.a = self.a.or(carry);
+                    self.a
+                } else {
+                    stored = operand.or(carry);
+                    stored
+                };
+                (self.n, self.z) = (result, result);
+                if !matches!(name, "INC" | "DEC") {
+                    self.c = result;
+                }
+            }
+            "SLO" | "RLA" | "SRE" | "RRA" | "ISC" => {
+                let carry = |rotates| if rotates { self.c } else { None };
+                stored = operand.or(carry(matches!(name, "RLA" | "RRA")));
+                self.a = self.a.or(stored).or(carry(matches!(name, "RRA" | "ISC")));
+                (self.n, self.z, self.c, self.v) = (self.a, self.a, self.a, self.a);
+            }
+            "DCP" => {
+                stored = operand;
+                self.compare(self.a.or(stored));
+            }
+            "SBX" => {
+                self.x = self.a.or(self.x).or(operand);
+                (self.n, self.z, self.c) = (self.x, self.x, self.x);
+            }
+            "LXA" => {
+                (self.a, self.x) = (self.a.or(operand), self.a.or(operand));
+                (self.n, self.z) = (self.a, self.a);
+            }
+            "INX" | "DEX" => (self.n, self.z) = (self.x, self.x),
+            "INY" | "DEY" => (self.n, self.z) = (self.y, self.y),
+            "TAX" => (self.x, self.n, self.z) = (self.a, self.a, self.a),
+            "TAY" => (self.y, self.n, self.z) = (self.a, self.a, self.a),
+            "TXA" => (self.a, self.n, self.z) = (self.x, self.x, self.x),
+            "TYA" => (self.a, self.n, self.z) = (self.y, self.y, self.y),
+            "TSX" => (self.x, self.n, self.z) = (None, None, None),
+            "CLC" | "SEC" => self.c = None,
+            "CLV" => self.v = None,
+            "BPL" | "BMI" => self.site(cpu, &p, "N", self.n, false),
+            "BVC" | "BVS" => self.site(cpu, &p, "V", self.v, false),
+            "BCC" | "BCS" => self.site(cpu, &p, "C", self.c, false),
+            "BNE" | "BEQ" => self.site(cpu, &p, "Z", self.z, false),
+            "JMP" => self.site(cpu, &p, "target", pointer, true),
+            "RTS" => self.site(cpu, &p, "target", operand, true),
+            _ => (),
+        }
+
+        for w in writes {
+            self.mem[w.addr as usize] = stored;
+        }
+    }
+
+    fn compare(&mut self, l: Label) {
+        (self.n, self.z, self.c) = (l, l, l);
+    }
+
+    fn site(&mut self, cpu: &CPU, p: &Pending, on: &'static str, l: Label, jump: bool) {
+        let Some(origin) = l else {
+            return;
+        };
+        let (_, mode) = disasm::decode(p.opcode);
+        let taken = jump || cpu.pc != p.pc.wrapping_add(disasm::len(mode));
+        let site = self.sites.entry(p.pc).or_insert_with(|| Site {
+            text: disasm::line(cpu, p.pc).0,
+            on,
+            origin,
+            seen: 0,
+            taken: 0,
+        });
+        site.seen += 1;
+        site.taken += taken as u64;
+    }
+
+    /// Bytes of memory holding tainted data right now.
+    pub fn tainted_bytes(&self) -> usize {
+        self.mem.iter().filter(|l| l.is_some()).count()
+    }
+
+    pub fn report(&self) -> String {
+        if self.sites.is_empty() {
+            return "No branch or jump depended on the taint source\n".to_string();
+        }
+
+        let mut out = String::from("Branches and jumps decided by the taint source:\n");
+        for site in self.sites.values() {
+            let what = if site.on == "target" {
+                format!("target from ${:04X}, {} times", site.origin, site.seen)
+            } else {
+                format!(
+                    "on {} from ${:04X}, taken {} of {} times",
+                    site.on, site.origin, site.taken, site.seen
+                )
+            };
+            out += &format!("   {:28} {}\n", site.text, what);
+        }
+        out += &format!(
+            "{} bytes of memory hold tainted data\n",
+            self.tainted_bytes()
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::taint::*;
+
+    #[test]
+    fn follows_input_to_branches() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.load(vec![
+            0xa5, 0xff, // $0600 LDA $FF
+            0x29, 0x03, // $0602 AND #$03
+            0xaa, // $0604 TAX
+            0xbd, 0x00, 0x07, // $0605 LDA $0700,X
+            0x85, 0x10, // $0608 STA $10
+            0xa5, 0x10, // $060A LDA $10
+            0xf0, 0x02, // $060C BEQ $0610
+            0xa0, 0x01, // $060E LDY #$01
+            0xc0, 0x01, // $0610 CPY #$01
+            0xd0, 0x00, // $0612 BNE $0614
+            0x00, // $0614 BRK
+        ]);
+        cpu.bus.write(0x00ff, 0x77);
+        cpu.stop_on_brk = true;
+
+        let mut taint = Taint::new(0x00ff..=0x00ff);
+        taint.capture(&mut cpu);
+        cpu.run(|cpu| {
+            taint.update(cpu);
+            taint.capture(cpu);
+        });
+
+        // the lookup through X carries the key on, the Y compare is clean
+        assert_eq!(taint.sites.keys().collect::<Vec<_>>(), [&0x060c]);
+        let site = &taint.sites[&0x060c];
+        assert_eq!((site.on, site.origin), ("Z", 0x00ff));
+        assert_eq!((site.seen, site.taken), (1, 1));
+        assert_eq!(taint.tainted_bytes(), 1);
+        assert_eq!(
+            taint.report().lines().nth(1),
+            Some("   $060C  F0 02     BEQ $0610   on Z from $00FF, taken 1 of 1 times")
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::io::{self, Read, Write};
+
+use rand::Rng;
+
+use crate::cheats::{self, Cheat};
+use crate::cpu::{ExitReason, CPU};
+use crate::screen::{Display, FrameSource, PixelFormat, Screen, HEIGHT, WIDTH};
+use crate::soak::CYCLES_PER_FRAME;
+
+/// Puts the terminal into non-blocking raw mode for as long as it lives.
+struct RawTerminal {
+    saved: libc::termios,
+}
+
+impl RawTerminal {
+    fn enable() -> io::Result<RawTerminal> {
+        unsafe {
+            let mut saved = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut raw = saved;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(RawTerminal { saved })
+        }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved);
+        }
+        // show the cursor and reset colours
+        println!("\x1b[?25h\x1b[0m");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Draws an RGB24 image with one "▀" per two pixel rows, the top pixel as
+/// the foreground colour and the bottom one as the background.
+pub fn render_ansi(rgb: &[u8], w: usize, h: usize) -> String {
+    let mut out = String::from("\x1b[H");
+    for y in (0..h).step_by(2) {
+        for x in 0..w {
+            let top = &rgb[(y * w + x) * 3..][..3];
+            let bottom = if y + 1 < h {
+                &rgb[((y + 1) * w + x) * 3..][..3]
+            } else {
+                &[0, 0, 0]
+            };
+            out += &format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+                top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+            );
+        }
+        out += "\x1b[0m\n";
+    }
+    out
+}
+
+/// Draws an RGB24 image as braille characters, one per 2x4 pixel cell. A dot
+/// is set for every non-black pixel and the cell takes the colour of its
+/// brightest pixel.
+pub fn render_braille(rgb: &[u8], w: usize, h: usize) -> String {
+    // dot bit for each (x, y) inside the cell
+    const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+    let mut out = String::from("\x1b[H");
+    for cy in (0..h).step_by(4) {
+        for cx in (0..w).step_by(2) {
+            let mut bits = 0;
+            let mut color = [0_u8; 3];
+            for (dx, column) in DOTS.iter().enumerate() {
+                for (dy, bit) in column.iter().enumerate() {
+                    let (x, y) = (cx + dx, cy + dy);
+                    if x >= w || y >= h {
+                        continue;
+                    }
+
+                    let px = &rgb[(y * w + x) * 3..][..3];
+                    let luma = |c: &[u8]| c.iter().map(|&v| v as u16).sum::<u16>();
+                    if luma(px) > 0 {
+                        bits |= bit;
+                    }
+                    if luma(px) > luma(&color) {
+                        color.copy_from_slice(px);
+                    }
+                }
+            }
+
+            let ch = char::from_u32(0x2800 + bits).unwrap();
+            out += &format!("\x1b[38;2;{};{};{}m{}", color[0], color[1], color[2], ch);
+        }
+        out += "\x1b[0m\n";
+    }
+    out
+}
+
+/// Pixels per display pixel in sixel output.
+const SIXEL_SCALE: usize = 8;
+
+/// Draws an RGB24 image as DEC sixel graphics, scaled up by SIXEL_SCALE.
+pub fn render_sixel(rgb: &[u8], w: usize, h: usize) -> String {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let indices: Vec<usize> = rgb
+        .chunks_exact(3)
+        .take(w * h)
+        .map(|px| {
+            let px = [px[0], px[1], px[2]];
+            palette.iter().position(|&c| c == px).unwrap_or_else(|| {
+                palette.push(px);
+                palette.len() - 1
+            })
+        })
+        .collect();
+
+    let mut out = String::from("\x1b[H\x1bPq");
+    for (i, c) in palette.iter().enumerate() {
+        let pct = |v: u8| v as u32 * 100 / 255;
+        out += &format!("#{};2;{};{};{}", i, pct(c[0]), pct(c[1]), pct(c[2]));
+    }
+
+    let (sw, sh) = (w * SIXEL_SCALE, h * SIXEL_SCALE);
+    for band in (0..sh).step_by(6) {
+        for color in 0..palette.len() {
+            let mut row = Vec::with_capacity(sw);
+            for sx in 0..sw {
+                let mut bits = 0;
+                for dy in 0..6 {
+                    let sy = band + dy;
+                    if sy < sh && indices[(sy / SIXEL_SCALE) * w + sx / SIXEL_SCALE] == color {
+                        bits |= 1 << dy;
+                    }
+                }
+                row.push((63 + bits) as u8 as char);
+            }
+
+            if row.iter().all(|&c| c == '?') {
+                continue;
+            }
+
+            out += &format!("#{}", color);
+            // run-length encode repeated columns
+            let mut x = 0;
+            while x < row.len() {
+                let run = row[x..].iter().take_while(|&&c| c == row[x]).count();
+                if run > 3 {
+                    out += &format!("!{}{}", run, row[x]);
+                } else {
+                    out.extend(std::iter::repeat_n(row[x], run));
+                }
+                x += run;
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out + "\x1b\\"
+}
+
+/// Turns an RGB24 frame into terminal output.
+pub type Renderer = fn(&[u8], usize, usize) -> String;
+
+/// Runs the easy6502 machine in the terminal. WASD go to $FF like in the
+/// SDL front-end; q or Ctrl-C quits.
+pub fn run(cpu: &mut CPU, cheat_list: &[Cheat], render: Renderer) -> io::Result<ExitReason> {
+    let raw = RawTerminal::enable()?;
+    print!("\x1b[2J\x1b[?25l");
+
+    let mut display = Display::new(PixelFormat::Rgb24);
+    let mut rng = rand::thread_rng();
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut key = [0_u8; 1];
+
+    let mut patches = cheats::Patches::default();
+    let mut shown = None;
+
+    let reason = cpu.run(|cpu| {
+        if let Ok(1) = stdin.read(&mut key) {
+            match key[0] {
+                b'q' | 0x03 => cpu.halted = true,
+                k => cpu.bus.write(0xff, k),
+            }
+        }
+        cpu.bus.write(0xfe, rng.gen_range(1, 16));
+        patches.apply(cheat_list, cpu);
+
+        // read once a frame of cycles, as the SDL front-end does
+        let frame = cpu.cycles / CYCLES_PER_FRAME;
+        if shown != Some(frame) || cpu.halted || cpu.is_jammed() {
+            shown = Some(frame);
+            display.update(cpu);
+        }
+        if display.take_dirty() {
+            let _ = stdout.write_all(render(display.pixels(), WIDTH, HEIGHT).as_bytes());
+            let _ = stdout.flush();
+        }
+
+        ::std::thread::sleep(std::time::Duration::new(0, 70_000));
+    });
+
+    drop(raw);
+    Ok(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terminal::*;
+
+    #[test]
+    fn half_blocks() {
+        let rgb = [
+            255, 0, 0, //
+            0, 255, 0, //
+        ];
+
+        assert_eq!(
+            render_ansi(&rgb, 1, 2),
+            "\x1b[H\x1b[38;2;255;0;0m\x1b[48;2;0;255;0m▀\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn braille_dots() {
+        // 2x4 cell with only the left column lit, brightest pixel in the middle
+        let mut rgb = [0_u8; 2 * 4 * 3];
+        for y in 0..4 {
+            rgb[y * 2 * 3] = 100;
+        }
+        rgb[2 * 2 * 3 + 1] = 200;
+
+        assert_eq!(
+            render_braille(&rgb, 2, 4),
+            "\x1b[H\x1b[38;2;100;200;0m\u{2847}\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn sixel_bands() {
+        let rgb = [255, 255, 255];
+        let out = render_sixel(&rgb, 1, 1);
+
+        assert!(out.starts_with("\x1b[H\x1bPq#0;2;100;100;100"));
+        // 8 rows: one full band of 6, then 2 rows
+        assert!(out.contains("#0!8~$-#0!8B$-"));
+        assert!(out.ends_with("\x1b\\"));
+    }
+}
@@ -0,0 +1,328 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::bus::Bus;
+use crate::cartridge::Cartridge;
+use crate::cpu::{Magic, CPU};
+use crate::script;
+
+// blargg's test ROMs report through $6000: a status byte, then the DE B0 61
+// signature, then NUL-terminated text from $6004.
+const BLARGG_STATUS: u16 = 0x6000;
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_TEXT: u16 = 0x6004;
+const BLARGG_RUNNING: u8 = 0x80;
+const BLARGG_NEEDS_RESET: u8 = 0x81;
+
+// Klaus Dormann's functional test is a flat 64K image entered at $0400. It
+// ends in a jump-to-self, at the success address if every test passed.
+const KLAUS_SIZE: usize = 0x10000;
+const KLAUS_ENTRY: u16 = 0x0400;
+pub const KLAUS_SUCCESS: u16 = 0x3469;
+
+pub struct Outcome {
+    pub name: String,
+    pub passed: bool,
+    pub status: String,
+    pub output: String,
+    pub cycles: u64,
+}
+
+fn blargg_status(cpu: &mut CPU) -> Option<u8> {
+    let sig = [
+        cpu.bus.memory.read(BLARGG_STATUS + 1),
+        cpu.bus.memory.read(BLARGG_STATUS + 2),
+        cpu.bus.memory.read(BLARGG_STATUS + 3),
+    ];
+
+    (sig == BLARGG_SIGNATURE).then(|| cpu.bus.memory.read(BLARGG_STATUS))
+}
+
+fn blargg_text(cpu: &mut CPU) -> String {
+    let text: Vec<u8> = (BLARGG_TEXT..0x7000)
+        .map(|adr| cpu.bus.memory.read(adr))
+        .take_while(|&b| b != 0)
+        .collect();
+
+    String::from_utf8_lossy(&text).trim().to_string()
+}
+
+fn run_blargg(cpu: &mut CPU, max_cycles: u64) -> (bool, String) {
+    let mut reset_at = None;
+    while cpu.cycles < max_cycles && !cpu.halted && !cpu.is_jammed() {
+        cpu.exec();
+
+        match blargg_status(cpu) {
+            Some(BLARGG_RUNNING) | None => (),
+            Some(BLARGG_NEEDS_RESET) => {
+                // the ROM wants at least 100ms before the reset
+                let at = *reset_at.get_or_insert(cpu.cycles + 180_000);
+                if cpu.cycles >= at {
+                    reset_at = None;
+                    cpu.bus.memory.write(BLARGG_STATUS, BLARGG_RUNNING);
+                    cpu.reset();
+                }
+            }
+            Some(code) => return (code == 0, format!("result {}", code)),
+        }
+    }
+
+    (false, stop_reason(cpu))
+}
+
+fn run_klaus(cpu: &mut CPU, max_cycles: u64, success: u16) -> (bool, String) {
+    while cpu.cycles < max_cycles && !cpu.halted && !cpu.is_jammed() {
+        let pc = cpu.pc;
+        cpu.exec();
+
+        if cpu.pc == pc {
+            return (pc == success, format!("trapped at ${:04X}", pc));
+        }
+    }
+
+    (false, stop_reason(cpu))
+}
+
+fn stop_reason(cpu: &CPU) -> String {
+    if cpu.is_jammed() {
+        format!("jammed at ${:04X}", cpu.pc)
+    } else if cpu.halted {
+        format!("halted at ${:04X}", cpu.pc)
+    } else {
+        "timed out".to_string()
+    }
+}
+
+/// Runs a single test ROM, picking the harness from the image format.
+pub fn run_rom(path: &Path, max_cycles: u64) -> io::Result<Outcome> {
+    let data = fs::read(path)?;
+    let mut cpu = CPU::new(Bus::default());
+
+    let blargg = Cartridge::is_ines(&data);
+    if blargg {
+        Cartridge::from_ines(&data)?.insert(&mut cpu);
+        // blargg's ROMs expect LXA to behave as on the NES's 2A03
+        cpu.magic = Magic::Fixed(0xFF);
+    } else if data.len() == KLAUS_SIZE {
+        cpu.bus.memory.load(0, &data);
+        cpu.pc = KLAUS_ENTRY;
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an iNES image or a 64K test binary",
+        ));
+    }
+
+    let (passed, status) = if blargg {
+        run_blargg(&mut cpu, max_cycles)
+    } else {
+        run_klaus(&mut cpu, max_cycles, KLAUS_SUCCESS)
+    };
+
+    Ok(Outcome {
+        name: path.file_name().unwrap().to_string_lossy().to_string(),
+        passed,
+        status,
+        output: if blargg {
+            blargg_text(&mut cpu)
+        } else {
+            String::new()
+        },
+        cycles: cpu.cycles,
+    })
+}
+
+/// Maps `f` over `items` on `jobs` threads (all cores if None), each item
+/// handed to whichever thread is free next. Results keep the input order.
+pub fn par_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: Option<usize>,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let jobs = jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let done = Mutex::new(Vec::with_capacity(items.len()));
+
+    thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else { break };
+                let r = f(item);
+                done.lock().unwrap().push((i, r));
+            });
+        }
+    });
+
+    let mut done = done.into_inner().unwrap();
+    done.sort_by_key(|(i, _)| *i);
+    done.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Runs a test ROM, or a `.toml` test script through `script::run`.
+pub fn run_file(path: &Path, max_cycles: u64) -> io::Result<Outcome> {
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        script::run(path, max_cycles)
+    } else {
+        run_rom(path, max_cycles)
+    }
+}
+
+/// Runs every .nes/.bin ROM and .toml script in `dir`, sorted by name, one
+/// per thread. `dir` may also be a single ROM or script.
+pub fn run_dir(dir: &Path, max_cycles: u64, jobs: Option<usize>) -> io::Result<Vec<Outcome>> {
+    if dir.is_file() {
+        return Ok(vec![run_file(dir, max_cycles)?]);
+    }
+
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == "nes" || ext == "bin" || ext == "toml")
+        })
+        .collect();
+    paths.sort();
+
+    par_map(&paths, jobs, |p| run_file(p, max_cycles))
+        .into_iter()
+        .collect()
+}
+
+pub fn print_report(outcomes: &[Outcome]) {
+    let width = outcomes.iter().map(|o| o.name.len()).max().unwrap_or(4);
+    println!("{:width$}  {:6}  {:>12}  STATUS", "ROM", "RESULT", "CYCLES");
+    for o in outcomes {
+        println!(
+            "{:width$}  {:6}  {:>12}  {}",
+            o.name,
+            if o.passed { "pass" } else { "FAIL" },
+            o.cycles,
+            o.status
+        );
+        if !o.passed && !o.output.is_empty() {
+            for line in o.output.lines() {
+                println!("{:width$}  {}", "", line);
+            }
+        }
+    }
+
+    let passed = outcomes.iter().filter(|o| o.passed).count();
+    println!("\n{}/{} passed", passed, outcomes.len());
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn junit_xml(outcomes: &[Outcome]) -> String {
+    let failures = outcomes.iter().filter(|o| !o.passed).count();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuite name=\"rusty6502\" tests=\"{}\" failures=\"{}\">\n",
+        outcomes.len(),
+        failures
+    );
+
+    for o in outcomes {
+        xml += &format!("  <testcase name=\"{}\">\n", xml_escape(&o.name));
+        if !o.passed {
+            xml += &format!(
+                "    <failure message=\"{}\">{}</failure>\n",
+                xml_escape(&o.status),
+                xml_escape(&o.output)
+            );
+        }
+        xml += "  </testcase>\n";
+    }
+
+    xml + "</testsuite>\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testrom::*;
+
+    #[test]
+    fn junit_report() {
+        let outcomes = vec![
+            Outcome {
+                name: "a.nes".to_string(),
+                passed: true,
+                status: "result 0".to_string(),
+                output: String::new(),
+                cycles: 10,
+            },
+            Outcome {
+                name: "b.nes".to_string(),
+                passed: false,
+                status: "result 2".to_string(),
+                output: "ADC <failed>".to_string(),
+                cycles: 20,
+            },
+        ];
+
+        let xml = junit_xml(&outcomes);
+        assert!(xml.contains("tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("<failure message=\"result 2\">ADC &lt;failed&gt;</failure>"));
+    }
+
+    #[test]
+    fn par_map_keeps_order() {
+        let items: Vec<u64> = (0..50).collect();
+        assert_eq!(
+            par_map(&items, Some(4), |&i| i * 2),
+            (0..100).step_by(2).collect::<Vec<_>>()
+        );
+        assert!(par_map(&[] as &[u8], None, |&i| i).is_empty());
+    }
+
+    #[test]
+    fn klaus_trap() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.memory.load(0x0400, &[0x4c, 0x69, 0x34]); // JMP $3469
+        cpu.bus.memory.load(0x3469, &[0x4c, 0x69, 0x34]);
+        cpu.pc = KLAUS_ENTRY;
+
+        assert_eq!(
+            run_klaus(&mut cpu, 1000, KLAUS_SUCCESS),
+            (true, "trapped at $3469".to_string())
+        );
+    }
+
+    /// One test per ROM in test_roms/, each run as `nesemu test` would.
+    macro_rules! rom_tests {
+        ($($name:ident: $rom:expr,)*) => {
+            $(
+                #[test]
+                fn $name() {
+                    let path = Path::new("./test_roms/").join($rom);
+                    let outcome = run_rom(&path, 100_000_000).unwrap();
+                    assert!(outcome.passed, "{}: {}", outcome.status, outcome.output);
+                }
+            )*
+        };
+    }
+
+    rom_tests! {
+        implied: "01-implied.nes",
+        immediate: "02-immediate.nes",
+        zero_page: "03-zero_page.nes",
+        zp_xy: "04-zp_xy.nes",
+        absolute: "05-absolute.nes",
+        abs_xy: "06-abs_xy.nes",
+        ind_x: "07-ind_x.nes",
+        ind_y: "08-ind_y.nes",
+        branches: "09-branches.nes",
+        stack: "10-stack.nes",
+        special: "11-special.nes",
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+
+use crate::cpu::CPU;
+use crate::screen::{self, FrameSource, PixelFormat, Screen};
+
+/// Pixels per glyph side; character ROMs hold 8 bytes per glyph, MSB first.
+pub const GLYPH: usize = 8;
+
+// Palette entries glyphs are drawn with.
+const BACKGROUND: u8 = 0;
+const FOREGROUND: u8 = 1;
+
+/// Where the text screen lives: `cols` x `rows` character codes, row after
+/// row, starting at `base`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextLayout {
+    pub base: u16,
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl std::str::FromStr for TextLayout {
+    type Err = String;
+
+    /// COLSxROWS@BASE, base in hex, e.g. 80x25@0400.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("expected COLSxROWS@BASE: {}", s);
+        let (size, base) = s.split_once('@').ok_or_else(bad)?;
+        let (cols, rows) = size.split_once('x').ok_or_else(bad)?;
+
+        let layout = TextLayout {
+            base: u16::from_str_radix(base.trim_start_matches('$'), 16).map_err(|_| bad())?,
+            cols: cols.parse().map_err(|_| bad())?,
+            rows: rows.parse().map_err(|_| bad())?,
+        };
+        if layout.cols == 0 || layout.rows == 0 {
+            return Err(bad());
+        }
+        Ok(layout)
+    }
+}
+
+/// A character-cell display drawn with glyphs from a character ROM. Codes
+/// past the end of the ROM wrap around to its start.
+pub struct TextDisplay {
+    layout: TextLayout,
+    glyphs: Vec<u8>,
+    format: PixelFormat,
+    buffer: Vec<u8>,
+    dirty: bool,
+    codes: Vec<u8>,
+    latest: Vec<u8>,
+    indices: Vec<u8>,
+}
+
+impl TextDisplay {
+    pub fn new(layout: TextLayout, glyphs: Vec<u8>, format: PixelFormat) -> io::Result<Self> {
+        if glyphs.is_empty() || !glyphs.len().is_multiple_of(GLYPH) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("character ROM must be a multiple of {} bytes", GLYPH),
+            ));
+        }
+
+        let pixels = layout.cols * layout.rows * GLYPH * GLYPH;
+        Ok(TextDisplay {
+            layout,
+            glyphs,
+            format,
+            buffer: vec![0; pixels * format.bytes_per_pixel()],
+            dirty: true,
+            // never a valid first read, so the first update always draws
+            codes: Vec::new(),
+            latest: vec![0; layout.cols * layout.rows],
+            indices: vec![BACKGROUND; pixels],
+        })
+    }
+
+    pub fn from_file(layout: TextLayout, path: &str, format: PixelFormat) -> io::Result<Self> {
+        TextDisplay::new(layout, fs::read(path)?, format)
+    }
+
+    fn draw(&mut self) {
+        let count = self.glyphs.len() / GLYPH;
+        let width = self.layout.cols * GLYPH;
+
+        for (cell, &code) in self.codes.iter().enumerate() {
+            let glyph = &self.glyphs[(code as usize % count) * GLYPH..][..GLYPH];
+            let (col, row) = (cell % self.layout.cols, cell / self.layout.cols);
+
+            for (y, bits) in glyph.iter().enumerate() {
+                let start = (row * GLYPH + y) * width + col * GLYPH;
+                for (x, px) in self.indices[start..start + GLYPH].iter_mut().enumerate() {
+                    *px = if bits & (0x80 >> x) != 0 {
+                        FOREGROUND
+                    } else {
+                        BACKGROUND
+                    };
+                }
+            }
+        }
+
+        screen::convert(&self.indices, self.format, &mut self.buffer);
+    }
+}
+
+impl Screen for TextDisplay {
+    fn update(&mut self, cpu: &mut CPU) {
+        cpu.bus.memory.read_into(self.layout.base, &mut self.latest);
+        if self.latest != self.codes {
+            self.codes.clone_from(&self.latest);
+            self.draw();
+            self.dirty = true;
+        }
+    }
+}
+
+impl FrameSource for TextDisplay {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.layout.cols * GLYPH, self.layout.rows * GLYPH)
+    }
+
+    fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    fn pixels(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::text::*;
+
+    #[test]
+    fn draws_glyphs() {
+        assert_eq!(
+            "80x25@0400".parse(),
+            Ok(TextLayout {
+                base: 0x400,
+                cols: 80,
+                rows: 25
+            })
+        );
+        assert!("80x0@0400".parse::<TextLayout>().is_err());
+
+        // glyph 0 blank, glyph 1 a left-hand bar
+        let mut rom = vec![0; 2 * GLYPH];
+        rom[GLYPH..].fill(0x80);
+
+        let layout = TextLayout {
+            base: 0x400,
+            cols: 2,
+            rows: 1,
+        };
+        let mut text = TextDisplay::new(layout, rom, PixelFormat::Indexed).unwrap();
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.write(0x401, 3); // wraps to glyph 1
+
+        text.update(&mut cpu);
+        assert!(text.take_dirty());
+        assert_eq!(text.dimensions(), (16, 8));
+        for row in text.pixels().chunks(16) {
+            assert_eq!(row, &[0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        }
+
+        text.update(&mut cpu);
+        assert!(!text.take_dirty());
+
+        assert!(TextDisplay::new(layout, vec![0; 5], PixelFormat::Indexed).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+/// Side of one d-pad button as a fraction of the window height.
+pub const BUTTON: f32 = 0.12;
+
+/// The keys of the d-pad and their cells in a 3x3 grid.
+const KEYS: [(u8, f32, f32); 4] = [
+    (b'w', 1.0, 0.0),
+    (b'a', 0.0, 1.0),
+    (b'd', 2.0, 1.0),
+    (b's', 1.0, 2.0),
+];
+
+/// The on-screen d-pad in the bottom-left corner of a window `aspect` times
+/// as wide as it is tall. Each button is its key and its rectangle
+/// (x, y, w, h) in window coordinates running 0..1, the same coordinates
+/// SDL gives finger events in, so the buttons stay square.
+pub fn buttons(aspect: f32) -> impl Iterator<Item = (u8, [f32; 4])> {
+    let (w, h) = (BUTTON / aspect, BUTTON);
+    let (left, top) = (w / 2.0, 1.0 - 3.5 * h);
+    KEYS.iter()
+        .map(move |&(key, col, row)| (key, [left + col * w, top + row * h, w, h]))
+}
+
+/// The key under a touch at (`x`, `y`), if any.
+pub fn key_at(aspect: f32, x: f32, y: f32) -> Option<u8> {
+    buttons(aspect)
+        .find(|(_, [bx, by, w, h])| (*bx..bx + w).contains(&x) && (*by..by + h).contains(&y))
+        .map(|(key, _)| key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::touch::*;
+
+    #[test]
+    fn dpad_hits() {
+        // square window: the pad spans x 0.06..0.42, y 0.58..0.94
+        assert_eq!(key_at(1.0, 0.24, 0.64), Some(b'w'));
+        assert_eq!(key_at(1.0, 0.10, 0.76), Some(b'a'));
+        assert_eq!(key_at(1.0, 0.38, 0.76), Some(b'd'));
+        assert_eq!(key_at(1.0, 0.24, 0.90), Some(b's'));
+        assert_eq!(key_at(1.0, 0.24, 0.76), None);
+        assert_eq!(key_at(1.0, 0.90, 0.10), None);
+
+        // twice as wide: same height, half the width
+        assert_eq!(key_at(2.0, 0.19, 0.76), Some(b'd'));
+        assert_eq!(key_at(2.0, 0.38, 0.76), None);
+    }
+}
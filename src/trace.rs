@@ -0,0 +1,227 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Where a CPU's instruction trace goes, see `CPU::trace`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Destination {
+    Off,
+    Stdout,
+    /// A file path, `{id}` in it replaced by the instance's number so
+    /// machines running side by side each get their own file
+    File(String),
+}
+
+/// `off`, `stdout` (or `-`), or a path template.
+impl std::str::FromStr for Destination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("expected off, stdout or a path".to_string()),
+            "off" => Ok(Destination::Off),
+            "stdout" | "-" => Ok(Destination::Stdout),
+            path => Ok(Destination::File(path.to_string())),
+        }
+    }
+}
+
+/// When a trace file is moved aside, and how many old ones are kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation {
+    pub max_bytes: u64,
+    pub keep: usize,
+}
+
+impl Destination {
+    /// A writer for instance `id`, or None when tracing is off. Files are
+    /// appended to, and rotated as `rotation` says if given.
+    pub fn open(
+        &self,
+        id: usize,
+        rotation: Option<Rotation>,
+    ) -> io::Result<Option<Box<dyn Write + Send>>> {
+        Ok(match self {
+            Destination::Off => None,
+            Destination::Stdout => Some(Box::new(io::stdout())),
+            Destination::File(template) => {
+                let path = PathBuf::from(template.replace("{id}", &id.to_string()));
+                Some(match rotation {
+                    Some(r) => Box::new(Rotating::open(path, r.max_bytes, r.keep)?),
+                    None => Box::new(append(&path)?),
+                })
+            }
+        })
+    }
+}
+
+fn append(path: &PathBuf) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// A file that is moved aside to PATH.1 once it holds `max_bytes`, the
+/// older ones to PATH.2 and so on up to `keep`, and started again empty.
+/// A write is never split between files, so a line written whole stays
+/// whole.
+pub struct Rotating {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl Rotating {
+    pub fn open(path: PathBuf, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = append(&path)?;
+        Ok(Rotating {
+            written: file.metadata()?.len(),
+            path,
+            file,
+            max_bytes,
+            keep,
+        })
+    }
+
+    fn backup(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..self.keep).rev() {
+            match fs::rename(self.backup(n), self.backup(n + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, self.backup(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for Rotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        // all of it, so a line can't straddle a rotation
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// A trace kept in memory, for tests and tools that read it back. Clones
+/// share the buffer, so one can go to the CPU and one be kept.
+#[derive(Clone, Default)]
+pub struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::CPU;
+    use crate::trace::*;
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = std::env::temp_dir().join("rusty6502-trace");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let template = dir.join("cpu{id}.log").to_str().unwrap().to_string();
+
+        let dest: Destination = template.parse().unwrap();
+        let rotation = Rotation {
+            max_bytes: 16,
+            keep: 2,
+        };
+        let mut w = dest.open(3, Some(rotation)).unwrap().unwrap();
+        for line in [
+            "0600|A9\n",
+            "0602|85\n",
+            "0604|E8\n",
+            "0605|4C\n",
+            "0600|A9\n",
+            "0602|85\n",
+            "0604|E8\n",
+        ] {
+            w.write_all(line.as_bytes()).unwrap();
+        }
+        drop(w);
+
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("cpu3.log"), "0604|E8\n");
+        assert_eq!(read("cpu3.log.1"), "0600|A9\n0602|85\n");
+        assert_eq!(read("cpu3.log.2"), "0604|E8\n0605|4C\n");
+        assert!(!dir.join("cpu3.log.3").exists());
+
+        assert_eq!("-".parse(), Ok(Destination::Stdout));
+        assert!(Destination::Off.open(0, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn traces_to_memory() {
+        let buffer = Buffer::default();
+        let mut cpu = CPU::new(Bus::default());
+        cpu.trace = Some(Box::new(buffer.clone()));
+        cpu.load(vec![
+            0xa9, 0x01, // $0600 LDA #$01
+            0xe8, // $0602 INX
+        ]);
+        cpu.exec();
+        cpu.exec();
+        assert_eq!(buffer.contents(), "600|A9\n602|E8\n");
+    }
+
+    struct Full;
+
+    impl Write for Full {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_trace_is_dropped() {
+        let mut cpu = CPU::new(Bus::default());
+        cpu.trace = Some(Box::new(Full));
+        cpu.load(vec![0xe8, 0xe8]); // INX, INX
+        cpu.exec();
+        cpu.exec();
+
+        assert_eq!(cpu.reg.x, 2);
+        assert!(cpu.trace.is_none());
+        assert_eq!(cpu.trace_error.unwrap().to_string(), "disk full");
+    }
+}
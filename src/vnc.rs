@@ -0,0 +1,390 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::cheats::{self, Cheat};
+use crate::cpu::{ExitReason, CPU};
+use crate::filter::{self, Filter};
+use crate::screen::{Display, FrameSource, PixelFormat as Format, Screen, HEIGHT, WIDTH};
+use crate::soak::CYCLES_PER_FRAME;
+
+const FB_WIDTH: usize = WIDTH * filter::SCALE;
+const FB_HEIGHT: usize = HEIGHT * filter::SCALE;
+
+/// The latest upscaled RGB24 frame, shared between the CPU and the clients.
+#[derive(Default)]
+struct Framebuffer {
+    rgb: Vec<u8>,
+    generation: u64,
+}
+
+/// An RFB pixel format. Only true-colour formats are honoured.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PixelFormat {
+    bits_per_pixel: u8,
+    big_endian: bool,
+    max: [u16; 3],
+    shift: [u8; 3],
+}
+
+impl PixelFormat {
+    const DEFAULT: PixelFormat = PixelFormat {
+        bits_per_pixel: 32,
+        big_endian: false,
+        max: [255, 255, 255],
+        shift: [16, 8, 0],
+    };
+
+    fn to_bytes(self) -> [u8; 16] {
+        let mut b = [0; 16];
+        b[0] = self.bits_per_pixel;
+        b[1] = 24;
+        b[2] = self.big_endian as u8;
+        b[3] = 1;
+        for i in 0..3 {
+            b[4 + i * 2..][..2].copy_from_slice(&self.max[i].to_be_bytes());
+        }
+        b[10..13].copy_from_slice(&self.shift);
+        b
+    }
+
+    fn from_bytes(b: &[u8; 16]) -> Option<PixelFormat> {
+        let max = |i: usize| u16::from_be_bytes([b[4 + i * 2], b[5 + i * 2]]);
+        match (b[0], b[3]) {
+            (8 | 16 | 32, 1) => Some(PixelFormat {
+                bits_per_pixel: b[0],
+                big_endian: b[2] != 0,
+                max: [max(0), max(1), max(2)],
+                shift: [b[10], b[11], b[12]],
+            }),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, rgb: &[u8], out: &mut Vec<u8>) {
+        let mut v = 0_u32;
+        for ((&c, &max), &shift) in rgb.iter().zip(&self.max).zip(&self.shift) {
+            v |= (c as u32 * max as u32 / 255) << shift;
+        }
+
+        let bytes = (self.bits_per_pixel / 8) as usize;
+        if self.big_endian {
+            out.extend_from_slice(&v.to_be_bytes()[4 - bytes..]);
+        } else {
+            out.extend_from_slice(&v.to_le_bytes()[..bytes]);
+        }
+    }
+}
+
+fn read_u16(stream: &mut TcpStream) -> io::Result<u16> {
+    let mut b = [0; 2];
+    stream.read_exact(&mut b)?;
+    Ok(u16::from_be_bytes(b))
+}
+
+fn read_u32(stream: &mut TcpStream) -> io::Result<u32> {
+    let mut b = [0; 4];
+    stream.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
+}
+
+fn skip(stream: &mut TcpStream, n: u64) -> io::Result<()> {
+    io::copy(&mut stream.take(n), &mut io::sink())?;
+    Ok(())
+}
+
+/// RFB 3.8 handshake with no authentication, or 3.7 or 3.3 if that is what
+/// the client speaks.
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    stream.write_all(b"RFB 003.008\n")?;
+    let mut version = [0; 12];
+    stream.read_exact(&mut version)?;
+
+    if &version[..] == b"RFB 003.003\n" {
+        // 3.3 clients get told the security type instead of choosing it
+        stream.write_all(&1_u32.to_be_bytes())?;
+    } else {
+        stream.write_all(&[1, 1])?;
+        let mut chosen = [0; 1];
+        stream.read_exact(&mut chosen)?;
+        // before 3.8 only VNC authentication ends in a SecurityResult
+        if &version[..] != b"RFB 003.007\n" {
+            stream.write_all(&0_u32.to_be_bytes())?;
+        }
+    }
+
+    // ClientInit: shared flag, ignored since every client shares
+    let mut shared = [0; 1];
+    stream.read_exact(&mut shared)?;
+
+    let name = b"rusty6502";
+    let mut init = Vec::new();
+    init.extend_from_slice(&(FB_WIDTH as u16).to_be_bytes());
+    init.extend_from_slice(&(FB_HEIGHT as u16).to_be_bytes());
+    init.extend_from_slice(&PixelFormat::DEFAULT.to_bytes());
+    init.extend_from_slice(&(name.len() as u32).to_be_bytes());
+    init.extend_from_slice(name);
+    stream.write_all(&init)
+}
+
+/// Sends the whole framebuffer as one raw rectangle.
+fn send_update(stream: &mut TcpStream, format: &PixelFormat, rgb: &[u8]) -> io::Result<()> {
+    let mut msg = vec![0, 0, 0, 1];
+    for v in [0, 0, FB_WIDTH as u16, FB_HEIGHT as u16] {
+        msg.extend_from_slice(&v.to_be_bytes());
+    }
+    msg.extend_from_slice(&0_i32.to_be_bytes());
+    for px in rgb.chunks_exact(3) {
+        format.encode(px, &mut msg);
+    }
+    stream.write_all(&msg)
+}
+
+/// Talks to one client until it disconnects.
+fn serve(mut stream: TcpStream, fb: Arc<Mutex<Framebuffer>>, keys: Sender<u8>) -> io::Result<()> {
+    handshake(&mut stream)?;
+
+    let mut format = PixelFormat::DEFAULT;
+    // Some(incremental) while the client is waiting for an update
+    let mut pending = None;
+    let mut sent = None;
+
+    loop {
+        stream.set_read_timeout(Some(Duration::from_millis(20)))?;
+        let mut kind = [0; 1];
+        let message = match stream.read(&mut kind) {
+            Ok(0) => return Ok(()),
+            Ok(_) => Some(kind[0]),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        stream.set_read_timeout(None)?;
+
+        match message {
+            // SetPixelFormat
+            Some(0) => {
+                let mut b = [0; 19];
+                stream.read_exact(&mut b)?;
+                if let Some(f) = PixelFormat::from_bytes(b[3..].try_into().unwrap()) {
+                    format = f;
+                }
+            }
+            // SetEncodings: everything supports raw
+            Some(2) => {
+                skip(&mut stream, 1)?;
+                let n = read_u16(&mut stream)?;
+                skip(&mut stream, n as u64 * 4)?;
+            }
+            // FramebufferUpdateRequest
+            Some(3) => {
+                let mut b = [0; 9];
+                stream.read_exact(&mut b)?;
+                pending = Some(b[0] != 0);
+            }
+            // KeyEvent: printable keysyms match the keycodes the SDL front-end writes
+            Some(4) => {
+                let mut b = [0; 3];
+                stream.read_exact(&mut b)?;
+                let keysym = read_u32(&mut stream)?;
+                if b[0] != 0 && (0x20..0x7f).contains(&keysym) {
+                    let _ = keys.send(keysym as u8);
+                }
+            }
+            // PointerEvent
+            Some(5) => skip(&mut stream, 5)?,
+            // ClientCutText
+            Some(6) => {
+                skip(&mut stream, 3)?;
+                let len = read_u32(&mut stream)?;
+                skip(&mut stream, len as u64)?;
+            }
+            Some(other) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown RFB message {}", other),
+                ))
+            }
+            None => {}
+        }
+
+        if let Some(incremental) = pending {
+            let fb = fb.lock().unwrap();
+            if !fb.rgb.is_empty() && (!incremental || sent != Some(fb.generation)) {
+                send_update(&mut stream, &format, &fb.rgb)?;
+                sent = Some(fb.generation);
+                pending = None;
+            }
+        }
+    }
+}
+
+/// Runs the easy6502 machine headlessly, serving the display over VNC on
+/// `addr`. Keys from any client go to $FF.
+pub fn run(
+    cpu: &mut CPU,
+    cheat_list: &[Cheat],
+    addr: &str,
+    filter: Filter,
+) -> io::Result<ExitReason> {
+    let listener = TcpListener::bind(addr)?;
+    println!("VNC listening on {}", listener.local_addr()?);
+
+    let fb = Arc::new(Mutex::new(Framebuffer::default()));
+    let (keys, key_rx) = mpsc::channel();
+
+    let shared = fb.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let fb = shared.clone();
+            let keys = keys.clone();
+            thread::spawn(move || {
+                let peer = stream.peer_addr();
+                if let Err(e) = serve(stream, fb, keys) {
+                    println!("VNC client {:?}: {}", peer, e);
+                }
+            });
+        }
+    });
+
+    let mut display = Display::new(Format::Rgb24);
+    let mut rng = rand::thread_rng();
+    let mut scaled = Vec::new();
+
+    let mut patches = cheats::Patches::default();
+    let mut shown = None;
+
+    let reason = cpu.run(|cpu| {
+        while let Ok(k) = key_rx.try_recv() {
+            cpu.bus.write(0xff, k);
+        }
+        cpu.bus.write(0xfe, rng.gen_range(1, 16));
+        patches.apply(cheat_list, cpu);
+
+        // read once a frame of cycles, as the SDL front-end does
+        let frame = cpu.cycles / CYCLES_PER_FRAME;
+        if shown != Some(frame) || cpu.halted || cpu.is_jammed() {
+            shown = Some(frame);
+            display.update(cpu);
+        }
+        if display.take_dirty() {
+            filter::apply(filter, display.pixels(), WIDTH, HEIGHT, &mut scaled);
+            let mut fb = fb.lock().unwrap();
+            fb.rgb.clone_from(&scaled);
+            fb.generation += 1;
+        }
+
+        ::std::thread::sleep(std::time::Duration::new(0, 70_000));
+    });
+
+    Ok(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vnc::*;
+
+    #[test]
+    fn pixel_formats() {
+        let mut out = Vec::new();
+        PixelFormat::DEFAULT.encode(&[0x11, 0x22, 0x33], &mut out);
+        assert_eq!(out, [0x33, 0x22, 0x11, 0x00]);
+
+        // RGB565 big endian
+        let rgb565 = PixelFormat {
+            bits_per_pixel: 16,
+            big_endian: true,
+            max: [31, 63, 31],
+            shift: [11, 5, 0],
+        };
+        let parsed = PixelFormat::from_bytes(&rgb565.to_bytes()).unwrap();
+        assert_eq!(parsed, rgb565);
+
+        out.clear();
+        parsed.encode(&[255, 0, 255], &mut out);
+        assert_eq!(out, [0xf8, 0x1f]);
+    }
+
+    #[test]
+    fn handshake_and_update() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let fb = Arc::new(Mutex::new(Framebuffer {
+            rgb: vec![0xff; FB_WIDTH * FB_HEIGHT * 3],
+            generation: 1,
+        }));
+        let (keys, key_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let _ = serve(stream, fb, keys);
+        });
+
+        let mut c = TcpStream::connect(addr).unwrap();
+        let mut version = [0; 12];
+        c.read_exact(&mut version).unwrap();
+        assert_eq!(&version, b"RFB 003.008\n");
+        c.write_all(b"RFB 003.008\n").unwrap();
+
+        let mut security = [0; 2];
+        c.read_exact(&mut security).unwrap();
+        assert_eq!(security, [1, 1]);
+        c.write_all(&[1]).unwrap();
+        assert_eq!(read_u32(&mut c).unwrap(), 0);
+
+        c.write_all(&[1]).unwrap();
+        assert_eq!(read_u16(&mut c).unwrap() as usize, FB_WIDTH);
+        assert_eq!(read_u16(&mut c).unwrap() as usize, FB_HEIGHT);
+        skip(&mut c, 16).unwrap();
+        let len = read_u32(&mut c).unwrap();
+        skip(&mut c, len as u64).unwrap();
+
+        // key down 'w', then a full update request
+        c.write_all(&[4, 1, 0, 0, 0, 0, 0, b'w']).unwrap();
+        c.write_all(&[3, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+
+        let mut header = [0; 16];
+        c.read_exact(&mut header).unwrap();
+        assert_eq!(&header[..4], &[0, 0, 0, 1]);
+        let mut pixels = vec![0; FB_WIDTH * FB_HEIGHT * 4];
+        c.read_exact(&mut pixels).unwrap();
+        assert_eq!(&pixels[..4], &[0xff, 0xff, 0xff, 0x00]);
+
+        assert_eq!(key_rx.recv().unwrap(), b'w');
+    }
+
+    #[test]
+    fn rfb_37_has_no_security_result() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = handshake(&mut stream);
+        });
+
+        let mut c = TcpStream::connect(addr).unwrap();
+        skip(&mut c, 12).unwrap();
+        c.write_all(b"RFB 003.007\n").unwrap();
+
+        let mut security = [0; 2];
+        c.read_exact(&mut security).unwrap();
+        assert_eq!(security, [1, 1]);
+        c.write_all(&[1]).unwrap();
+
+        // straight on to ServerInit
+        c.write_all(&[1]).unwrap();
+        assert_eq!(read_u16(&mut c).unwrap() as usize, FB_WIDTH);
+        assert_eq!(read_u16(&mut c).unwrap() as usize, FB_HEIGHT);
+    }
+}
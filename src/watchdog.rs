@@ -0,0 +1,105 @@
+use crate::bus::{Device, Lines};
+
+/// Where the watchdog sits and how long it waits: ADDR:CYCLES, ADDR in hex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchdogSpec {
+    pub addr: u16,
+    pub cycles: u64,
+}
+
+impl std::str::FromStr for WatchdogSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("expected ADDR:CYCLES: {}", s);
+        let (addr, cycles) = s.split_once(':').ok_or_else(bad)?;
+
+        Ok(WatchdogSpec {
+            addr: u16::from_str_radix(addr.trim_start_matches('$'), 16).map_err(|_| bad())?,
+            cycles: cycles.parse().ok().filter(|&c| c > 0).ok_or_else(bad)?,
+        })
+    }
+}
+
+/// Resets the machine unless its page is written at least once every
+/// `timeout` cycles, like the watchdogs on many arcade boards. Reads return
+/// the low byte of the cycles left. A timeout of 0 resets every cycle.
+pub struct Watchdog {
+    timeout: u64,
+    remaining: u64,
+}
+
+impl Watchdog {
+    pub fn new(timeout: u64) -> Self {
+        Watchdog {
+            timeout,
+            remaining: timeout,
+        }
+    }
+}
+
+impl Device for Watchdog {
+    fn read(&mut self, _adr: u16) -> u8 {
+        self.remaining as u8
+    }
+
+    fn write(&mut self, _adr: u16, _data: u8) {
+        self.remaining = self.timeout;
+    }
+
+    fn peek(&self, _adr: u16) -> u8 {
+        self.remaining as u8
+    }
+
+    fn tick(&mut self) -> Lines {
+        self.remaining = self.remaining.saturating_sub(1);
+        let reset = self.remaining == 0;
+        if reset {
+            self.remaining = self.timeout;
+        }
+        Lines {
+            reset,
+            ..Lines::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::CPU;
+    use crate::watchdog::*;
+
+    #[test]
+    fn resets_unless_petted() {
+        assert_eq!(
+            "d000:5000".parse(),
+            Ok(WatchdogSpec {
+                addr: 0xD000,
+                cycles: 5000
+            })
+        );
+        assert!("d000:0".parse::<WatchdogSpec>().is_err());
+
+        // INX, JMP $0600: never pets the dog
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.map(0xD0..=0xD0, Box::new(Watchdog::new(100)));
+        cpu.load(vec![0xe8, 0x4c, 0x00, 0x06]);
+        for _ in 0..40 {
+            cpu.exec();
+        }
+        assert!(cpu.reg.x < 40);
+
+        // INX, STA $D000, JMP $0600: pets it every 9 cycles
+        let mut cpu = CPU::new(Bus::default());
+        cpu.bus.map(0xD0..=0xD0, Box::new(Watchdog::new(100)));
+        cpu.load(vec![0xe8, 0x8d, 0x00, 0xd0, 0x4c, 0x00, 0x06]);
+        for _ in 0..120 {
+            cpu.exec();
+        }
+        assert_eq!(cpu.reg.x, 40);
+
+        let mut dog = Watchdog::new(0);
+        assert!(dog.tick().reset && dog.tick().reset);
+    }
+}